@@ -62,6 +62,7 @@ impl Handler for CliHandler {
     fn handle<'a>(&'a self, peer: Peer<Self>, client: ClientPtr, msg: Message) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>>
     where
         Self: Sync + 'a {
+        #[allow(clippy::match_single_binding)]
         async fn run(_self: &CliHandler, _peer: Peer<CliHandler>, _client: ClientPtr, msg: Message) {
             match msg {
                 _ => {}
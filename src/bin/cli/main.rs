@@ -22,6 +22,9 @@ struct Args {
     /// the wallet of the user
     #[clap(long, short)]
     wallet: String,
+    /// the genesis file of the network
+    #[clap(long, short)]
+    genesis: Option<String>,
 }
 
 
@@ -43,6 +46,8 @@ async fn main() {
             port: args.port,
             reconnect: true,
         }],
+        genesis: args.genesis.clone(),
+        ..Default::default()
     };
     let peer = Peer::<CliHandler>::new(config);
 
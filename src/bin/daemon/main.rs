@@ -1,17 +1,71 @@
-use clap::Parser;
+use std::{fs::File, io::{BufReader, BufWriter}, path::Path};
+
+use clap::{Parser, Subcommand};
 
 use mccloud::{
+    blockchain::{export, genesis, Blockchain, Data},
     config::Config,
+    key::Key,
     network::{
         peer::Peer,
         handler::daemon::DaemonHandler,
-    }, 
+    },
 };
 
 #[derive(Parser)]
 struct Args {
     #[clap(long, short)]
     config: String,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Creates the genesis block of a new network in the genesis file of the config.
+    Init {
+        /// data items to include in the genesis block
+        #[clap(long, short)]
+        data: Vec<String>,
+    },
+    /// Writes the main chain to a portable file.
+    Export {
+        #[clap(long, short)]
+        file: String,
+    },
+    /// Reads and validates the blocks of an exported file into the chain.
+    Import {
+        #[clap(long, short)]
+        file: String,
+    },
+}
+
+fn run(config: &Config, command: Command) -> Result<(), anyhow::Error> {
+    match command {
+        Command::Init { data } => {
+            let path = config.genesis.as_ref()
+                .ok_or_else(|| anyhow::anyhow!("no genesis file in the config"))?;
+            let key = Key::new();
+            let data = data.into_iter().map(|d| Data::build(&key, d.into_bytes())).collect();
+            let block = genesis::create(&key, data);
+
+            genesis::save(&block, Path::new(path))?;
+            log::info!("created genesis block in {}\nnetwork: {}", path, hex::encode(&block.header.hash));
+        }
+        Command::Export { file } => {
+            let blockchain = Blockchain::open(config)?;
+            let count = export::export(&blockchain, &mut BufWriter::new(File::create(&file)?))?;
+            log::info!("exported {} blocks to {}", count, file);
+        }
+        Command::Import { file } => {
+            let mut blockchain = Blockchain::open(config)?;
+            let count = export::import(&mut blockchain, &mut BufReader::new(File::open(&file)?))?;
+            blockchain.flush();
+            log::info!("imported {} blocks from {}", count, file);
+        }
+    }
+
+    Ok(())
 }
 
 #[tokio::main]
@@ -22,6 +76,15 @@ async fn main() {
     env_logger::init_from_env(env);
 
     let config = Config::load(&args.config).await.unwrap();
+
+    if let Some(command) = args.command {
+        if let Err(e) = run(&config, command) {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+        return
+    }
+
     let peer = Peer::<DaemonHandler>::new(config);
 
     if let Err(e) = peer.listen().await {
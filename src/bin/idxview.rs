@@ -1,27 +1,258 @@
-use std::collections::HashMap;
-
 use clap::Parser;
+use serde_json::{json, Value};
+
+use mccloud::{
+    blockchain::{Block, BlockHeader, BlockStore, Data, store},
+    config::StoreKind,
+    highlander::GameResult,
+    key::PubKey,
+};
+
+#[derive(clap::ArgEnum, Clone, Copy)]
+enum Payload {
+    Hex,
+    Utf8,
+    Json,
+}
 
+/// explores the blockchain of a node folder
 #[derive(Parser)]
 struct Args {
+    /// the folder of the node
     #[clap(long, short)]
-    file: String,
+    folder: String,
+    /// the storage backend of the node
+    #[clap(long, arg_enum, default_value = "file")]
+    store: Store,
+    /// show the main chain block at this height in detail
+    #[clap(long)]
+    height: Option<usize>,
+    /// show the block with this hex encoded hash in detail
+    #[clap(long)]
+    block: Option<String>,
+    /// how to show the payload of the data items
+    #[clap(long, short, arg_enum, default_value = "utf8")]
+    payload: Payload,
+    /// print JSON instead of text
+    #[clap(long)]
+    json: bool,
 }
 
-fn main() {
-    let args = Args::parse();
+#[derive(clap::ArgEnum, Clone, Copy)]
+enum Store {
+    File,
+    Sled,
+}
+
+fn short(key: &[u8]) -> String {
+    let key = hex::encode(key);
+    key[..key.len().min(16)].to_owned()
+}
+
+fn payload(data: &[u8], format: Payload) -> Value {
+    match format {
+        Payload::Hex => Value::String(hex::encode(data)),
+        Payload::Utf8 => Value::String(String::from_utf8_lossy(data).into_owned()),
+        Payload::Json => serde_json::from_slice(data).unwrap_or_else(|_| Value::String(hex::encode(data))),
+    }
+}
+
+fn header_json(header: &BlockHeader) -> Value {
+    json!({
+        "height": header.height,
+        "hash": hex::encode(&header.hash),
+        "parent": hex::encode(&header.parent),
+        "timestamp": header.timestamp,
+        "root": hex::encode(&header.root),
+        "proof": hex::encode(&header.proof),
+        "joins": hex::encode(&header.joins),
+        "members": header.members.iter().map(hex::encode).collect::<Vec<_>>(),
+        "author": hex::encode(&header.author),
+    })
+}
+
+fn data_json(data: &Data, format: Payload) -> Value {
+    json!({
+        "hash": hex::encode(data.hash()),
+        "author": hex::encode(&data.author),
+        "size": data.data.len(),
+        "payload": payload(&data.data, format),
+    })
+}
+
+fn game_json(game: &GameResult) -> Value {
+    let levels: Vec<Vec<Option<String>>> = game.levels()
+        .into_iter()
+        .map(|l| l.iter().map(|k| k.as_ref().map(hex::encode)).collect())
+        .collect();
+    let mut roster: Vec<(&PubKey, &Vec<u8>)> = game.roster.iter().collect();
+    roster.sort();
+
+    json!({
+        "winner": hex::encode(&game.winner),
+        "author": hex::encode(&game.author),
+        "epoch": game.epoch,
+        "roster_hash": hex::encode(&game.roster_hash),
+        "roster": roster.into_iter().map(|(k, v)| json!({"key": hex::encode(k), "rounds": v})).collect::<Vec<_>>(),
+        "excluded": game.excluded.iter().map(hex::encode).collect::<Vec<_>>(),
+        "votes": game.votes.iter().map(|v| hex::encode(v.author())).collect::<Vec<_>>(),
+        "population": game.population.iter().map(hex::encode).collect::<Vec<_>>(),
+        "levels": levels,
+    })
+}
+
+///
+/// Prints the subtree of the entry `index` in round `level` with the winner on top.
+///
+fn draw(levels: &[&[Option<PubKey>]], level: usize, index: usize, prefix: &str, last: bool) {
+    let name = match levels[level].get(index) {
+        Some(Some(key)) => short(key),
+        _ => "-".to_owned(),
+    };
+    let (branch, indent) = if level + 1 == levels.len() { ("", "") }
+        else if last { ("└── ", "    ") }
+        else { ("├── ", "│   ") };
+    println!("  {}{}{}", prefix, branch, name);
+
+    if level > 0 {
+        let prefix = format!("{}{}", prefix, indent);
+        let children: Vec<usize> = [index * 2, index * 2 + 1]
+            .into_iter()
+            .filter(|i| *i < levels[level - 1].len())
+            .collect();
+        for (n, child) in children.iter().enumerate() {
+            draw(levels, level - 1, *child, &prefix, n + 1 == children.len());
+        }
+    }
+}
 
-    let data = std::fs::read(args.file).unwrap();
-    let index: HashMap<Vec<u8>, (u64, u64)> = rmp_serde::from_slice(&data).unwrap();
-    let mut sorted = Vec::new();
+fn size(block: &Block) -> usize {
+    rmp_serde::to_vec_named(block).map(|v| v.len()).unwrap_or(0)
+}
+
+fn list(st: &dyn BlockStore, json: bool) {
+    let height = st.header(&st.tip()).unwrap().map(|h| h.height).unwrap_or(0);
+    let mut rows = Vec::new();
+
+    if !json {
+        println!("{:>8}  {:64}  {:16}  {:16}  {:>5}  {:>8}", "height", "hash", "author", "winner", "data", "size");
+    }
+
+    for h in 1..=height {
+        let hash = st.hash_at(h).unwrap().unwrap_or_default();
+        let header = match st.header(&hash).unwrap() {
+            Some(header) => header,
+            None => continue,
+        };
+        let block = st.get(&hash).unwrap();
+
+        if json {
+            let mut row = header_json(&header);
+            row["winner"] = block.as_ref().and_then(|b| b.proof.game()).map(|g| Value::String(hex::encode(&g.winner))).unwrap_or(Value::Null);
+            row["data"] = block.as_ref().map(|b| json!(b.data.len())).unwrap_or(Value::Null);
+            row["size"] = block.as_ref().map(|b| json!(size(b))).unwrap_or(Value::Null);
+            rows.push(row);
+        }
+        else {
+            let (winner, data, size) = match &block {
+                Some(b) => (
+                    b.proof.game().map(|g| short(&g.winner)).unwrap_or_else(|| "-".to_owned()),
+                    b.data.len().to_string(),
+                    size(b).to_string(),
+                ),
+                None => ("pruned".to_owned(), "-".to_owned(), "-".to_owned()),
+            };
+            println!("{:>8}  {:64}  {:16}  {:16}  {:>5}  {:>8}", h, hex::encode(&hash), short(&header.author), winner, data, size);
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rows).unwrap());
+    }
+}
+
+fn show(st: &dyn BlockStore, hash: &[u8], format: Payload, json: bool) {
+    let header = match st.header(hash).unwrap() {
+        Some(header) => header,
+        None => {
+            eprintln!("unknown block {}", hex::encode(hash));
+            std::process::exit(1);
+        }
+    };
+    let block = st.get(hash).unwrap();
 
-    for (k, v) in index.iter() {
-        sorted.push((k, v.0, v.1));
+    if json {
+        let mut value = header_json(&header);
+        if let Some(block) = &block {
+            value["size"] = json!(size(block));
+            if let Some(game) = block.proof.game() {
+                value["game_result"] = game_json(game);
+            }
+            value["data"] = block.data.iter().map(|d| data_json(d, format)).collect();
+        }
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return
     }
 
-    sorted.sort_by_cached_key(|x| x.1);
+    println!("height:    {}", header.height);
+    println!("hash:      {}", hex::encode(&header.hash));
+    println!("parent:    {}", hex::encode(&header.parent));
+    println!("timestamp: {}", header.timestamp);
+    println!("root:      {}", hex::encode(&header.root));
+    println!("author:    {}", hex::encode(&header.author));
+
+    let block = match block {
+        Some(block) => block,
+        None => {
+            println!("the body of the block was pruned");
+            return
+        }
+    };
 
-    for k in sorted {
-        println!("  {} -> {} : {}", hex::encode(k.0), k.1, k.2);
+    println!("size:      {}", size(&block));
+    if let Some(game) = block.proof.game() {
+        println!("winner:    {}", hex::encode(&game.winner));
+        println!("game:");
+        let levels = game.levels();
+        if !levels.is_empty() {
+            draw(&levels, levels.len() - 1, 0, "", true);
+        }
     }
-}
\ No newline at end of file
+
+    println!("data:      {}", block.data.len());
+    for (i, d) in block.data.iter().enumerate() {
+        println!("  [{}] {}", i, hex::encode(d.hash()));
+        println!("      author:  {}", hex::encode(&d.author));
+        match payload(&d.data, format) {
+            Value::String(s) => println!("      payload: {}", s),
+            v => println!("      payload: {}", serde_json::to_string_pretty(&v).unwrap().replace('\n', "\n      ")),
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let kind = match args.store {
+        Store::File => StoreKind::File,
+        Store::Sled => StoreKind::Sled,
+    };
+    let st = match store::open_read_only(&kind, &args.folder) {
+        Ok(st) => st,
+        Err(e) => {
+            eprintln!("could not open {}: {}", args.folder, e);
+            std::process::exit(1);
+        }
+    };
+
+    let hash = match (args.height, &args.block) {
+        (_, Some(hash)) => Some(hex::decode(hash).unwrap()),
+        (Some(height), None) => Some(st.hash_at(height).unwrap().unwrap_or_default()),
+        (None, None) => None,
+    };
+
+    match hash {
+        Some(hash) => show(st.as_ref(), &hash, args.payload, args.json),
+        None => list(st.as_ref(), args.json),
+    }
+}
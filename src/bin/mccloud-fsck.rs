@@ -0,0 +1,80 @@
+use std::path::Path;
+
+use clap::Parser;
+use mccloud::blockchain::fsck;
+
+/// checks the block files of a node folder
+#[derive(Parser)]
+struct Args {
+    /// the folder of the node
+    #[clap(long, short)]
+    folder: String,
+    /// rebuild the index files from bc.db and bc.hdr
+    #[clap(long)]
+    rebuild_index: bool,
+}
+
+fn print_hashes(title: &str, hashes: &[Vec<u8>]) {
+    if !hashes.is_empty() {
+        println!("{}: {}", title, hashes.len());
+        for hash in hashes {
+            println!("  {}", hex::encode(hash));
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let folder = Path::new(&args.folder);
+
+    if args.rebuild_index {
+        let skipped = fsck::rebuild_index(folder).unwrap();
+        println!("rebuilt index of {}", folder.display());
+        for pos in skipped {
+            println!("skipped record at {}", pos);
+        }
+    }
+
+    let report = fsck::check(folder).unwrap();
+
+    println!("blocks:  {}", report.blocks);
+    println!("pruned:  {}", report.headers);
+    println!("tip:     {} [{}]", hex::encode(&report.tip), report.height);
+
+    for (pos, reason) in &report.invalid {
+        println!("invalid record at {}: {}", pos, reason);
+    }
+    if let Some((pos, len)) = report.torn {
+        println!("torn tail of bc.db at {}, {} bytes", pos, len);
+    }
+    for (pos, reason) in &report.invalid_headers {
+        println!("invalid header record at {}: {}", pos, reason);
+    }
+    if let Some((pos, len)) = report.torn_headers {
+        println!("torn tail of bc.hdr at {}, {} bytes", pos, len);
+    }
+    print_hashes("duplicates", &report.duplicates);
+    if report.roots.len() > 1 {
+        print_hashes("first blocks", &report.roots);
+    }
+    print_hashes("orphans", &report.orphans);
+    print_hashes("side branch blocks", &report.stale);
+
+    for entry in &report.past_eof {
+        println!("index entry past the end: {} -> {} : {}", hex::encode(&entry.header.hash), entry.pos, entry.len);
+    }
+    for pos in &report.invalid_index {
+        println!("invalid index record at {}", pos);
+    }
+    if let Some((pos, len)) = report.torn_index {
+        println!("torn tail of bc.idx at {}, {} bytes", pos, len);
+    }
+
+    if report.is_clean() {
+        println!("ok");
+    }
+    else {
+        println!("defects found");
+        std::process::exit(1);
+    }
+}
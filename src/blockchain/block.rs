@@ -1,72 +1,317 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use serde::{Serialize, Deserialize};
 use sha2::{Digest, Sha256};
 
 use crate::{highlander::GameResult, key::{PubKey, Key}};
 
-use super::data::Data;
+use super::{data::Data, join::{Join, joins_hash}, merkle};
+
+///
+/// How far in milliseconds the timestamp of a block may lie ahead of the local clock.
+///
+/// It is well below [FALLBACK_DELAY], so a fallback producer cannot skip its delay by a timestamp
+/// in the future.
+///
+pub const MAX_FUTURE_DRIFT: u64 = 5_000;
 
+///
+/// The milliseconds a fallback producer waits for each producer ranked before it.
+///
+pub const FALLBACK_DELAY: u64 = 30_000;
 
+///
+/// The largest size in bytes of an encoded block, larger records of an export are not read.
+///
+pub const MAX_BLOCK_SIZE: usize = 16 * 1024 * 1024;
+
+///
+/// The current time in milliseconds since the UNIX epoch.
+///
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+///
+/// What shows that the author of a [Block] was allowed to produce it.
+///
+/// The [Consensus](crate::consensus::Consensus) of the network checks the proof, the block itself
+/// only commits to it.
+///
 #[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Block {
+#[allow(clippy::large_enum_variant)]
+pub enum Proof {
+    /// The result of the tournament the author played for the block.
+    Game(GameResult),
+    /// The producer follows from the chain and the nodes alone, like by its turn.
+    Elected,
+}
+
+impl Proof {
+    ///
+    /// The hash the header commits to, empty if there is nothing to prove.
+    ///
+    pub fn hash(&self) -> Vec<u8> {
+        match self {
+            Proof::Game(game) => game.hash(),
+            Proof::Elected => Vec::new(),
+        }
+    }
+
+    ///
+    /// The game result, if the producer was decided by a tournament.
+    ///
+    pub fn game(&self) -> Option<&GameResult> {
+        match self {
+            Proof::Game(game) => Some(game),
+            Proof::Elected => None,
+        }
+    }
+
+    ///
+    /// The nodes the producer was decided among, for the block of `author`, sorted by key.
+    ///
+    /// A committee is drawn from all members, otherwise the whole roster played.
+    ///
+    pub fn members(&self, author: &[u8]) -> Vec<PubKey> {
+        let mut members = match self {
+            Proof::Game(game) if !game.population.is_empty() => game.population.clone(),
+            Proof::Game(game) => game.roster.keys().chain(game.excluded.iter()).cloned().collect(),
+            Proof::Elected => vec![author.to_vec()],
+        };
+        members.sort();
+
+        members
+    }
+
+    ///
+    /// The rank of `author` as a producer, 0 for the first one to produce the block.
+    ///
+    pub fn rank(&self, author: &[u8]) -> usize {
+        match self {
+            Proof::Game(game) => game.rank(author).unwrap_or(usize::MAX),
+            Proof::Elected => 0,
+        }
+    }
+}
+
+///
+/// The header of a [Block].
+///
+/// The header commits to the body through the Merkle root of the data and the hashes of the proof
+/// and the joins, so it can be shared and validated on its own.
+///
+/// It names the members which play the round for the next block, so the roster of a round
+/// follows from the chain, also after the bodies are pruned.
+///
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlockHeader {
     pub parent: Vec<u8>,
-    pub game: GameResult,
-    pub data: Vec<Data>,
+    /// The number of blocks from the first block up to and including this one.
+    pub height: usize,
+    /// The creation time in milliseconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// The rank of the author as a producer of the block, 0 if it was the first one to produce it.
+    pub rank: usize,
+    /// The Merkle root over the hashes of the [Data] items.
+    pub root: Vec<u8>,
+    /// The hash of the [Proof].
+    pub proof: Vec<u8>,
+    /// The hash of the [Joins](Join).
+    pub joins: Vec<u8>,
+    /// The nodes which play the round for the next block, sorted by key.
+    pub members: Vec<PubKey>,
     /// The public key of the node which created the block.
     pub author: PubKey,
-    /// The hash of the block data.
+    /// The hash of the header.
     pub hash: Vec<u8>,
-    /// The sign for the [GameResult] and [Data] blocks.
+    /// The sign of the author over the header hash.
     pub sign: Vec<u8>,
 }
 
-pub fn block_hash(parent: &Vec<u8>, author: &Vec<u8>, data: &Vec<Data>, game: &GameResult) -> Vec<u8> {
+///
+/// Hashes a field of variable length behind its length, so no bytes move from one field to the next.
+///
+fn update_field(sha: &mut Sha256, field: &[u8]) {
+    sha.update((field.len() as u64).to_be_bytes());
+    sha.update(field);
+}
+
+///
+/// The hash over all fields of `header`, besides the hash and the signature.
+///
+pub fn block_hash(header: &BlockHeader) -> Vec<u8> {
     let mut sha = Sha256::new();
 
-    for d in data {
-        sha.update(&d.data);
-        sha.update(&d.author);
-        sha.update(&d.sign);
+    update_field(&mut sha, &header.parent);
+    sha.update((header.height as u64).to_be_bytes());
+    sha.update(header.timestamp.to_be_bytes());
+    sha.update((header.rank as u64).to_be_bytes());
+    update_field(&mut sha, &header.root);
+    update_field(&mut sha, &header.proof);
+    update_field(&mut sha, &header.joins);
+    sha.update((header.members.len() as u64).to_be_bytes());
+    for id in &header.members {
+        update_field(&mut sha, id);
     }
+    update_field(&mut sha, &header.author);
+
+    sha.finalize().to_vec()
+}
+
+///
+/// The members after a block with `proof` and `joins` on a parent which names `members`, sorted by key.
+///
+/// The nodes its tournament dropped leave, the joining nodes enter.
+///
+pub fn next_members(members: &[PubKey], proof: &Proof, joins: &[Join]) -> Vec<PubKey> {
+    let excluded = proof.game().map(|game| game.excluded.as_slice()).unwrap_or_default();
+    let mut next: Vec<PubKey> = members.iter()
+        .filter(|id| !excluded.contains(id))
+        .chain(joins.iter().map(|join| &join.author))
+        .cloned()
+        .collect();
+    next.sort();
+    next.dedup();
+
+    next
+}
+
+///
+/// The Merkle root over the hashes of `data`.
+///
+pub fn data_root(data: &[Data]) -> Vec<u8> {
+    let hashes: Vec<Vec<u8>> = data.iter().map(Data::hash).collect();
+    merkle::root(&hashes)
+}
 
-    for id in &game.tree {
-        if let Some(ref id) = id {
-            sha.update(id);
+impl BlockHeader {
+    ///
+    /// Checks the hash and the signature of the header.
+    ///
+    pub fn validate(&self) -> bool {
+        let hash = block_hash(self);
+
+        if self.hash == hash {
+            match Key::validate(&hash, &self.author, &self.sign) {
+                Ok(_) => true,
+                Err(e) => {
+                    log::error!("unvalid block: {}", e);
+                    false
+                }
+            }
+        }
+        else {
+            log::error!(
+                "hashes do not match:\nhash     : {}\ncalc hash: {}",
+                hex::encode(&self.hash),
+                hex::encode(hash),
+            );
+            false
         }
     }
 
-    // if we do not sort the keys, the hashing will be diffirent on another machine
-    let mut roster_keys: Vec<&PubKey> = game.roster.keys().collect();
-    roster_keys.sort();
-    for id in roster_keys {
-        let v = &game.roster[id];
-        sha.update(id);
-        sha.update(v);
+    ///
+    /// Checks the height and timestamp of the header against its parent and the local clock.
+    ///
+    /// For the first block of a chain, `parent_height` and `parent_timestamp` are `0`.
+    ///
+    pub fn validate_successor(&self, parent_height: usize, parent_timestamp: u64) -> bool {
+        if self.height != parent_height + 1 {
+            log::error!(
+                "block height does not follow its parent:\nnode:   {}\nheight: {}\nparent: {}",
+                hex::encode(&self.hash),
+                self.height,
+                parent_height,
+            );
+            false
+        }
+        else if self.timestamp < parent_timestamp || self.timestamp > now() + MAX_FUTURE_DRIFT {
+            log::error!(
+                "block timestamp out of bounds:\nnode:      {}\ntimestamp: {}\nparent:    {}",
+                hex::encode(&self.hash),
+                self.timestamp,
+                parent_timestamp,
+            );
+            false
+        }
+        else {
+            true
+        }
     }
+}
 
-    sha.update(&game.winner);
-
-    sha.update(parent);
-    sha.update(author);
-
-    sha.finalize().to_vec()
+///
+/// A [BlockHeader] together with its body, the [Proof] and the [Data] items.
+///
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub proof: Proof,
+    pub data: Vec<Data>,
+    pub joins: Vec<Join>,
 }
 
 impl Block {
-    pub fn build(parent: &Vec<u8>, game: GameResult, key: &Key, data: Vec<Data>) -> Block {
+    ///
+    /// Builds a block without joins, whose members are the nodes its proof was decided among.
+    ///
+    /// This fits the first block of a chain, a block on a parent follows its members with [Block::build_on].
+    ///
+    pub fn build(parent: &[u8], height: usize, timestamp: u64, proof: Proof, key: &Key, data: Vec<Data>) -> Block {
         let author = key.public_key.clone();
-        let hash = block_hash(parent, &author, &data, &game);
-        let sign = key.sign(&hash).unwrap();
+        let members = next_members(&proof.members(&author), &proof, &[]);
 
-        Block {
-            parent: parent.clone(),
-            game,
+        let mut block = Block {
+            header: BlockHeader {
+                parent: parent.to_vec(),
+                height,
+                timestamp,
+                rank: proof.rank(&author),
+                root: data_root(&data),
+                proof: proof.hash(),
+                joins: joins_hash(&[]),
+                members,
+                author,
+                hash: Vec::new(),
+                sign: Vec::new(),
+            },
+            proof,
             data,
-            author,
-            hash: hash.to_vec(),
-            sign,
-        }
+            joins: Vec::new(),
+        };
+        block.sign(key);
+
+        block
+    }
+
+    ///
+    /// Builds the block on `parent`, which takes in `joins`, the members follow from the ones of the parent.
+    ///
+    pub fn build_on(parent: &BlockHeader, timestamp: u64, proof: Proof, key: &Key, data: Vec<Data>, joins: Vec<Join>) -> Block {
+        let mut block = Self::build(&parent.hash, parent.height + 1, timestamp, proof, key, data);
+
+        block.header.members = next_members(&parent.members, &block.proof, &joins);
+        block.header.joins = joins_hash(&joins);
+        block.joins = joins;
+        block.sign(key);
+
+        block
+    }
+
+    fn sign(&mut self, key: &Key) {
+        self.header.hash = block_hash(&self.header);
+        self.header.sign = key.sign(&self.header.hash).unwrap();
     }
 
+    ///
+    /// Checks the data, that the header commits to the body and the header itself.
+    ///
+    /// Whether the author was allowed to produce the block is up to the [Consensus](crate::consensus::Consensus).
+    ///
     pub fn validate(&self) -> bool {
         for d in &self.data {
             if !d.validate() {
@@ -74,24 +319,54 @@ impl Block {
             }
         }
 
-        let hash = block_hash(&self.parent, &self.author, &self.data, &self.game);
+        if self.header.root != data_root(&self.data) {
+            log::error!("data root does not match block {}", hex::encode(&self.header.hash));
+            return false
+        }
 
-        if self.hash == hash {
-            match Key::validate(&hash, &self.author, &self.sign) {
-                Ok(_) => true,
-                Err(e) => {
-                    log::error!("unvalid block: {}", e);
-                    false
-                }
-            }
+        if self.header.proof != self.proof.hash() {
+            log::error!("proof hash does not match block {}", hex::encode(&self.header.hash));
+            return false
         }
-        else {
+
+        if self.header.joins != joins_hash(&self.joins) || !self.joins.iter().all(Join::validate) {
+            log::error!("joins do not match block {}", hex::encode(&self.header.hash));
+            return false
+        }
+
+        if self.header.rank != self.proof.rank(&self.header.author) {
+            log::error!("rank does not match block {}", hex::encode(&self.header.hash));
+            return false
+        }
+
+        self.header.validate()
+    }
+
+    ///
+    /// Checks that the block names the members which follow from the ones of `parent`.
+    ///
+    /// A join is only taken in within its window, once and of a node which is not a member yet.
+    ///
+    pub fn validate_members(&self, parent: &BlockHeader) -> bool {
+        let height = self.header.height;
+        let taken = |i: usize, join: &Join| self.joins[..i].iter().any(|j| j.author == join.author);
+        let refused = self.joins.iter().enumerate()
+            .find(|(i, j)| !j.is_open(height) || parent.members.contains(&j.author) || taken(*i, j));
+        if let Some((_, join)) = refused {
             log::error!(
-                "hashes do not match:\nhash     : {}\ncalc hash: {}",
-                hex::encode(&self.hash),
-                hex::encode(hash),
+                "block {} takes in a join it may not:\nnode:   {}\nheight: {}",
+                hex::encode(&self.header.hash),
+                hex::encode(&join.author),
+                join.height,
             );
-            false
+            return false
+        }
+
+        if self.header.members != next_members(&parent.members, &self.proof, &self.joins) {
+            log::error!("block {} names other members than follow from its parent", hex::encode(&self.header.hash));
+            return false
         }
+
+        true
     }
-}
\ No newline at end of file
+}
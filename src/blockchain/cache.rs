@@ -0,0 +1,87 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::block::{Block, BlockHeader};
+
+
+///
+/// A least recently used cache of blocks, which also remembers the main chain height of its blocks.
+///
+pub struct BlockCache {
+    capacity: usize,
+    blocks: HashMap<Vec<u8>, Block>,
+    /// The cached hashes, from the least to the most recently used.
+    order: VecDeque<Vec<u8>>,
+    /// The hashes of cached main chain blocks by height.
+    heights: HashMap<usize, Vec<u8>>,
+}
+
+impl BlockCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            blocks: HashMap::new(),
+            order: VecDeque::new(),
+            heights: HashMap::new(),
+        }
+    }
+
+    fn touch(&mut self, hash: &[u8]) {
+        if let Some(i) = self.order.iter().position(|h| h == hash) {
+            if let Some(hash) = self.order.remove(i) {
+                self.order.push_back(hash);
+            }
+        }
+    }
+
+    pub fn get(&mut self, hash: &[u8]) -> Option<Block> {
+        let block = self.blocks.get(hash).cloned()?;
+        self.touch(hash);
+        Some(block)
+    }
+
+    pub fn header(&mut self, hash: &[u8]) -> Option<BlockHeader> {
+        let header = self.blocks.get(hash)?.header.clone();
+        self.touch(hash);
+        Some(header)
+    }
+
+    pub fn get_by_height(&mut self, height: usize) -> Option<Block> {
+        let hash = self.heights.get(&height)?.clone();
+        self.get(&hash)
+    }
+
+    ///
+    /// Adds `block`, `main` tells if the block is on the main chain.
+    ///
+    pub fn insert(&mut self, block: Block, main: bool) {
+        let hash = block.header.hash.clone();
+
+        if main {
+            self.heights.insert(block.header.height, hash.clone());
+        }
+
+        if self.blocks.insert(hash.clone(), block).is_some() {
+            self.touch(&hash);
+            return
+        }
+
+        self.order.push_back(hash);
+
+        while self.order.len() > self.capacity {
+            if let Some(old) = self.order.pop_front() {
+                if let Some(block) = self.blocks.remove(&old) {
+                    if self.heights.get(&block.header.height) == Some(&old) {
+                        self.heights.remove(&block.header.height);
+                    }
+                }
+            }
+        }
+    }
+
+    ///
+    /// Forgets the main chain heights above `height`, after the tip moved to another branch.
+    ///
+    pub fn truncate(&mut self, height: usize) {
+        self.heights.retain(|h, _| *h <= height);
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use crate::key::{PubKey, Key};
 
@@ -21,6 +22,17 @@ impl Data {
         }
     }
 
+    ///
+    /// The hash identifying this data chunk.
+    ///
+    pub fn hash(&self) -> Vec<u8> {
+        let mut sha = Sha256::new();
+        sha.update(&self.data);
+        sha.update(&self.author);
+        sha.update(&self.sign);
+        sha.finalize().to_vec()
+    }
+
     pub fn validate(&self) -> bool {
         match Key::validate(&self.data, &self.author, &self.sign) {
             Ok(_) => true,
@@ -0,0 +1,126 @@
+use std::io::{ErrorKind, Read, Write};
+
+use serde::{Serialize, Deserialize};
+
+use super::{Blockchain, block::{Block, MAX_BLOCK_SIZE}};
+
+/// The name of the export format, the first field of every export.
+pub const FORMAT: &str = "mccloud-chain";
+/// The version of the export format.
+pub const VERSION: u32 = 1;
+
+///
+/// The first record of an export.
+///
+/// An export is a sequence of records, each a `u32` big endian length followed by a msgpack map
+/// with named fields.
+/// The header is followed by the blocks of the main chain in height order.
+///
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExportHeader {
+    pub format: String,
+    pub version: u32,
+    /// The number of exported blocks.
+    pub height: usize,
+    /// The hash of the last exported block.
+    #[serde(with="serde_bytes")]
+    pub tip: Vec<u8>,
+}
+
+fn write_record<W: Write, T: Serialize>(writer: &mut W, value: &T) -> Result<(), anyhow::Error> {
+    let payload = rmp_serde::to_vec_named(value)?;
+    if payload.len() > MAX_BLOCK_SIZE {
+        anyhow::bail!("record of {} bytes is larger than a block may be", payload.len());
+    }
+    writer.write_all(&(payload.len() as u32).to_be_bytes())?;
+    writer.write_all(&payload)?;
+    Ok(())
+}
+
+///
+/// Reads the next record, `None` at the end of the input.
+///
+/// The length is checked against [MAX_BLOCK_SIZE] before anything is allocated for the record.
+///
+fn read_record<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>, anyhow::Error> {
+    let mut size = [0u8; 4];
+    match reader.read_exact(&mut size) {
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let size = u32::from_be_bytes(size) as usize;
+    if size > MAX_BLOCK_SIZE {
+        anyhow::bail!("record of {} bytes is larger than a block may be", size);
+    }
+
+    let mut payload = vec![0u8; size];
+    reader.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+///
+/// Writes the main chain of `blockchain` to `writer` and returns the number of blocks.
+///
+/// A pruned chain cannot be exported, as the blocks before the lowest one are missing.
+///
+pub fn export<W: Write>(blockchain: &Blockchain, writer: &mut W) -> Result<usize, anyhow::Error> {
+    if blockchain.lowest() > 1 {
+        anyhow::bail!("the chain is pruned below height {}", blockchain.lowest());
+    }
+
+    let (tip, height) = blockchain.highest_block();
+    write_record(writer, &ExportHeader {
+        format: FORMAT.to_owned(),
+        version: VERSION,
+        height,
+        tip,
+    })?;
+
+    let mut count = 0;
+    for block in blockchain.iter() {
+        write_record(writer, &block?)?;
+        count += 1;
+    }
+    writer.flush()?;
+
+    Ok(count)
+}
+
+///
+/// Reads an export from `reader` into `blockchain` and returns the number of blocks.
+///
+/// Every block is checked by [Blockchain::import_block], the import stops at the first rejected one.
+///
+pub fn import<R: Read>(blockchain: &mut Blockchain, reader: &mut R) -> Result<usize, anyhow::Error> {
+    let header: ExportHeader = match read_record(reader)? {
+        Some(record) => rmp_serde::from_slice(&record)?,
+        None => anyhow::bail!("empty export"),
+    };
+
+    if header.format != FORMAT || header.version != VERSION {
+        anyhow::bail!("unsupported export format {} version {}", header.format, header.version);
+    }
+
+    let mut count = 0;
+    let mut last = Vec::new();
+    while let Some(record) = read_record(reader)? {
+        let block: Block = rmp_serde::from_slice(&record)?;
+        let height = block.header.height;
+        last = block.header.hash.clone();
+
+        blockchain.import_block(block)
+            .map_err(|e| anyhow::anyhow!("block at height {} rejected: {}", height, e))?;
+        count += 1;
+    }
+
+    if count != header.height {
+        anyhow::bail!("export has {} blocks, but the header announces {}", count, header.height);
+    }
+    if last != header.tip {
+        anyhow::bail!("last block does not match the tip of the export {}", hex::encode(&header.tip));
+    }
+
+    Ok(count)
+}
@@ -0,0 +1,207 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use super::{
+    block::BlockHeader,
+    index::IndexEntry,
+    is_preferred,
+    journal,
+    store::{FileStore, file::decode, table::HashTable},
+};
+
+///
+/// The findings of [check] on the files of a [FileStore].
+///
+#[derive(Default, Debug)]
+pub struct Report {
+    /// The number of complete blocks in `bc.db`.
+    pub blocks: usize,
+    /// The number of pruned blocks in `bc.hdr`, of which only the header is left.
+    pub headers: usize,
+    /// Records of `bc.db` which do not decode or validate, with their position and the reason.
+    pub invalid: Vec<(u64, String)>,
+    /// Position and length of an unfinished record at the end of `bc.db`.
+    pub torn: Option<(u64, u64)>,
+    /// Records of `bc.hdr` which are no valid header, with their position and the reason.
+    pub invalid_headers: Vec<(u64, String)>,
+    /// Position and length of an unfinished record at the end of `bc.hdr`.
+    pub torn_headers: Option<(u64, u64)>,
+    /// Blocks stored more than once.
+    pub duplicates: Vec<Vec<u8>>,
+    /// Blocks starting a chain, more than one means the records do not form a single chain.
+    pub roots: Vec<Vec<u8>>,
+    /// Blocks which do not chain back to a first block, as a parent is not stored.
+    pub orphans: Vec<Vec<u8>>,
+    /// Blocks with a stored parent, which are not on the main chain.
+    pub stale: Vec<Vec<u8>>,
+    /// The tip of the main chain by the fork-choice rule and its height.
+    pub tip: Vec<u8>,
+    pub height: usize,
+    /// Entries of `bc.idx` pointing behind the end of `bc.db` or `bc.hdr`.
+    pub past_eof: Vec<IndexEntry>,
+    /// Records of `bc.idx` which do not decode, with their position.
+    pub invalid_index: Vec<u64>,
+    /// Position and length of an unfinished record at the end of `bc.idx`.
+    pub torn_index: Option<(u64, u64)>,
+}
+
+impl Report {
+    ///
+    /// Side branches are normal, everything else is a defect.
+    ///
+    pub fn is_clean(&self) -> bool {
+        self.invalid.is_empty()
+            && self.torn.is_none()
+            && self.invalid_headers.is_empty()
+            && self.torn_headers.is_none()
+            && self.duplicates.is_empty()
+            && self.roots.len() <= 1
+            && self.orphans.is_empty()
+            && self.past_eof.is_empty()
+            && self.invalid_index.is_empty()
+            && self.torn_index.is_none()
+    }
+}
+
+fn file_len(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+///
+/// Reads every record of the data files and the index journal in `folder` without changing them.
+///
+pub fn check(folder: &Path) -> Result<Report, anyhow::Error> {
+    let mut report = Report::default();
+
+    let mut headers: HashMap<Vec<u8>, BlockHeader> = HashMap::new();
+    let mut order = Vec::new();
+    let mut positions = HashMap::new();
+
+    for (name, pruned) in [("bc.hdr", true), ("bc.db", false)] {
+        let path = folder.join(name);
+        let (records, end) = journal::read_from(&path, 0)?;
+        let len = file_len(&path);
+        let (invalid, torn) = if pruned { (&mut report.invalid_headers, &mut report.torn_headers) }
+            else { (&mut report.invalid, &mut report.torn) };
+        if end < len {
+            *torn = Some((end, len - end));
+        }
+
+        for (pos, record) in records {
+            let header = match decode(&record) {
+                Ok((header, p)) if p == pruned => header,
+                Ok(_) => {
+                    invalid.push((pos, "misplaced record".to_owned()));
+                    continue
+                }
+                Err(reason) => {
+                    invalid.push((pos, reason));
+                    continue
+                }
+            };
+
+            if pruned {
+                report.headers += 1;
+            }
+            else {
+                report.blocks += 1;
+            }
+
+            if headers.contains_key(&header.hash) {
+                report.duplicates.push(header.hash);
+                continue
+            }
+            order.push(header.hash.clone());
+            positions.insert(header.hash.clone(), (pruned, pos));
+            headers.insert(header.hash.clone(), header);
+        }
+    }
+
+    // parents come before their children in height order
+    let mut sorted = order.clone();
+    sorted.sort_by_key(|h| headers[h].height);
+    let mut rooted = HashSet::new();
+
+    for hash in &sorted {
+        let header = &headers[hash];
+        let (pruned, pos) = positions[hash];
+        let invalid = if pruned { &mut report.invalid_headers } else { &mut report.invalid };
+        if header.parent.is_empty() {
+            if header.height != 1 {
+                invalid.push((pos, format!("first block {} at height {}", hex::encode(hash), header.height)));
+            }
+            report.roots.push(hash.clone());
+        }
+        else {
+            if let Some(parent) = headers.get(&header.parent) {
+                if parent.height + 1 != header.height {
+                    invalid.push((pos, format!("block {} does not follow its parent", hex::encode(hash))));
+                }
+            }
+            if !rooted.contains(&header.parent) {
+                report.orphans.push(hash.clone());
+                continue
+            }
+        }
+        rooted.insert(hash.clone());
+
+        let rank = headers.get(&report.tip).map(|h| h.rank).unwrap_or(0);
+        if is_preferred((header.height, header.rank, &header.hash), (report.height, rank, &report.tip)) {
+            report.height = header.height;
+            report.tip = header.hash.clone();
+        }
+    }
+
+    let mut main = HashSet::new();
+    let mut current = report.tip.clone();
+    while let Some(header) = headers.get(&current) {
+        main.insert(current);
+        current = header.parent.clone();
+    }
+    let orphans: HashSet<&Vec<u8>> = report.orphans.iter().collect();
+    report.stale = order.iter()
+        .filter(|h| !main.contains(*h) && !orphans.contains(h))
+        .cloned()
+        .collect();
+
+    let idxname = folder.join("bc.idx");
+    let (records, end) = journal::read_from(&idxname, 0)?;
+    let idx_len = file_len(&idxname);
+    if end < idx_len {
+        report.torn_index = Some((end, idx_len - end));
+    }
+
+    // positions in the data file count from its front before pruning cut it off
+    let tablename = folder.join("bc.hsh");
+    let db_base = if tablename.exists() {
+        HashTable::open(&tablename).map(|t| t.marks().db_base).unwrap_or(0)
+    }
+    else {
+        0
+    };
+    let db_len = db_base + file_len(&folder.join("bc.db"));
+    let hdr_len = file_len(&folder.join("bc.hdr"));
+    let file_end = |pruned: bool| if pruned { hdr_len } else { db_len };
+
+    for (pos, record) in records {
+        match rmp_serde::from_slice::<IndexEntry>(&record) {
+            Ok(entry) if entry.pos + entry.len > file_end(entry.pruned) => report.past_eof.push(entry),
+            Ok(_) => {}
+            Err(_) => report.invalid_index.push(pos),
+        }
+    }
+
+    Ok(report)
+}
+
+///
+/// Rebuilds the index files in `folder` from the data files, see [FileStore::reindex].
+///
+/// `bc.db` and `bc.hdr` are only read, records which are not a valid block or header are skipped,
+/// those of `bc.db` are reported by their position.
+///
+pub fn rebuild_index(folder: &Path) -> Result<Vec<u64>, anyhow::Error> {
+    FileStore::reindex(folder)
+}
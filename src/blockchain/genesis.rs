@@ -0,0 +1,57 @@
+use std::path::Path;
+
+use crate::{
+    config::Config,
+    key::Key,
+};
+
+use super::{block::{Block, Proof, now}, data::Data};
+
+///
+/// Creates the genesis block of a new network, the first block every other block chains back to.
+///
+/// The genesis block is trusted by the config of every node, so it proves nothing.
+///
+pub fn create(key: &Key, data: Vec<Data>) -> Block {
+    Block::build(&[], 1, now(), Proof::Elected, key, data)
+}
+
+///
+/// Writes the genesis block to `path`, an existing file is never replaced.
+///
+pub fn save(block: &Block, path: &Path) -> Result<(), anyhow::Error> {
+    if path.exists() {
+        anyhow::bail!("genesis file {} already exists", path.display());
+    }
+    if let Some(folder) = path.parent() {
+        std::fs::create_dir_all(folder)?;
+    }
+
+    std::fs::write(path, rmp_serde::to_vec_named(block)?)?;
+    Ok(())
+}
+
+///
+/// Reads and validates the genesis block at `path`.
+///
+pub fn load(path: &Path) -> Result<Block, anyhow::Error> {
+    let block: Block = rmp_serde::from_slice(&std::fs::read(path)?)?;
+
+    if !block.header.parent.is_empty() || block.header.height != 1 || !block.validate() {
+        anyhow::bail!("invalid genesis block in {}", path.display());
+    }
+
+    Ok(block)
+}
+
+///
+/// The network identifier of `config`, which is the hash of its genesis block.
+///
+/// Nodes without a genesis block form a network with an empty identifier.
+///
+pub fn network_id(config: &Config) -> Result<Vec<u8>, anyhow::Error> {
+    match &config.genesis {
+        Some(path) => Ok(load(Path::new(path))?.header.hash),
+        None => Ok(Vec::new()),
+    }
+}
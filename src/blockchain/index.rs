@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::key::PubKey;
+
+use super::block::{Block, BlockHeader};
+
+
+///
+/// The position and header of a single block in `bc.db`.
+///
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IndexEntry {
+    /// Offset of the block in `bc.db`, counted from its front before pruning cut it off, or of the
+    /// header of a pruned block in `bc.hdr`.
+    pub pos: u64,
+    /// Length of the serialized block in bytes.
+    pub len: u64,
+    pub header: BlockHeader,
+    /// The body of the block was pruned, the record in `bc.hdr` holds only the header.
+    #[serde(default)]
+    pub pruned: bool,
+}
+
+///
+/// Where a [Data](super::Data) item is stored in the main chain.
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DataLocation {
+    /// The hash of the block holding the item.
+    pub block: Vec<u8>,
+    pub height: usize,
+    /// The position of the item in the data of the block.
+    pub position: usize,
+}
+
+///
+/// The secondary index of the [Data](super::Data) items on the main chain, by data hash and by author,
+/// as the [MemoryStore](super::store::MemoryStore) keeps it.
+///
+/// Blocks are added at the tip and removed from the tip, so the items of an author stay in chain order.
+///
+#[derive(Default)]
+pub struct DataIndex {
+    locations: HashMap<Vec<u8>, DataLocation>,
+    authors: HashMap<PubKey, Vec<Vec<u8>>>,
+}
+
+impl DataIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Adds the data of `block`, which became the new tip.
+    ///
+    pub fn add_block(&mut self, block: &Block) {
+        for (position, data) in block.data.iter().enumerate() {
+            let hash = data.hash();
+            self.authors.entry(data.author.clone()).or_default().push(hash.clone());
+            self.locations.insert(hash, DataLocation {
+                block: block.header.hash.clone(),
+                height: block.header.height,
+                position,
+            });
+        }
+    }
+
+    ///
+    /// Removes the data of `block`, which was the tip.
+    ///
+    pub fn remove_block(&mut self, block: &Block) {
+        for data in block.data.iter().rev() {
+            let hash = data.hash();
+
+            if self.locations.get(&hash).map(|l| &l.block) == Some(&block.header.hash) {
+                self.locations.remove(&hash);
+            }
+
+            if let Some(items) = self.authors.get_mut(&data.author) {
+                if let Some(i) = items.iter().rposition(|h| *h == hash) {
+                    items.remove(i);
+                }
+                if items.is_empty() {
+                    self.authors.remove(&data.author);
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, hash: &[u8]) -> Option<&DataLocation> {
+        self.locations.get(hash)
+    }
+
+    ///
+    /// The hashes of the data items of `author` in chain order.
+    ///
+    pub fn by_author(&self, author: &[u8]) -> &[Vec<u8>] {
+        self.authors.get(author).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::key::{PubKey, Key};
+
+/// The number of blocks from its height on a [Join] may be taken into.
+pub const JOIN_WINDOW: usize = 16;
+
+///
+/// The request of a node to become a member, one of the nodes the parent block names to play
+/// the round for the next block.
+///
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Join {
+    pub author: PubKey,
+    /// The height of the first block which may take the join in.
+    pub height: usize,
+    pub sign: Vec<u8>,
+}
+
+///
+/// The signed payload of a join, it cannot be taken for the data of another message.
+///
+fn payload(height: usize) -> Vec<u8> {
+    let mut sha = Sha256::new();
+    sha.update(b"join");
+    sha.update((height as u64).to_be_bytes());
+    sha.finalize().to_vec()
+}
+
+impl Join {
+    pub fn build(key: &Key, height: usize) -> Self {
+        Self {
+            author: key.public_key.clone(),
+            height,
+            sign: key.sign(&payload(height)).unwrap(),
+        }
+    }
+
+    pub fn hash(&self) -> Vec<u8> {
+        let mut sha = Sha256::new();
+        sha.update(&self.author);
+        sha.update((self.height as u64).to_be_bytes());
+        sha.update(&self.sign);
+        sha.finalize().to_vec()
+    }
+
+    pub fn validate(&self) -> bool {
+        match Key::validate(&payload(self.height), &self.author, &self.sign) {
+            Ok(_) => true,
+            Err(e) => {
+                log::error!("unvalid join: {}", e);
+                false
+            }
+        }
+    }
+
+    ///
+    /// If the block at `height` may take the join in.
+    ///
+    pub fn is_open(&self, height: usize) -> bool {
+        height >= self.height && height < self.height.saturating_add(JOIN_WINDOW)
+    }
+}
+
+///
+/// The hash over the joins of a block, which its header commits to.
+///
+pub fn joins_hash(joins: &[Join]) -> Vec<u8> {
+    let mut sha = Sha256::new();
+    for join in joins {
+        sha.update(join.hash());
+    }
+    sha.finalize().to_vec()
+}
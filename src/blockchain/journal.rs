@@ -0,0 +1,156 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// A record payload together with its position in the file.
+pub type Record = (u64, Vec<u8>);
+
+///
+/// Appends `payload` as a length prefixed record to the file at `path` and syncs it to disk.
+///
+/// Returns the position of the payload in the file.
+///
+pub fn append(path: &Path, payload: &[u8]) -> io::Result<u64> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let pos = file.seek(SeekFrom::End(0))?;
+
+    let mut buffer = Vec::with_capacity(payload.len() + 4);
+    buffer.extend((payload.len() as u32).to_be_bytes());
+    buffer.extend(payload);
+    file.write_all(&buffer)?;
+    file.sync_data()?;
+
+    Ok(pos + 4)
+}
+
+///
+/// Reads the complete records of a file one by one, together with the position of their payload.
+///
+pub struct Records {
+    reader: Option<BufReader<File>>,
+    len: u64,
+    end: u64,
+}
+
+impl Records {
+    ///
+    /// The end of the last record read.
+    ///
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+}
+
+impl Iterator for Records {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = self.reader.as_mut()?;
+        if self.end + 4 > self.len {
+            return None
+        }
+
+        let mut size = [0u8; 4];
+        if let Err(e) = reader.read_exact(&mut size) {
+            return Some(Err(e))
+        }
+        let size = u32::from_be_bytes(size) as u64;
+
+        // a torn record at the end is not complete yet
+        if self.end + 4 + size > self.len {
+            self.reader = None;
+            return None
+        }
+
+        let mut payload = vec![0u8; size as usize];
+        if let Err(e) = reader.read_exact(&mut payload) {
+            return Some(Err(e))
+        }
+        let pos = self.end + 4;
+        self.end = pos + size;
+
+        Some(Ok((pos, payload)))
+    }
+}
+
+///
+/// Iterates over the complete records of the file at `path`, starting with the record at `start`.
+///
+/// A missing file has no records.
+///
+pub fn records(path: &Path, start: u64) -> io::Result<Records> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Records { reader: None, len: 0, end: 0 }),
+        Err(e) => return Err(e),
+    };
+    let len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    reader.seek(SeekFrom::Start(start))?;
+
+    Ok(Records { reader: Some(reader), len, end: start })
+}
+
+///
+/// Reads all complete records of the file at `path`, starting with the record at `start`.
+///
+/// Returns the records together with the position of their payload, and the end of the last complete record.
+/// A missing file has no records.
+///
+pub fn read_from(path: &Path, start: u64) -> io::Result<(Vec<Record>, u64)> {
+    let mut iter = records(path, start)?;
+    let records = iter.by_ref().collect::<io::Result<Vec<Record>>>()?;
+
+    Ok((records, iter.end()))
+}
+
+///
+/// Cuts the file at `path` to `len` bytes and syncs it to disk.
+///
+pub fn truncate(path: &Path, len: u64) -> io::Result<()> {
+    let file = OpenOptions::new().write(true).open(path)?;
+    file.set_len(len)?;
+    file.sync_all()
+}
+
+///
+/// Replaces the file at `path` with the given records.
+///
+/// The records are written to a temporary file first, which is then renamed, so a crash leaves
+/// either the old or the new file.
+///
+pub fn rewrite<'a, T: Iterator<Item=&'a [u8]>>(path: &Path, records: T) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp)?;
+
+    for payload in records {
+        file.write_all(&(payload.len() as u32).to_be_bytes())?;
+        file.write_all(payload)?;
+    }
+    file.sync_all()?;
+
+    std::fs::rename(tmp, path)
+}
+
+///
+/// Cuts the first `len` bytes off the file at `path`, which must end a record.
+///
+/// The rest of the file is copied to a temporary file first, which is then renamed, so a crash
+/// leaves either the old or the new file.
+///
+pub fn drop_front(path: &Path, len: u64) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut source = File::open(path)?;
+    source.seek(SeekFrom::Start(len))?;
+
+    let mut file = File::create(&tmp)?;
+    io::copy(&mut source, &mut file)?;
+    file.sync_all()?;
+
+    std::fs::rename(tmp, path)
+}
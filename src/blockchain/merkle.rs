@@ -0,0 +1,138 @@
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use super::block::BlockHeader;
+
+
+fn leaf(hash: &[u8]) -> Vec<u8> {
+    let mut sha = Sha256::new();
+    sha.update([0u8]);
+    sha.update(hash);
+    sha.finalize().to_vec()
+}
+
+fn node(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut sha = Sha256::new();
+    sha.update([1u8]);
+    sha.update(left);
+    sha.update(right);
+    sha.finalize().to_vec()
+}
+
+fn next_level(level: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [l, r] => node(l, r),
+            [l] => l.clone(),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+///
+/// Computes the Merkle root over the given leaf hashes.
+///
+/// Leaves and inner nodes are hashed with different prefixes, so a leaf can never pass as an inner node.
+/// A node without a sibling is moved up a level unchanged.
+/// The root of no leaves is the hash of the empty input.
+///
+pub fn root(hashes: &[Vec<u8>]) -> Vec<u8> {
+    if hashes.is_empty() {
+        return Sha256::digest([]).to_vec()
+    }
+
+    let mut level: Vec<Vec<u8>> = hashes.iter().map(|h| leaf(h)).collect();
+
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+
+    level.remove(0)
+}
+
+///
+/// A single step on the path from a leaf to the Merkle root.
+///
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProofStep {
+    /// The hash of the sibling node.
+    #[serde(with="serde_bytes")]
+    pub hash: Vec<u8>,
+    /// Indicates if the sibling is the left node.
+    pub left: bool,
+}
+
+///
+/// Computes the Merkle path for the leaf at `index`, or `None` if there is no such leaf.
+///
+pub fn proof(hashes: &[Vec<u8>], index: usize) -> Option<Vec<ProofStep>> {
+    if index >= hashes.len() {
+        return None
+    }
+
+    let mut path = Vec::new();
+    let mut index = index;
+    let mut level: Vec<Vec<u8>> = hashes.iter().map(|h| leaf(h)).collect();
+
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+        if sibling < level.len() {
+            path.push(ProofStep {
+                hash: level[sibling].clone(),
+                left: sibling < index,
+            });
+        }
+
+        level = next_level(&level);
+        index /= 2;
+    }
+
+    Some(path)
+}
+
+///
+/// Checks that the leaf `hash` is part of the tree with the given `root`.
+///
+pub fn verify(hash: &[u8], path: &[ProofStep], root: &[u8]) -> bool {
+    let mut current = leaf(hash);
+
+    for step in path {
+        current = if step.left {
+            node(&step.hash, &current)
+        }
+        else {
+            node(&current, &step.hash)
+        };
+    }
+
+    current == root
+}
+
+///
+/// Proves that a [Data](super::Data) item was committed in the block of `header`.
+///
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InclusionProof {
+    pub header: BlockHeader,
+    /// The Merkle path from the data hash to the root in the header.
+    pub path: Vec<ProofStep>,
+}
+
+impl InclusionProof {
+    ///
+    /// Checks the proof for `data_hash` against headers the caller already trusts.
+    ///
+    pub fn verify(&self, data_hash: &[u8], trusted: &[BlockHeader]) -> bool {
+        if !trusted.iter().any(|h| h.hash == self.header.hash) {
+            log::error!("proof header is not trusted {}", hex::encode(&self.header.hash));
+            false
+        }
+        else if !self.header.validate() {
+            false
+        }
+        else {
+            verify(data_hash, &self.path, &self.header.root)
+        }
+    }
+}
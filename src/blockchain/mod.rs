@@ -1,169 +1,821 @@
 pub mod block;
+pub mod cache;
 pub mod data;
+pub mod export;
+pub mod fsck;
+pub mod genesis;
+pub mod index;
+pub mod join;
+pub mod journal;
+pub mod merkle;
+pub mod orphans;
+pub mod snapshot;
+pub mod store;
 
 use std::{
-    fs::OpenOptions,
-    path::{Path, PathBuf},
-    collections::HashMap,
-    io::{Seek, Write, SeekFrom}
+    collections::HashSet,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
 };
 
-use tokio::{fs::File, io::{AsyncSeekExt, AsyncReadExt}};
-
 use crate::{
-    highlander::GameResult,
-    key::Key
+    config::{Config, Retention},
+    consensus::{self, Consensus, HighlanderConsensus},
+    key::{Key, PubKey},
 };
 
+use self::{block::now, cache::BlockCache, orphans::OrphanPool};
+
 pub use self::{
-    block::Block,
+    block::{Block, BlockHeader, Proof},
     data::Data,
+    index::{DataLocation, IndexEntry},
+    join::Join,
+    merkle::InclusionProof,
+    snapshot::{HeaderSync, Snapshot},
+    store::BlockStore,
 };
 
+/// The number of blocks kept in the [BlockCache] of a [Blockchain].
+pub const CACHE_SIZE: usize = 256;
+
+/// The number of blocks with an unknown parent a [Blockchain] keeps.
+pub const MAX_ORPHANS: usize = 256;
+
+/// How long in milliseconds a block with an unknown parent is kept.
+pub const ORPHAN_TTL: u64 = 10 * 60 * 1000;
+
+/// The number of joins a [Blockchain] keeps until a block takes them in.
+pub const MAX_JOINS: usize = 256;
+
+///
+/// The fork-choice rule.
+///
+/// Returns `true` if the tip `a` is preferred over the tip `b`, where each tip is given as `(height, rank, hash)`.
+/// The chain with the most blocks wins, on equal length the one whose tip is made by the producer
+/// of the lower rank, so a late fallback block does not replace the block of the winner.
+/// The lower tip hash decides between tips of the same rank.
+///
+pub fn is_preferred(a: (usize, usize, &[u8]), b: (usize, usize, &[u8])) -> bool {
+    a.0 > b.0 || (a.0 == b.0 && (a.1 < b.1 || (a.1 == b.1 && a.2 < b.2)))
+}
+
 pub struct Blockchain {
-    folder: PathBuf,
+    store: Box<dyn BlockStore>,
+    cache: Mutex<BlockCache>,
     bucket: Vec<Data>,
-    highest_hash: Vec<u8>,
-    index: HashMap<Vec<u8>, (u64, u64)>,
+    /// Blocks whose parent is not known yet.
+    orphans: OrphanPool,
+    /// The hash of the genesis block, empty if the chain has none.
+    genesis: Vec<u8>,
+    /// Decides who produces the blocks and checks every block before it connects.
+    consensus: Box<dyn Consensus>,
+    /// The joins of the nodes which are not members yet, taken into the next block this node produces.
+    joins: Vec<Join>,
+}
+
+///
+/// A [Blockchain] shared between tasks.
+///
+/// The blockchain does blocking I/O, so all its operations run on the blocking thread pool
+/// instead of the async runtime.
+///
+#[derive(Clone)]
+pub struct SharedBlockchain(Arc<Mutex<Blockchain>>);
+
+impl SharedBlockchain {
+    pub fn new(blockchain: Blockchain) -> Self {
+        Self(Arc::new(Mutex::new(blockchain)))
+    }
+
+    ///
+    /// Runs `f` with exclusive access to the blockchain on the blocking thread pool.
+    ///
+    pub async fn run<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Blockchain) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let blockchain = self.0.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut blockchain = blockchain.lock().unwrap_or_else(PoisonError::into_inner);
+            f(&mut blockchain)
+        }).await.expect("blockchain task panicked")
+    }
 }
 
 impl Blockchain {
+    ///
+    /// Opens the blockchain in `folder` with the [FileStore](store::FileStore).
+    ///
     pub fn new(folder: &str) -> Self {
-        let folder = Path::new(folder).to_path_buf();
+        Self::with_store(store::open(&Default::default(), folder).unwrap()).unwrap()
+    }
 
-        if !folder.exists() {
-            std::fs::create_dir_all(&folder).unwrap();
+    ///
+    /// Opens the blockchain of `config`, with its store, its consensus and its genesis block.
+    ///
+    pub fn open(config: &Config) -> Result<Self, anyhow::Error> {
+        let mut blockchain = Self::with_store(store::open(&config.store, &config.folder)?)?;
+        blockchain.set_consensus(consensus::from_config(config)?);
+        if let Some(path) = &config.genesis {
+            blockchain.set_genesis(&genesis::load(Path::new(path))?)?;
         }
 
-        let idxname = folder.join("bc.idx");
+        Ok(blockchain)
+    }
 
-        let index: HashMap<Vec<u8>, (u64, u64)> = if idxname.exists() {
-            let file = std::fs::File::open(idxname).unwrap();
-            rmp_serde::from_read(file).unwrap()
-        }
-        else {
-            HashMap::new()
+    ///
+    /// Opens the blockchain in `store`, the data index is brought up to the tip if it lags behind.
+    ///
+    pub fn with_store(store: Box<dyn BlockStore>) -> Result<Self, anyhow::Error> {
+        let mut blockchain = Self {
+            store,
+            cache: Mutex::new(BlockCache::new(CACHE_SIZE)),
+            bucket: Vec::new(),
+            orphans: OrphanPool::new(MAX_ORPHANS, ORPHAN_TTL),
+            genesis: Vec::new(),
+            consensus: Box::new(HighlanderConsensus::new(Duration::from_secs(Config::default().round_timeout), None)),
+            joins: Vec::new(),
         };
-        
-        let mut hh = Vec::new();
-        let mut hidx = 0;
-        for (h, p) in index.iter() {
-            if p.0 >= hidx {
-                hidx = p.0;
-                hh = h.clone();
+        blockchain.update_data_index()?;
+
+        Ok(blockchain)
+    }
+
+    ///
+    /// Replaces the [Consensus] the blocks are checked with, a [HighlanderConsensus] by default.
+    ///
+    pub fn set_consensus(&mut self, consensus: Box<dyn Consensus>) {
+        self.consensus = consensus;
+    }
+
+    pub fn consensus(&self) -> &dyn Consensus {
+        self.consensus.as_ref()
+    }
+
+    pub fn consensus_mut(&mut self) -> &mut dyn Consensus {
+        self.consensus.as_mut()
+    }
+
+    ///
+    /// The members the tip names to play the round for the next block, none for an empty chain.
+    ///
+    pub fn members(&self) -> Option<Vec<PubKey>> {
+        self.header(&self.store.tip()).map(|header| header.members)
+    }
+
+    ///
+    /// Keeps the join of a node which is not a member yet, until a block takes it in.
+    ///
+    /// Returns if the join is valid and new, a join of the same node replaces the one kept before.
+    ///
+    pub fn add_join(&mut self, join: Join) -> bool {
+        let (tip, height) = self.highest_block();
+        let members = self.header(&tip).map(|header| header.members).unwrap_or_default();
+
+        if !join.is_open(height + 1) || members.contains(&join.author) || !join.validate() {
+            return false
+        }
+        if self.joins.iter().any(|j| j.author == join.author && j.height >= join.height) {
+            return false
+        }
+
+        self.joins.retain(|j| j.author != join.author);
+        if self.joins.len() == MAX_JOINS {
+            self.joins.remove(0);
+        }
+        self.joins.push(join);
+
+        true
+    }
+
+    ///
+    /// Makes `genesis` the first block of the chain, all other blocks have to chain back to it.
+    ///
+    /// An empty chain starts with the genesis block, a chain starting with another block belongs
+    /// to another network and is refused.
+    ///
+    pub fn set_genesis(&mut self, genesis: &Block) -> Result<(), anyhow::Error> {
+        if !genesis.header.parent.is_empty() || !genesis.validate() {
+            anyhow::bail!("invalid genesis block {}", hex::encode(&genesis.header.hash));
+        }
+
+        match self.store.hash_at(1)? {
+            Some(first) if first != genesis.header.hash => {
+                anyhow::bail!("the chain starts with {} instead of the genesis block", hex::encode(first));
+            }
+            Some(_) => {}
+            None => {
+                self.store.put(genesis)?;
+                self.store.set_tip(&genesis.header.hash)?;
+                self.update_data_index()?;
             }
         }
-        
-        log::debug!("highest hash {}", hex::encode(&hh));
 
-        Self {
-            folder,
-            bucket: Vec::new(),
-            highest_hash: hh,
-            index,
+        self.genesis = genesis.header.hash.clone();
+
+        Ok(())
+    }
+
+    ///
+    /// The hash of the genesis block, which identifies the network, empty if the chain has none.
+    ///
+    pub fn network(&self) -> &[u8] {
+        &self.genesis
+    }
+
+    fn header(&self, hash: &[u8]) -> Option<BlockHeader> {
+        if hash.is_empty() {
+            return None
+        }
+
+        if let Some(header) = self.cache().header(hash) {
+            return Some(header)
+        }
+
+        match self.store.header(hash) {
+            Ok(header) => header,
+            Err(e) => {
+                log::error!("could not read header {}: {}", hex::encode(hash), e);
+                None
+            }
+        }
+    }
+
+    fn cache(&self) -> std::sync::MutexGuard<'_, BlockCache> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    ///
+    /// Loads the block with `hash`, from the cache if possible.
+    ///
+    fn block(&self, hash: &[u8]) -> Result<Option<Block>, anyhow::Error> {
+        if let Some(block) = self.cache().get(hash) {
+            return Ok(Some(block))
+        }
+
+        let block = self.store.get(hash)?;
+        if let Some(ref block) = block {
+            self.cache().insert(block.clone(), false);
+        }
+
+        Ok(block)
+    }
+
+    ///
+    /// Loads the main chain block at `height`, from the cache if possible.
+    ///
+    fn block_at(&self, height: usize) -> Result<Option<Block>, anyhow::Error> {
+        if let Some(block) = self.cache().get_by_height(height) {
+            return Ok(Some(block))
         }
+
+        let block = self.store.get_by_height(height)?;
+        if let Some(ref block) = block {
+            self.cache().insert(block.clone(), true);
+        }
+
+        Ok(block)
+    }
+
+    fn height_of(&self, hash: &[u8]) -> usize {
+        self.header(hash).map(|h| h.height).unwrap_or(0)
+    }
+
+    fn timestamp_of(&self, hash: &[u8]) -> u64 {
+        self.header(hash).map(|h| h.timestamp).unwrap_or(0)
     }
 
     pub fn highest_block(&self) -> (Vec<u8>, usize) {
-        (self.highest_hash.clone(), self.index.len())
+        let tip = self.store.tip();
+        let height = self.height_of(&tip);
+        (tip, height)
+    }
+
+    ///
+    /// The lowest height from which on this node can serve blocks, lower blocks were pruned.
+    ///
+    pub fn lowest(&self) -> usize {
+        self.store.lowest()
+    }
+
+    ///
+    /// Iterates over the complete blocks of the main chain in height order.
+    ///
+    pub fn iter(&self) -> impl Iterator<Item=Result<Block, anyhow::Error>> + '_ {
+        self.store.iter()
     }
 
     pub fn add_to_cache(&mut self, data: Data) {
         self.bucket.push(data);
     }
 
-    pub fn generate_new_block(&mut self, game: GameResult, key: &Key) -> Block {
-        let block = Block::build(
-            &self.highest_hash,
-            game,
-            key,
-            self.bucket.drain(..).collect()
-        );
+    ///
+    /// Produces the block on the tip with `proof`, which the [Consensus] has to allow.
+    ///
+    ///
+    /// The block takes in the kept joins, its members follow from the ones of the tip.
+    ///
+    pub fn generate_new_block(&mut self, proof: Proof, key: &Key) -> Result<Block, anyhow::Error> {
+        let tip = self.store.tip();
+        let parent = self.header(&tip);
+        let timestamp = now().max(self.timestamp_of(&tip));
+        let data = self.bucket.drain(..).collect();
 
-        self.highest_hash = block.hash.clone();
+        let block = match &parent {
+            Some(parent) => {
+                let joins = self.joins.iter()
+                    .filter(|j| j.is_open(parent.height + 1) && !parent.members.contains(&j.author))
+                    .cloned()
+                    .collect();
+                Block::build_on(parent, timestamp, proof, key, data, joins)
+            }
+            None => Block::build(&tip, 1, timestamp, proof, key, data),
+        };
+        if !self.consensus.validate(&block, parent.as_ref()) {
+            self.bucket.extend(block.data);
+            anyhow::bail!("the consensus does not allow this node to produce the block");
+        }
+
+        self.store.put(&block)?;
+        self.store.set_tip(&block.header.hash)?;
+        self.cache().insert(block.clone(), true);
+        self.update_data_index()?;
 
-        self.save_block(&block);
+        let height = block.header.height;
+        self.joins.retain(|j| j.is_open(height + 1) && !block.header.members.contains(&j.author));
 
-        block
+        Ok(block)
     }
 
-    pub async fn get_blocks(&self, from: Vec<u8>, to: Vec<u8>) -> Result<Vec<Block>, anyhow::Error> {
+    ///
+    /// The height of the last block `hash` has in common with the main chain.
+    ///
+    fn fork_point(&self, hash: &[u8]) -> Result<usize, anyhow::Error> {
+        let mut current = hash.to_vec();
+
+        while let Some(header) = self.header(&current) {
+            if self.store.hash_at(header.height)?.as_deref() == Some(current.as_slice()) {
+                return Ok(header.height)
+            }
+            current = header.parent;
+        }
+
+        Ok(0)
+    }
+
+    ///
+    /// Loads the main chain blocks after `from` up to `to`.
+    ///
+    /// If `from` is not on the main chain, the blocks start after the fork point.
+    ///
+    pub fn get_blocks(&self, from: Vec<u8>, to: Vec<u8>) -> Result<Vec<Block>, anyhow::Error> {
+        let start = self.fork_point(&from)? + 1;
+        let end = self.height_of(&to);
         let mut blocks = Vec::new();
 
-        let filename = self.folder.join("bc.db");
-        let mut file = File::open(filename).await?;
-        let mut index = to;
-        log::debug!("index keys:");
-        for (idx, pos) in self.index.iter() {
-            println!("{} {:?}", hex::encode(idx), pos);
-        }
-        while index != from {
-            if let Some(pos) = self.index.get(&index) {
-                file.seek(SeekFrom::Start(pos.0)).await?;
-                let mut buffer = vec![0u8; pos.1 as usize];
-                file.read_exact(&mut buffer).await?;
-                let block: Block = rmp_serde::from_slice(&buffer)?;
-                let parent = block.parent.clone();
-
-                if !block.validate() {
-                    log::warn!("invalid block:\n{}", hex::encode(&block.hash));
+        if start < self.lowest() && start <= end {
+            anyhow::bail!("blocks from height {} on are requested, but below {} are pruned", start, self.lowest());
+        }
+
+        for height in start..=end {
+            match self.block_at(height)? {
+                Some(block) => blocks.push(block),
+                None => {
+                    log::error!("could not read block at height {}", height);
+                    break
                 }
+            }
+        }
 
-                blocks.push(block);
-                index = parent;
+        Ok(blocks)
+    }
+
+    ///
+    /// Looks up the data item with `data_hash` in the main chain and proves its inclusion.
+    ///
+    pub fn prove(&self, data_hash: &[u8]) -> Option<InclusionProof> {
+        let location = match self.data_location(data_hash) {
+            Ok(location) => location?,
+            Err(e) => {
+                log::error!("could not look up data {}: {}", hex::encode(data_hash), e);
+                return None
             }
-            else {
-                log::error!("could new read block:\n{}", hex::encode(index));
-                break
+        };
+        let block = self.read_block(&location.block);
+
+        match block {
+            Ok(block) => {
+                let hashes: Vec<Vec<u8>> = block.data.iter().map(Data::hash).collect();
+                let i = hashes.iter().position(|h| h == data_hash)?;
+                merkle::proof(&hashes, i).map(|path| InclusionProof { header: block.header, path })
+            }
+            Err(e) => {
+                log::error!("could not prove data {}: {}", hex::encode(data_hash), e);
+                None
             }
         }
+    }
 
-        blocks.reverse();
+    ///
+    /// Indexes the data of all complete blocks of the main chain again.
+    ///
+    pub fn rebuild_data_index(&mut self) -> Result<(), anyhow::Error> {
+        self.store.clear_data()?;
+        self.update_data_index()
+    }
 
-        Ok(blocks)
+    ///
+    /// Brings the data index of the store up to the tip, from the last block whose data is indexed.
+    ///
+    /// The blocks of a branch the chain moved away from are removed first. An index which does not
+    /// lead back to the main chain is built again from the lowest complete block.
+    ///
+    fn update_data_index(&mut self) -> Result<(), anyhow::Error> {
+        let mut current = self.store.data_tip();
+        if current == self.store.tip() {
+            return Ok(())
+        }
+        if current.is_empty() {
+            self.store.clear_data()?;
+        }
+
+        while !current.is_empty() && !self.on_main_chain(&current) {
+            match self.block(&current)? {
+                Some(block) => {
+                    self.store.unindex_data(&block)?;
+                    current = block.header.parent;
+                }
+                None => {
+                    log::warn!("data index ends in the unknown block {}, index it again", hex::encode(&current));
+                    self.store.clear_data()?;
+                    current.clear();
+                }
+            }
+        }
+
+        let (_, height) = self.highest_block();
+        for height in (self.height_of(&current) + 1).max(self.lowest())..=height {
+            let block = self.block_at(height)?
+                .ok_or_else(|| anyhow::anyhow!("missing block at height {}", height))?;
+            self.store.index_data(&block)?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Where the data item with `hash` is stored on the main chain, the data of pruned blocks is gone.
+    ///
+    fn data_location(&self, hash: &[u8]) -> Result<Option<DataLocation>, anyhow::Error> {
+        Ok(self.store.data_location(hash)?.filter(|l| l.height >= self.lowest()))
+    }
+
+    ///
+    /// Looks up the data item with `hash` on the main chain, together with where it is stored.
+    ///
+    /// Only the items of complete blocks are found, the data of pruned blocks is gone.
+    ///
+    pub fn find_data(&self, hash: &[u8]) -> Result<Option<(DataLocation, Data)>, anyhow::Error> {
+        let location = match self.data_location(hash)? {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+
+        let block = self.read_block(&location.block)?;
+        let data = block.data.get(location.position).cloned()
+            .ok_or_else(|| anyhow::anyhow!("data index out of date at block {}", hex::encode(&location.block)))?;
+
+        Ok(Some((location, data)))
     }
 
-    pub fn add_new_block(&mut self, block: Block) {
-        if block.validate() {
-            if self.highest_hash == block.parent {
-                self.save_block(&block);
-                self.highest_hash = block.hash.clone();
+    ///
+    /// The data items of `author` on the main chain in chain order.
+    ///
+    pub fn data_by_author(&self, author: &[u8]) -> Result<Vec<Data>, anyhow::Error> {
+        let hashes = self.store.author_data(author)?;
+        let mut items = Vec::with_capacity(hashes.len());
+
+        for hash in hashes {
+            if let Some((_, data)) = self.find_data(&hash)? {
+                items.push(data);
             }
-            else {
-                log::error!(
-                    "new block has not current highest block as parent:\nnode:    {}\nparent:  {}\nhighest: {}",
-                    hex::encode(&block.hash),
-                    hex::encode(&block.parent),
-                    hex::encode(&self.highest_hash)
-                );
+        }
+
+        Ok(items)
+    }
+
+    ///
+    /// Stores a valid block, even if it is not on the current main chain.
+    ///
+    /// If the branch of the block is preferred by the fork-choice rule of [is_preferred],
+    /// the chain is reorganized to it.
+    /// Blocks with an unknown parent are kept back in the [OrphanPool] until the parent arrives.
+    ///
+    /// Returns `false` if the block is rejected, a block which is kept back is not rejected.
+    ///
+    pub fn add_new_block(&mut self, block: Block) -> bool {
+        if !block.validate() {
+            log::error!("invalid block {}", hex::encode(&block.header.hash));
+            return false
+        }
+
+        if !block.header.parent.is_empty() && self.header(&block.header.parent).is_none() {
+            log::warn!(
+                "parent of new block is unknown:\nnode:   {}\nparent: {}",
+                hex::encode(&block.header.hash),
+                hex::encode(&block.header.parent),
+            );
+            self.orphans.insert(block);
+            return true
+        }
+
+        let first = block.header.hash.clone();
+        let mut accepted = true;
+        let mut queue = vec![block];
+
+        while let Some(block) = queue.pop() {
+            if self.header(&block.header.hash).is_some() {
+                log::debug!("block already known {}", hex::encode(&block.header.hash));
+                continue
             }
+
+            let hash = block.header.hash.clone();
+            if let Err(e) = self.connect(block) {
+                log::error!("could not add block {}: {}", hex::encode(&hash), e);
+                accepted &= hash != first;
+                continue
+            }
+
+            queue.extend(self.orphans.take_children(&hash));
+        }
+
+        accepted
+    }
+
+    ///
+    /// Adds `block`, which has to follow a known parent, and fails with the reason if it is rejected.
+    ///
+    /// The block is checked like by [Blockchain::add_new_block], but nothing is kept back, this is
+    /// meant for blocks read in order like from an import.
+    ///
+    pub fn import_block(&mut self, block: Block) -> Result<(), anyhow::Error> {
+        if !block.validate() {
+            anyhow::bail!("invalid block {}", hex::encode(&block.header.hash));
+        }
+
+        if !block.header.parent.is_empty() && self.header(&block.header.parent).is_none() {
+            anyhow::bail!("unknown parent {}", hex::encode(&block.header.parent));
+        }
+
+        if self.header(&block.header.hash).is_some() {
+            return Ok(())
+        }
+
+        self.connect(block)
+    }
+
+    ///
+    /// Stores a valid block with a known parent and moves the tip to it if it is preferred.
+    ///
+    /// The [Consensus] checks that the author was allowed to produce the block.
+    ///
+    fn connect(&mut self, block: Block) -> Result<(), anyhow::Error> {
+        if block.header.parent.is_empty() && !self.genesis.is_empty() && block.header.hash != self.genesis {
+            anyhow::bail!("block does not chain back to the genesis block");
+        }
+
+        let parent = self.header(&block.header.parent);
+        let (height, timestamp) = parent.as_ref().map(|p| (p.height, p.timestamp)).unwrap_or((0, 0));
+        if !block.header.validate_successor(height, timestamp) {
+            anyhow::bail!("block does not follow its parent");
+        }
+
+        // the first block names the members on trust, like it is trusted as the genesis block
+        if parent.as_ref().is_some_and(|parent| !block.validate_members(parent)) {
+            anyhow::bail!("block does not follow the members of its parent");
+        }
+
+        if !self.consensus.validate(&block, parent.as_ref()) {
+            anyhow::bail!("block is not allowed by the consensus");
+        }
+
+        self.store.put(&block)?;
+        self.cache().insert(block.clone(), false);
+
+        let (hh, hheight) = self.highest_block();
+        let hrank = self.header(&hh).map(|h| h.rank).unwrap_or(0);
+        if is_preferred((block.header.height, block.header.rank, &block.header.hash), (hheight, hrank, &hh)) {
+            self.reorganize(block.header.hash.clone());
         }
         else {
-            log::error!("invalid block {}", hex::encode(&block.hash));
+            log::info!(
+                "new block is on a side branch:\nnode:    {}\nheight:  {}\nhighest: {}",
+                hex::encode(&block.header.hash),
+                block.header.height,
+                hex::encode(&hh)
+            );
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Moves the tip to `tip`.
+    ///
+    /// The blocks between the old tip and the common ancestor are rolled back and their data is put
+    /// back into the bucket, the blocks of the new branch are rolled forward and their data is
+    /// removed from the bucket.
+    ///
+    fn reorganize(&mut self, tip: Vec<u8>) {
+        let highest = self.store.tip();
+        let mut old = highest.clone();
+        let mut new = tip.clone();
+        let mut detached = Vec::new();
+        let mut attached = Vec::new();
+
+        while old != new {
+            if self.height_of(&old) >= self.height_of(&new) {
+                let parent = self.header(&old).map(|h| h.parent).unwrap_or_default();
+                detached.push(old);
+                old = parent;
+            }
+            else {
+                let parent = self.header(&new).map(|h| h.parent).unwrap_or_default();
+                attached.push(new);
+                new = parent;
+            }
+        }
+
+        if !detached.is_empty() {
+            log::warn!(
+                "reorganize chain:\nold tip:  {}\nnew tip:  {}\nancestor: {}\nrollback: {}",
+                hex::encode(&highest),
+                hex::encode(&tip),
+                hex::encode(&old),
+                detached.len(),
+            );
+        }
+
+        let read = |hashes: &[Vec<u8>]| -> Vec<Block> {
+            hashes.iter().filter_map(|hash| match self.read_block(hash) {
+                Ok(block) => Some(block),
+                Err(e) => {
+                    log::error!("could not read block {}: {}", hex::encode(hash), e);
+                    None
+                }
+            }).collect()
+        };
+        let attached_blocks = read(&attached);
+        let detached_blocks = read(&detached);
+
+        let mut included: HashSet<Vec<u8>> = attached_blocks.iter()
+            .flat_map(|b| b.data.iter().map(Data::hash))
+            .collect();
+
+        let mut bucket: Vec<Data> = detached_blocks.iter().rev()
+            .flat_map(|b| b.data.iter().cloned())
+            .collect();
+        bucket.append(&mut self.bucket);
+        // drops data which is part of the new branch and duplicates
+        bucket.retain(|d| included.insert(d.hash()));
+
+        self.bucket = bucket;
+
+        if let Err(e) = self.store.set_tip(&tip) {
+            log::error!("could not move tip to {}: {}", hex::encode(&tip), e);
+        }
+
+        let ancestor = self.height_of(&old);
+        let mut cache = self.cache();
+        cache.truncate(ancestor);
+        for hash in &attached {
+            if let Some(block) = cache.get(hash) {
+                cache.insert(block, true);
+            }
+        }
+        drop(cache);
+
+        // the index follows the main chain, it catches up on the next block or start if this fails
+        if let Err(e) = self.update_data_index() {
+            log::error!("could not index data: {}", e);
         }
     }
 
-    fn save_block(&mut self, block: &Block) {
-        log::info!("save block {}", hex::encode(&block.hash));
+    fn read_block(&self, hash: &[u8]) -> Result<Block, anyhow::Error> {
+        self.block(hash)?.ok_or_else(|| anyhow::anyhow!("unknown block"))
+    }
 
-        let filename = self.folder.join("bc.db");
-        let mut file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .append(true)
-            .open(filename).unwrap();
-        let pos = file.seek(std::io::SeekFrom::End(0)).unwrap();       
-        let data = rmp_serde::to_vec_named(&block).unwrap();
-        let end = data.len() as u64;
-        file.write_all(&data).unwrap();
+    ///
+    /// Checks if the block with `hash` is part of the main chain.
+    ///
+    pub fn on_main_chain(&self, hash: &[u8]) -> bool {
+        match self.header(hash) {
+            Some(header) => matches!(self.store.hash_at(header.height), Ok(Some(h)) if h == hash),
+            None => false,
+        }
+    }
 
-        self.index.insert(block.hash.clone(), (pos, end));
+    ///
+    /// Creates the [Snapshot] of the main chain up to the tip, which has to be a block of `key`.
+    ///
+    pub fn snapshot(&self, key: &Key) -> Result<Snapshot, anyhow::Error> {
+        let (_, height) = self.highest_block();
+        Snapshot::build(&self.headers(1, height)?, key)
     }
 
-    pub fn save_index(&self) {
-        let filename = self.folder.join("bc.idx");
-        let mut file = std::fs::File::create(filename).unwrap();
-        let data = rmp_serde::to_vec_named(&self.index).unwrap();
-        file.write_all(&data).unwrap();
+    ///
+    /// The headers of the main chain from height `from` up to and including `to`.
+    ///
+    pub fn headers(&self, from: usize, to: usize) -> Result<Vec<BlockHeader>, anyhow::Error> {
+        let mut headers = Vec::with_capacity((to + 1).saturating_sub(from));
+
+        for height in from.max(1)..=to {
+            let hash = self.store.hash_at(height)?.unwrap_or_default();
+            let header = self.header(&hash)
+                .ok_or_else(|| anyhow::anyhow!("missing header at height {}", height))?;
+            headers.push(header);
+        }
+
+        Ok(headers)
     }
-}
\ No newline at end of file
+
+    ///
+    /// Starts an empty chain, or one with only the genesis block, from `snapshot` and its `headers`.
+    ///
+    /// Only the headers are stored, so the chain continues after the checkpoint as if the blocks
+    /// up to it were pruned.
+    ///
+    pub fn bootstrap(&mut self, snapshot: &Snapshot, headers: &[BlockHeader]) -> Result<(), anyhow::Error> {
+        let (tip, height) = self.highest_block();
+        if height > 1 || (height == 1 && tip != self.genesis) {
+            anyhow::bail!("only an empty chain is bootstrapped from a snapshot");
+        }
+        if !snapshot.validate() || !snapshot.verify_headers(headers) {
+            anyhow::bail!("invalid snapshot {}", hex::encode(&snapshot.hash));
+        }
+        if !self.genesis.is_empty() && headers.first().map(|h| &h.hash) != Some(&self.genesis) {
+            anyhow::bail!("snapshot {} does not start with the genesis block", hex::encode(&snapshot.hash));
+        }
+
+        for header in headers {
+            if self.header(&header.hash).is_none() {
+                self.store.put_header(header)?;
+            }
+        }
+        self.store.set_tip(&snapshot.checkpoint.hash)?;
+        self.update_data_index()?;
+
+        log::info!("bootstrapped from snapshot {}", hex::encode(&snapshot.hash));
+
+        Ok(())
+    }
+
+    ///
+    /// Drops the bodies of the blocks which are not kept by `retention`, the headers are kept.
+    ///
+    /// The new blocks are still validated against the headers of their parents, but the pruned
+    /// blocks cannot be served to other nodes anymore.
+    ///
+    pub fn prune(&mut self, retention: &Retention) -> Result<(), anyhow::Error> {
+        let (_, height) = self.highest_block();
+        let lowest = self.lowest();
+
+        let keep = match retention {
+            Retention::Blocks(n) => (height + 1).saturating_sub(*n).max(1),
+            Retention::Age(seconds) => {
+                let limit = now().saturating_sub(seconds * 1000);
+                let mut keep = height + 1;
+                while keep > lowest {
+                    let hash = self.store.hash_at(keep - 1)?.unwrap_or_default();
+                    if self.timestamp_of(&hash) < limit {
+                        break
+                    }
+                    keep -= 1;
+                }
+                keep
+            }
+        };
+
+        if keep <= lowest {
+            return Ok(())
+        }
+
+        self.store.prune(keep)?;
+        *self.cache() = BlockCache::new(CACHE_SIZE);
+
+        Ok(())
+    }
+
+    ///
+    /// Writes all pending changes of the store to disk.
+    ///
+    pub fn flush(&mut self) {
+        if let Err(e) = self.store.flush() {
+            log::error!("could not flush store: {}", e);
+        }
+    }
+}
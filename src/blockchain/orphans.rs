@@ -0,0 +1,112 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::block::{now, Block};
+
+
+///
+/// Blocks whose parent is not known yet, kept until the parent arrives.
+///
+/// The pool holds at most `capacity` blocks, each block once, and drops blocks older than `ttl`
+/// milliseconds. When it is full, the block received first is dropped.
+///
+pub struct OrphanPool {
+    capacity: usize,
+    ttl: u64,
+    /// The blocks by hash, together with the time they were received.
+    blocks: HashMap<Vec<u8>, (u64, Block)>,
+    /// The hashes of the blocks waiting for a parent, keyed by the parent hash.
+    children: HashMap<Vec<u8>, Vec<Vec<u8>>>,
+    /// The hashes in the order they were received.
+    order: VecDeque<Vec<u8>>,
+}
+
+impl OrphanPool {
+    pub fn new(capacity: usize, ttl: u64) -> Self {
+        Self {
+            capacity,
+            ttl,
+            blocks: HashMap::new(),
+            children: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn contains(&self, hash: &[u8]) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    ///
+    /// Keeps `block` until its parent arrives, returns `false` if it is already kept.
+    ///
+    pub fn insert(&mut self, block: Block) -> bool {
+        self.expire();
+
+        let hash = block.header.hash.clone();
+        if self.blocks.contains_key(&hash) {
+            return false
+        }
+
+        while self.blocks.len() >= self.capacity {
+            match self.order.front().cloned() {
+                Some(oldest) => self.remove(&oldest),
+                None => break,
+            }
+        }
+
+        self.children.entry(block.header.parent.clone()).or_default().push(hash.clone());
+        self.order.push_back(hash.clone());
+        self.blocks.insert(hash, (now(), block));
+
+        true
+    }
+
+    ///
+    /// Takes the blocks waiting for `parent` out of the pool.
+    ///
+    pub fn take_children(&mut self, parent: &[u8]) -> Vec<Block> {
+        let hashes = self.children.remove(parent).unwrap_or_default();
+        self.order.retain(|h| !hashes.contains(h));
+
+        hashes.iter()
+            .filter_map(|h| self.blocks.remove(h))
+            .map(|(_, block)| block)
+            .collect()
+    }
+
+    fn remove(&mut self, hash: &[u8]) {
+        self.order.retain(|h| h != hash);
+
+        if let Some((_, block)) = self.blocks.remove(hash) {
+            let parent = &block.header.parent;
+            if let Some(siblings) = self.children.get_mut(parent) {
+                siblings.retain(|h| h != hash);
+                if siblings.is_empty() {
+                    self.children.remove(parent);
+                }
+            }
+            log::debug!("drop orphan block {}", hex::encode(hash));
+        }
+    }
+
+    ///
+    /// Drops the blocks kept longer than the time to live.
+    ///
+    fn expire(&mut self) {
+        let limit = now().saturating_sub(self.ttl);
+
+        while let Some(oldest) = self.order.front().cloned() {
+            match self.blocks.get(&oldest) {
+                Some((received, _)) if *received < limit => self.remove(&oldest),
+                _ => break,
+            }
+        }
+    }
+}
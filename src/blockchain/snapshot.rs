@@ -0,0 +1,221 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::key::Key;
+
+use super::{block::BlockHeader, merkle};
+
+/// A snapshot is produced for every block whose height is a multiple of this.
+pub const CHECKPOINT_INTERVAL: usize = 1000;
+
+/// The most headers sent in one range, see [HeaderSync].
+pub const MAX_HEADERS: usize = 2000;
+
+///
+/// The state of the chain at a checkpoint block, signed by the author of that block.
+///
+/// The state a node derives from the chain is the sequence of main chain headers, so a new node
+/// can validate the blocks after the checkpoint without downloading the bodies before it.
+/// The snapshot holds only the checkpoint header and commits to the other headers with their
+/// Merkle root, they are fetched in ranges with a [HeaderSync].
+///
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snapshot {
+    /// The header of the checkpoint block.
+    pub checkpoint: BlockHeader,
+    /// The Merkle root over the main chain header hashes from the first block up to and including the checkpoint.
+    pub root: Vec<u8>,
+    /// The hash of the snapshot.
+    pub hash: Vec<u8>,
+    /// The sign of the checkpoint author over the snapshot hash.
+    pub sign: Vec<u8>,
+}
+
+///
+/// The hash of a snapshot, committing to the number of headers and the Merkle root over their hashes.
+///
+pub fn snapshot_hash(count: usize, root: &[u8]) -> Vec<u8> {
+    let mut sha = Sha256::new();
+    sha.update((count as u64).to_be_bytes());
+    sha.update(root);
+    sha.finalize().to_vec()
+}
+
+fn headers_root(headers: &[BlockHeader]) -> Vec<u8> {
+    let hashes: Vec<Vec<u8>> = headers.iter().map(|h| h.hash.clone()).collect();
+    merkle::root(&hashes)
+}
+
+impl Snapshot {
+    ///
+    /// Creates the snapshot of `headers`, `key` has to be the author of the last one.
+    ///
+    pub fn build(headers: &[BlockHeader], key: &Key) -> Result<Self, anyhow::Error> {
+        let checkpoint = match headers.last() {
+            Some(last) if last.author == key.public_key => last.clone(),
+            _ => anyhow::bail!("only the author of the checkpoint block signs its snapshot"),
+        };
+
+        let root = headers_root(headers);
+        let hash = snapshot_hash(headers.len(), &root);
+        let sign = key.sign(&hash).map_err(|e| anyhow::anyhow!("{}", e))?;
+
+        Ok(Self {
+            checkpoint,
+            root,
+            hash,
+            sign,
+        })
+    }
+
+    ///
+    /// Checks the checkpoint header, the hash and the sign of the checkpoint author.
+    ///
+    pub fn validate(&self) -> bool {
+        if !self.checkpoint.validate() {
+            log::error!("invalid checkpoint header {}", hex::encode(&self.checkpoint.hash));
+            return false
+        }
+
+        if self.hash != snapshot_hash(self.checkpoint.height, &self.root) {
+            log::error!("wrong snapshot hash {}", hex::encode(&self.hash));
+            return false
+        }
+
+        match Key::validate(&self.hash, &self.checkpoint.author, &self.sign) {
+            Ok(_) => true,
+            Err(e) => {
+                log::error!("invalid snapshot sign: {}", e);
+                false
+            }
+        }
+    }
+
+    ///
+    /// Checks that `headers` form a valid chain from the first block up to the checkpoint, whose
+    /// hashes have the Merkle root of the snapshot.
+    ///
+    pub fn verify_headers(&self, headers: &[BlockHeader]) -> bool {
+        let mut parent: &[u8] = &[];
+        let mut timestamp = 0;
+        for (i, header) in headers.iter().enumerate() {
+            if header.parent != parent || header.height != i + 1 || header.timestamp < timestamp || !header.validate() {
+                log::error!("invalid snapshot header {}", hex::encode(&header.hash));
+                return false
+            }
+            parent = &header.hash;
+            timestamp = header.timestamp;
+        }
+
+        if headers.last().map(|h| &h.hash) != Some(&self.checkpoint.hash) || headers_root(headers) != self.root {
+            log::error!("headers do not match snapshot {}", hex::encode(&self.hash));
+            return false
+        }
+
+        true
+    }
+
+    fn path(folder: &Path, hash: &[u8]) -> PathBuf {
+        folder.join("snapshots").join(format!("{}.snap", hex::encode(hash)))
+    }
+
+    ///
+    /// Writes the snapshot to the `snapshots` folder in `folder`.
+    ///
+    pub fn save(&self, folder: &Path) -> Result<(), anyhow::Error> {
+        let path = Self::path(folder, &self.hash);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(path, rmp_serde::to_vec_named(self)?)?;
+        Ok(())
+    }
+
+    ///
+    /// Reads the snapshot with `hash` from the `snapshots` folder in `folder`.
+    ///
+    pub fn load(folder: &Path, hash: &[u8]) -> Result<Option<Self>, anyhow::Error> {
+        let path = Self::path(folder, hash);
+
+        if !path.exists() {
+            return Ok(None)
+        }
+
+        Ok(Some(rmp_serde::from_slice(&std::fs::read(path)?)?))
+    }
+}
+
+///
+/// Collects the headers of a [Snapshot] in ranges, from the checkpoint down to the first block.
+///
+/// Each range has to end in the parent of the lowest header collected so far, so the headers
+/// are checked against the signed checkpoint as they arrive.
+///
+pub struct HeaderSync {
+    snapshot: Snapshot,
+    /// The most headers requested at once.
+    batch: usize,
+    /// The collected headers, from the checkpoint down.
+    headers: Vec<BlockHeader>,
+}
+
+impl HeaderSync {
+    pub fn new(snapshot: Snapshot, batch: usize) -> Self {
+        let headers = vec![snapshot.checkpoint.clone()];
+
+        Self {
+            snapshot,
+            batch: batch.max(1),
+            headers,
+        }
+    }
+
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    ///
+    /// The first and last height of the range to request next, `None` once all headers are collected.
+    ///
+    pub fn next_range(&self) -> Option<(usize, usize)> {
+        let lowest = self.headers.last()?.height;
+        if lowest <= 1 {
+            return None
+        }
+
+        Some((lowest.saturating_sub(self.batch).max(1), lowest - 1))
+    }
+
+    ///
+    /// Adds a range of `headers` in ascending order, which has to end below the lowest header collected so far.
+    ///
+    pub fn add(&mut self, headers: Vec<BlockHeader>) -> Result<(), anyhow::Error> {
+        if headers.is_empty() {
+            anyhow::bail!("empty range of headers");
+        }
+
+        for header in headers.into_iter().rev() {
+            let lowest = self.headers.last().unwrap();
+            if header.hash != lowest.parent || header.height + 1 != lowest.height
+                || header.timestamp > lowest.timestamp || !header.validate() {
+                anyhow::bail!("header {} does not lead to the checkpoint", hex::encode(&header.hash));
+            }
+            self.headers.push(header);
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// The headers from the first block up to the checkpoint, once all of them are collected.
+    ///
+    pub fn finish(self) -> Option<(Snapshot, Vec<BlockHeader>)> {
+        if self.next_range().is_some() {
+            return None
+        }
+
+        let mut headers = self.headers;
+        headers.reverse();
+        Some((self.snapshot, headers))
+    }
+}
@@ -0,0 +1,757 @@
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::blockchain::{
+    block::{Block, BlockHeader},
+    index::{DataLocation, IndexEntry},
+    is_preferred,
+    journal,
+};
+
+use super::{BlockStore, table::{HashTable, Marks}};
+
+/// The 32 byte hash followed by position and length of the index record.
+const HEIGHT_RECORD: u64 = 48;
+
+/// The files of the data index, they do not depend on the positions in the data file.
+const DATA_FILES: [&str; 4] = ["bc.dix", "bc.dhs", "bc.dau", "bc.dtp"];
+
+///
+/// A record of the data journal `bc.dix`.
+///
+/// The records of the items of an author are linked from the latest one back, the latest one is
+/// removed first as blocks are removed from the tip.
+///
+#[derive(Serialize, Deserialize)]
+struct DataRecord {
+    hash: Vec<u8>,
+    /// Where the item is stored, `None` if it was removed or marks an author without items.
+    location: Option<DataLocation>,
+    /// The position and length of the record of the previous item of the author.
+    previous: Option<(u64, u64)>,
+}
+
+fn author_key(author: &[u8]) -> Vec<u8> {
+    Sha256::digest(author).to_vec()
+}
+
+
+fn file_len(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+///
+/// Decodes a record of `bc.db`, a valid block or the valid header of a pruned block.
+///
+/// Returns the header and if the block is pruned, or why the record is neither.
+///
+pub fn decode(record: &[u8]) -> Result<(BlockHeader, bool), String> {
+    match rmp_serde::from_slice::<Block>(record) {
+        Ok(block) if block.validate() => Ok((block.header, false)),
+        Ok(_) => Err("invalid block".to_owned()),
+        Err(e) => match rmp_serde::from_slice::<BlockHeader>(record) {
+            Ok(header) if header.validate() => Ok((header, true)),
+            Ok(_) => Err("invalid pruned header".to_owned()),
+            Err(_) => Err(format!("not a block: {}", e)),
+        }
+    }
+}
+
+fn read_at(path: &Path, pos: u64, len: u64) -> Result<Vec<u8>, anyhow::Error> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(pos))?;
+    let mut buffer = vec![0u8; len as usize];
+    file.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+///
+/// The [BlockStore] of the `bc.db` data file.
+///
+/// * `bc.db` is the journal of all complete blocks, pruning cuts the old blocks off its front.
+/// * `bc.hdr` is the journal of the headers of pruned blocks.
+/// * `bc.idx` is the journal of an [IndexEntry] for each block in `bc.db` and header in `bc.hdr`.
+/// * `bc.hsh` is a [HashTable] from block hash to the index record.
+/// * `bc.hgt` holds a fixed size record for each block of the main chain, ordered by height.
+/// * `bc.dix` is the journal of the data index, `bc.dhs` a [HashTable] from data hash to its record,
+///   `bc.dau` a [HashTable] from the hash of an author to the record of its latest item, and
+///   `bc.dtp` holds the last block whose data is indexed.
+///
+/// Opening the store reads only the headers of these files, its cost does not grow with the chain.
+///
+pub struct FileStore {
+    folder: PathBuf,
+    table: HashTable,
+    /// The height of the main chain tip.
+    height: usize,
+    tip: Vec<u8>,
+    lowest: usize,
+    /// Opened by [FileStore::open_read_only], the files are never written.
+    read_only: bool,
+}
+
+impl FileStore {
+    ///
+    /// Opens the store in `folder` and recovers it.
+    ///
+    /// An index written by another version of the store is rebuilt from the data file. A data file
+    /// whose first record is no block of this version is refused, instead of cutting it off.
+    ///
+    pub fn open(folder: &Path) -> Result<Self, anyhow::Error> {
+        let tablename = folder.join("bc.hsh");
+        let table = match HashTable::open(&tablename) {
+            Err(e) if e.kind() == ErrorKind::InvalidData => {
+                log::warn!("index of {} has another version", folder.display());
+                std::fs::remove_file(&tablename)?;
+                HashTable::open(&tablename)?
+            }
+            table => table?,
+        };
+
+        // the data index is built again by the blockchain, once its tip is gone
+        for name in ["bc.dhs", "bc.dau"] {
+            if let Err(e) = HashTable::open(&folder.join(name)) {
+                log::warn!("data index of {} is unusable: {}", folder.display(), e);
+                Self::remove_data(folder)?;
+            }
+        }
+
+        let mut store = Self {
+            folder: folder.to_path_buf(),
+            table,
+            height: 0,
+            tip: Vec::new(),
+            lowest: 1,
+            read_only: false,
+        };
+        store.recover()?;
+        store.load_tip()?;
+
+        log::debug!("highest hash {}", hex::encode(&store.tip));
+
+        Ok(store)
+    }
+
+    ///
+    /// Opens the store in `folder` without writing to any of its files, for tools which inspect
+    /// the files of a node.
+    ///
+    /// Nothing is recovered, blocks appended after the last indexed one are not visible.
+    /// All changes to the store fail.
+    ///
+    pub fn open_read_only(folder: &Path) -> Result<Self, anyhow::Error> {
+        let tablename = folder.join("bc.hsh");
+        if !tablename.exists() {
+            anyhow::bail!("no index in {}", folder.display());
+        }
+
+        let mut store = Self {
+            folder: folder.to_path_buf(),
+            table: HashTable::open(&tablename)?,
+            height: 0,
+            tip: Vec::new(),
+            lowest: 1,
+            read_only: true,
+        };
+        let marks = store.table.marks();
+        if marks.db_end != marks.db_base + file_len(&folder.join("bc.db"))
+            || marks.hdr_end != file_len(&folder.join("bc.hdr")) {
+            log::warn!("index does not match the data file, open the store writable to recover it");
+        }
+        store.load_tip()?;
+
+        Ok(store)
+    }
+
+    fn writable(&self) -> Result<(), anyhow::Error> {
+        if self.read_only {
+            anyhow::bail!("store in {} is opened read-only", self.folder.display());
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Indexes the data file in `folder` again without changing it.
+    ///
+    /// Unlike the recovery on [FileStore::open], records which are neither a valid block nor
+    /// a pruned header are skipped instead of cutting the data file, and the blocks after them
+    /// stay indexed. The new index files are written to a temporary folder and replace the old
+    /// ones once they are complete.
+    ///
+    /// Returns the positions of the skipped records of `bc.db`, skipped records of `bc.hdr` are
+    /// only logged.
+    ///
+    pub fn reindex(folder: &Path) -> Result<Vec<u64>, anyhow::Error> {
+        let tmp = folder.join("reindex");
+        if tmp.exists() {
+            std::fs::remove_dir_all(&tmp)?;
+        }
+        std::fs::create_dir(&tmp)?;
+
+        let mut store = Self {
+            folder: tmp.clone(),
+            table: HashTable::open(&tmp.join("bc.hsh"))?,
+            height: 0,
+            tip: Vec::new(),
+            lowest: 1,
+            read_only: false,
+        };
+
+        let mut skipped = Vec::new();
+        let mut rooted = HashSet::new();
+        let mut tip = Vec::new();
+        let mut height = 0;
+        let mut rank = 0;
+        let mut marks = Marks::default();
+
+        // the pruned headers are the ancestors of the blocks, so they come first
+        for (name, pruned) in [("bc.hdr", true), ("bc.db", false)] {
+            let (records, end) = journal::read_from(&folder.join(name), 0)?;
+
+            for (pos, record) in records {
+                let header = match decode(&record) {
+                    Ok((header, p)) if p == pruned => header,
+                    result => {
+                        let reason = result.err().unwrap_or_else(|| "misplaced record".to_owned());
+                        log::warn!("skip record of {} at {}: {}", name, pos, reason);
+                        if !pruned {
+                            skipped.push(pos);
+                        }
+                        continue
+                    }
+                };
+
+                // a block after a skipped parent is indexed, but cannot become the tip
+                if header.parent.is_empty() || rooted.contains(&header.parent) {
+                    if is_preferred((header.height, header.rank, &header.hash), (height, rank, &tip)) {
+                        height = header.height;
+                        rank = header.rank;
+                        tip = header.hash.clone();
+                    }
+                    rooted.insert(header.hash.clone());
+                }
+
+                store.index(pos, record.len() as u64, header, pruned)?;
+            }
+
+            if pruned {
+                marks.hdr_end = end;
+            }
+            else {
+                marks.db_end = end;
+            }
+        }
+
+        // the skipped records at the end are covered as well, so the next recovery leaves them alone
+        marks.idx_end = file_len(&tmp.join("bc.idx"));
+        store.table.set_marks(marks)?;
+        store.set_tip(&tip)?;
+        drop(store);
+
+        for name in ["bc.idx", "bc.hsh", "bc.hgt"] {
+            let (new, old) = (tmp.join(name), folder.join(name));
+            if new.exists() {
+                std::fs::rename(new, old)?;
+            }
+            else if old.exists() {
+                std::fs::remove_file(old)?;
+            }
+        }
+        std::fs::remove_dir(tmp)?;
+
+        Ok(skipped)
+    }
+
+    ///
+    /// Brings the index in line with the data file `bc.db` and the header journal `bc.hdr`.
+    ///
+    /// Records appended after the last indexed one are indexed and a torn record at the end of
+    /// either file is cut off.
+    /// If a file is shorter than the index claims, the whole index is rebuilt from both files.
+    ///
+    fn recover(&mut self) -> Result<(), anyhow::Error> {
+        let idxname = self.folder.join("bc.idx");
+        let marks = self.table.marks();
+        let db_len = marks.db_base + file_len(&self.folder.join("bc.db"));
+        let hdr_len = file_len(&self.folder.join("bc.hdr"));
+        let idx_len = file_len(&idxname);
+
+        if db_len < marks.db_end || hdr_len < marks.hdr_end || idx_len < marks.idx_end
+            || (marks.db_end == 0 && db_len > 0) || (marks.hdr_end == 0 && hdr_len > 0) {
+            log::warn!("index does not match the data file");
+            return self.rebuild()
+        }
+
+        if idx_len > marks.idx_end {
+            log::warn!("cut unfinished index records at {}, {} bytes", marks.idx_end, idx_len - marks.idx_end);
+            journal::truncate(&idxname, marks.idx_end)?;
+        }
+
+        for (name, pruned, indexed, len) in [("bc.hdr", true, marks.hdr_end, hdr_len), ("bc.db", false, marks.db_end, db_len)] {
+            if len > indexed {
+                let mut recovered = 0;
+                let end = self.scan(pruned, indexed, |_| recovered += 1)?;
+
+                if recovered > 0 {
+                    log::warn!("recovered {} records of {} missing in the index", recovered, name);
+                }
+                if end < len {
+                    log::warn!("cut torn data of {} at {}, {} bytes", name, end, len - end);
+                    let base = if pruned { 0 } else { marks.db_base };
+                    journal::truncate(&self.folder.join(name), end - base)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Throws away all index files and indexes every header and block of the data files again.
+    ///
+    fn rebuild(&mut self) -> Result<(), anyhow::Error> {
+        log::warn!("rebuild index of {}", self.folder.display());
+
+        for name in ["bc.idx", "bc.hsh", "bc.hgt"] {
+            let path = self.folder.join(name);
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+
+        self.table = HashTable::open(&self.folder.join("bc.hsh"))?;
+        self.height = 0;
+        self.tip = Vec::new();
+
+        let mut tip = Vec::new();
+        let mut height = 0;
+        let mut rank = 0;
+
+        // the pruned headers are the ancestors of the blocks, so they come first
+        for (name, pruned) in [("bc.hdr", true), ("bc.db", false)] {
+            let path = self.folder.join(name);
+            let len = file_len(&path);
+            let end = self.scan(pruned, 0, |header| {
+                if is_preferred((header.height, header.rank, &header.hash), (height, rank, &tip)) {
+                    height = header.height;
+                    rank = header.rank;
+                    tip = header.hash.clone();
+                }
+            })?;
+
+            if end == 0 && len > 0 {
+                anyhow::bail!("{} holds no blocks of this version", path.display());
+            }
+            if end < len {
+                log::warn!("cut torn data of {} at {}, {} bytes", name, end, len - end);
+                journal::truncate(&path, end)?;
+            }
+        }
+
+        self.set_tip(&tip)
+    }
+
+    ///
+    /// Indexes the valid blocks of the data file, or with `pruned` the valid headers of the header
+    /// journal, from `start` on.
+    ///
+    /// Stops at the first record which is not one of them and returns its position.
+    ///
+    fn scan<F: FnMut(&BlockHeader)>(&mut self, pruned: bool, start: u64, mut f: F) -> Result<u64, anyhow::Error> {
+        let (name, base) = if pruned { ("bc.hdr", 0) } else { ("bc.db", self.table.marks().db_base) };
+        let (records, end) = journal::read_from(&self.folder.join(name), start - base)?;
+
+        for (pos, record) in records {
+            let header = match decode(&record) {
+                Ok((header, p)) if p == pruned => header,
+                _ => return Ok(base + pos - 4),
+            };
+
+            f(&header);
+            self.index(base + pos, record.len() as u64, header, pruned)?;
+        }
+
+        Ok(base + end)
+    }
+
+    fn index(&mut self, pos: u64, len: u64, header: BlockHeader, pruned: bool) -> Result<(), anyhow::Error> {
+        let hash = header.hash.clone();
+        let record = rmp_serde::to_vec_named(&IndexEntry { pos, len, header, pruned })?;
+        let idx_pos = journal::append(&self.folder.join("bc.idx"), &record)?;
+        self.table.insert(&hash, idx_pos, record.len() as u64)?;
+
+        let mut marks = self.table.marks();
+        marks.idx_end = idx_pos + record.len() as u64;
+        if pruned {
+            marks.hdr_end = pos + len;
+        }
+        else {
+            marks.db_end = pos + len;
+        }
+        self.table.set_marks(marks)?;
+
+        Ok(())
+    }
+
+    fn entry(&self, hash: &[u8]) -> Result<Option<IndexEntry>, anyhow::Error> {
+        match self.table.get(hash)? {
+            Some((pos, len)) => {
+                let record = read_at(&self.folder.join("bc.idx"), pos, len)?;
+                Ok(Some(rmp_serde::from_slice(&record)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn load_tip(&mut self) -> Result<(), anyhow::Error> {
+        let hgtname = self.folder.join("bc.hgt");
+        let len = file_len(&hgtname);
+
+        if !len.is_multiple_of(HEIGHT_RECORD) && !self.read_only {
+            log::warn!("cut torn height record");
+            journal::truncate(&hgtname, len - len % HEIGHT_RECORD)?;
+        }
+
+        self.height = (len / HEIGHT_RECORD) as usize;
+        self.tip = self.hash_at(self.height)?.unwrap_or_default();
+
+        // the pruned blocks of the main chain are the ones below some height
+        let (mut low, mut high) = (1, self.height + 1);
+        while low < high {
+            let mid = (low + high) / 2;
+            let pruned = match self.hash_at(mid)? {
+                Some(hash) => self.entry(&hash)?.map(|e| e.pruned).unwrap_or(false),
+                None => false,
+            };
+
+            if pruned {
+                low = mid + 1;
+            }
+            else {
+                high = mid;
+            }
+        }
+        self.lowest = low;
+
+        Ok(())
+    }
+}
+
+impl FileStore {
+    fn remove_data(folder: &Path) -> Result<(), anyhow::Error> {
+        for name in DATA_FILES {
+            let path = folder.join(name);
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Opens a table of the data index, a read-only store without data index has none.
+    ///
+    fn data_table(&self, name: &str) -> Result<Option<HashTable>, anyhow::Error> {
+        let path = self.folder.join(name);
+        if self.read_only && !path.exists() {
+            return Ok(None)
+        }
+
+        Ok(Some(HashTable::open(&path)?))
+    }
+
+    fn data_record(&self, pointer: Option<(u64, u64)>) -> Result<Option<DataRecord>, anyhow::Error> {
+        match pointer {
+            Some((pos, len)) => Ok(Some(rmp_serde::from_slice(&read_at(&self.folder.join("bc.dix"), pos, len)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn append_data(&self, record: &DataRecord) -> Result<(u64, u64), anyhow::Error> {
+        let data = rmp_serde::to_vec_named(record)?;
+        let pos = journal::append(&self.folder.join("bc.dix"), &data)?;
+        Ok((pos, data.len() as u64))
+    }
+
+    fn set_data_tip(&self, hash: &[u8]) -> Result<(), anyhow::Error> {
+        journal::rewrite(&self.folder.join("bc.dtp"), [hash].into_iter())?;
+        Ok(())
+    }
+}
+
+impl BlockStore for FileStore {
+    fn put(&mut self, block: &Block) -> Result<(), anyhow::Error> {
+        self.writable()?;
+        log::info!("save block {}", hex::encode(&block.header.hash));
+
+        // the block is written first, so a crash in between is repaired by the next recovery
+        let data = rmp_serde::to_vec_named(block)?;
+        let pos = journal::append(&self.folder.join("bc.db"), &data)?;
+
+        self.index(pos, data.len() as u64, block.header.clone(), false)
+    }
+
+    fn put_header(&mut self, header: &BlockHeader) -> Result<(), anyhow::Error> {
+        self.writable()?;
+        let data = rmp_serde::to_vec_named(header)?;
+        let pos = journal::append(&self.folder.join("bc.hdr"), &data)?;
+
+        self.index(pos, data.len() as u64, header.clone(), true)?;
+        self.lowest = self.lowest.max(header.height + 1);
+
+        Ok(())
+    }
+
+    fn get(&self, hash: &[u8]) -> Result<Option<Block>, anyhow::Error> {
+        match self.entry(hash)? {
+            Some(entry) if entry.pruned => Ok(None),
+            Some(entry) => {
+                let data = read_at(&self.folder.join("bc.db"), entry.pos - self.table.marks().db_base, entry.len)?;
+                Ok(Some(rmp_serde::from_slice(&data)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn header(&self, hash: &[u8]) -> Result<Option<BlockHeader>, anyhow::Error> {
+        Ok(self.entry(hash)?.map(|e| e.header))
+    }
+
+    fn hash_at(&self, height: usize) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        if height == 0 || height > self.height {
+            return Ok(None)
+        }
+
+        let pos = (height as u64 - 1) * HEIGHT_RECORD;
+        let record = read_at(&self.folder.join("bc.hgt"), pos, HEIGHT_RECORD)?;
+
+        Ok(Some(record[..32].to_vec()))
+    }
+
+    fn tip(&self) -> Vec<u8> {
+        self.tip.clone()
+    }
+
+    fn set_tip(&mut self, hash: &[u8]) -> Result<(), anyhow::Error> {
+        self.writable()?;
+        let mut branch = Vec::new();
+        let mut current = hash.to_vec();
+        let mut height = 0;
+
+        while !current.is_empty() {
+            let (pos, len) = self.table.get(&current)?
+                .ok_or_else(|| anyhow::anyhow!("unknown block {}", hex::encode(&current)))?;
+            let entry: IndexEntry = rmp_serde::from_slice(&read_at(&self.folder.join("bc.idx"), pos, len)?)?;
+
+            if self.hash_at(entry.header.height)?.as_deref() == Some(current.as_slice()) {
+                height = entry.header.height;
+                break
+            }
+
+            let mut record = Vec::with_capacity(HEIGHT_RECORD as usize);
+            record.extend(&current);
+            record.extend(pos.to_be_bytes());
+            record.extend(len.to_be_bytes());
+            branch.push(record);
+
+            current = entry.header.parent;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.folder.join("bc.hgt"))?;
+        file.set_len(height as u64 * HEIGHT_RECORD)?;
+        file.seek(SeekFrom::End(0))?;
+        for record in branch.iter().rev() {
+            file.write_all(record)?;
+        }
+        file.sync_all()?;
+
+        self.height = height + branch.len();
+        self.tip = hash.to_vec();
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    ///
+    /// Cuts the blocks below `height` off the front of `bc.db` and appends their headers to `bc.hdr`.
+    ///
+    /// Only the front of the data file up to the first block which is kept is pruned, so a block
+    /// stored late stays until the blocks before it are pruned. The records are read one by one
+    /// up to the first kept block, the rest of the data file is only copied.
+    ///
+    fn prune(&mut self, height: usize) -> Result<(), anyhow::Error> {
+        self.writable()?;
+        let dbname = self.folder.join("bc.db");
+        let mut headers = Vec::new();
+        let mut cut = 0;
+
+        for record in journal::records(&dbname, 0)? {
+            let (pos, record) = record?;
+            match rmp_serde::from_slice::<Block>(&record) {
+                Ok(block) if block.header.height < height => headers.push(block.header),
+                _ => break,
+            }
+            cut = pos + record.len() as u64;
+        }
+
+        if headers.is_empty() {
+            return Ok(())
+        }
+
+        log::info!("prune {} blocks below height {}", headers.len(), height);
+
+        // cleared marks make a crash from here on rebuild the index from the data files
+        let marks = self.table.marks();
+        self.table.set_marks(Marks::default())?;
+
+        for header in &headers {
+            let data = rmp_serde::to_vec_named(header)?;
+            let pos = journal::append(&self.folder.join("bc.hdr"), &data)?;
+            self.index(pos, data.len() as u64, header.clone(), true)?;
+        }
+        journal::drop_front(&dbname, cut)?;
+
+        // the blocks which are kept stay at their position, counted from the old front
+        let Marks { idx_end, hdr_end, .. } = self.table.marks();
+        self.table.set_marks(Marks { db_end: marks.db_end, idx_end, hdr_end, db_base: marks.db_base + cut })?;
+
+        self.load_tip()
+    }
+
+    fn lowest(&self) -> usize {
+        self.lowest
+    }
+
+    ///
+    /// Appends a record for each item and links it to the latest item of its author.
+    ///
+    /// The items of the block already linked to their author are not appended again, so a block
+    /// whose data was indexed in part before a crash is indexed again without duplicates.
+    ///
+    fn index_data(&mut self, block: &Block) -> Result<(), anyhow::Error> {
+        self.writable()?;
+        let mut hashes = HashTable::open(&self.folder.join("bc.dhs"))?;
+        let mut authors = HashTable::open(&self.folder.join("bc.dau"))?;
+
+        for (position, item) in block.data.iter().enumerate() {
+            let hash = item.hash();
+            let location = DataLocation { block: block.header.hash.clone(), height: block.header.height, position };
+            let author = author_key(&item.author);
+            let head = authors.get(&author)?;
+
+            // the items of the block are the latest ones of their authors
+            let mut pointer = head;
+            let mut indexed = None;
+            while let Some(record) = self.data_record(pointer)? {
+                match record.location {
+                    Some(l) if l == location => {
+                        indexed = pointer;
+                        break
+                    }
+                    Some(l) if l.block == location.block => pointer = record.previous,
+                    _ => break,
+                }
+            }
+
+            let (pos, len) = match indexed {
+                Some(pointer) => pointer,
+                None => {
+                    let pointer = self.append_data(&DataRecord { hash: hash.clone(), location: Some(location), previous: head })?;
+                    authors.insert(&author, pointer.0, pointer.1)?;
+                    pointer
+                }
+            };
+            hashes.insert(&hash, pos, len)?;
+        }
+
+        self.set_data_tip(&block.header.hash)
+    }
+
+    ///
+    /// Appends an empty record for each item of the block and moves the authors back to their previous item.
+    ///
+    fn unindex_data(&mut self, block: &Block) -> Result<(), anyhow::Error> {
+        self.writable()?;
+        let mut hashes = HashTable::open(&self.folder.join("bc.dhs"))?;
+        let mut authors = HashTable::open(&self.folder.join("bc.dau"))?;
+
+        for (position, item) in block.data.iter().enumerate().rev() {
+            let hash = item.hash();
+            let location = DataLocation { block: block.header.hash.clone(), height: block.header.height, position };
+            let author = author_key(&item.author);
+
+            if self.data_record(hashes.get(&hash)?)?.is_some_and(|r| r.location.is_some_and(|l| l.block == location.block)) {
+                let (pos, len) = self.append_data(&DataRecord { hash: hash.clone(), location: None, previous: None })?;
+                hashes.insert(&hash, pos, len)?;
+            }
+
+            let head = self.data_record(authors.get(&author)?)?;
+            if let Some(head) = head.filter(|r| r.location.as_ref() == Some(&location)) {
+                let previous = match head.previous {
+                    Some(previous) => previous,
+                    None => self.append_data(&DataRecord { hash: Vec::new(), location: None, previous: None })?,
+                };
+                authors.insert(&author, previous.0, previous.1)?;
+            }
+        }
+
+        self.set_data_tip(&block.header.parent)
+    }
+
+    fn clear_data(&mut self) -> Result<(), anyhow::Error> {
+        self.writable()?;
+        Self::remove_data(&self.folder)
+    }
+
+    fn data_tip(&self) -> Vec<u8> {
+        match journal::read_from(&self.folder.join("bc.dtp"), 0) {
+            Ok((records, _)) => records.into_iter().next().map(|(_, hash)| hash).unwrap_or_default(),
+            Err(e) => {
+                log::error!("could not read the data tip: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn data_location(&self, hash: &[u8]) -> Result<Option<DataLocation>, anyhow::Error> {
+        let table = match self.data_table("bc.dhs")? {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+
+        Ok(self.data_record(table.get(hash)?)?.and_then(|r| r.location))
+    }
+
+    fn author_data(&self, author: &[u8]) -> Result<Vec<Vec<u8>>, anyhow::Error> {
+        let table = match self.data_table("bc.dau")? {
+            Some(table) => table,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut hashes = Vec::new();
+        let mut current = self.data_record(table.get(&author_key(author))?)?;
+        while let Some(record) = current.filter(|r| r.location.is_some()) {
+            current = self.data_record(record.previous)?;
+            hashes.push(record.hash);
+        }
+        hashes.reverse();
+
+        Ok(hashes)
+    }
+}
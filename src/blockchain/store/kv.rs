@@ -0,0 +1,241 @@
+use std::path::Path;
+
+use crate::blockchain::{
+    block::{Block, BlockHeader},
+    index::DataLocation,
+};
+
+use super::BlockStore;
+
+
+///
+/// A [BlockStore] on top of the embedded [sled] database in `bc.sled`.
+///
+/// Blocks and headers are kept in separate trees keyed by hash, the main chain in a tree keyed by height.
+/// The lowest complete height is kept in the default tree, sled reclaims the space of pruned blocks on its own.
+///
+/// The data index is a tree of the data locations keyed by data hash, and a tree of the data hashes
+/// keyed by author, height and position, so the items of an author are in chain order.
+///
+pub struct SledStore {
+    db: sled::Db,
+    blocks: sled::Tree,
+    headers: sled::Tree,
+    heights: sled::Tree,
+    data: sled::Tree,
+    authors: sled::Tree,
+    tip: Vec<u8>,
+    lowest: usize,
+}
+
+const LOWEST: &[u8] = b"lowest";
+const DATA_TIP: &[u8] = b"data_tip";
+
+fn height_key(height: usize) -> [u8; 8] {
+    (height as u64).to_be_bytes()
+}
+
+fn author_key(author: &[u8], location: &DataLocation) -> Vec<u8> {
+    let mut key = author.to_vec();
+    key.extend(height_key(location.height));
+    key.extend((location.position as u64).to_be_bytes());
+    key
+}
+
+impl SledStore {
+    pub fn open(folder: &Path) -> Result<Self, anyhow::Error> {
+        let db = sled::open(folder.join("bc.sled"))?;
+        let blocks = db.open_tree("blocks")?;
+        let headers = db.open_tree("headers")?;
+        let heights = db.open_tree("heights")?;
+        let data = db.open_tree("data")?;
+        let authors = db.open_tree("authors")?;
+        let tip = heights.last()?.map(|(_, v)| v.to_vec()).unwrap_or_default();
+        let lowest = match db.get(LOWEST)? {
+            Some(v) => u64::from_be_bytes(v.as_ref().try_into()?) as usize,
+            None => 1,
+        };
+
+        Ok(Self {
+            db,
+            blocks,
+            headers,
+            heights,
+            data,
+            authors,
+            tip,
+            lowest,
+        })
+    }
+}
+
+impl BlockStore for SledStore {
+    fn put(&mut self, block: &Block) -> Result<(), anyhow::Error> {
+        let hash = block.header.hash.as_slice();
+        self.blocks.insert(hash, rmp_serde::to_vec_named(block)?)?;
+        self.headers.insert(hash, rmp_serde::to_vec_named(&block.header)?)?;
+        Ok(())
+    }
+
+    fn put_header(&mut self, header: &BlockHeader) -> Result<(), anyhow::Error> {
+        self.headers.insert(header.hash.as_slice(), rmp_serde::to_vec_named(header)?)?;
+        self.lowest = self.lowest.max(header.height + 1);
+        self.db.insert(LOWEST, &height_key(self.lowest))?;
+        Ok(())
+    }
+
+    fn get(&self, hash: &[u8]) -> Result<Option<Block>, anyhow::Error> {
+        match self.blocks.get(hash)? {
+            Some(data) => Ok(Some(rmp_serde::from_slice(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn header(&self, hash: &[u8]) -> Result<Option<BlockHeader>, anyhow::Error> {
+        match self.headers.get(hash)? {
+            Some(data) => Ok(Some(rmp_serde::from_slice(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn hash_at(&self, height: usize) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        Ok(self.heights.get(height_key(height))?.map(|h| h.to_vec()))
+    }
+
+    fn tip(&self) -> Vec<u8> {
+        self.tip.clone()
+    }
+
+    fn set_tip(&mut self, hash: &[u8]) -> Result<(), anyhow::Error> {
+        let mut branch = Vec::new();
+        let mut current = hash.to_vec();
+        let mut height = 0;
+
+        while !current.is_empty() {
+            let header = self.header(&current)?
+                .ok_or_else(|| anyhow::anyhow!("unknown block {}", hex::encode(&current)))?;
+
+            if self.heights.get(height_key(header.height))?.as_deref() == Some(current.as_slice()) {
+                height = header.height;
+                break
+            }
+
+            branch.push((header.height, current));
+            current = header.parent;
+        }
+
+        // later writes to the same key win, so the branch replaces the removed heights
+        let mut batch = sled::Batch::default();
+        for key in self.heights.range(height_key(height + 1)..).keys() {
+            batch.remove(key?);
+        }
+        for (height, hash) in branch {
+            batch.insert(&height_key(height), hash);
+        }
+
+        self.heights.apply_batch(batch)?;
+        self.tip = hash.to_vec();
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), anyhow::Error> {
+        self.db.flush()?;
+        Ok(())
+    }
+
+    ///
+    /// Removes the blocks of the main chain between the lowest kept height and `height`,
+    /// their headers stay.
+    ///
+    fn prune(&mut self, height: usize) -> Result<(), anyhow::Error> {
+        if height <= self.lowest {
+            return Ok(())
+        }
+
+        let mut batch = sled::Batch::default();
+        for hash in self.heights.range(height_key(self.lowest)..height_key(height)).values() {
+            batch.remove(hash?);
+        }
+        self.blocks.apply_batch(batch)?;
+
+        self.lowest = self.lowest.max(height);
+        self.db.insert(LOWEST, &height_key(self.lowest))?;
+        self.db.flush()?;
+
+        Ok(())
+    }
+
+    fn lowest(&self) -> usize {
+        self.lowest
+    }
+
+    fn index_data(&mut self, block: &Block) -> Result<(), anyhow::Error> {
+        let mut data = sled::Batch::default();
+        let mut authors = sled::Batch::default();
+
+        for (position, item) in block.data.iter().enumerate() {
+            let hash = item.hash();
+            let location = DataLocation { block: block.header.hash.clone(), height: block.header.height, position };
+            authors.insert(author_key(&item.author, &location), hash.as_slice());
+            data.insert(hash, rmp_serde::to_vec_named(&location)?);
+        }
+
+        self.data.apply_batch(data)?;
+        self.authors.apply_batch(authors)?;
+        self.db.insert(DATA_TIP, block.header.hash.as_slice())?;
+
+        Ok(())
+    }
+
+    fn unindex_data(&mut self, block: &Block) -> Result<(), anyhow::Error> {
+        let mut data = sled::Batch::default();
+        let mut authors = sled::Batch::default();
+
+        for (position, item) in block.data.iter().enumerate() {
+            let hash = item.hash();
+            let location = DataLocation { block: block.header.hash.clone(), height: block.header.height, position };
+            authors.remove(author_key(&item.author, &location));
+            if self.data_location(&hash)?.is_some_and(|l| l.block == block.header.hash) {
+                data.remove(hash);
+            }
+        }
+
+        self.data.apply_batch(data)?;
+        self.authors.apply_batch(authors)?;
+        self.db.insert(DATA_TIP, block.header.parent.as_slice())?;
+
+        Ok(())
+    }
+
+    fn clear_data(&mut self) -> Result<(), anyhow::Error> {
+        self.data.clear()?;
+        self.authors.clear()?;
+        self.db.remove(DATA_TIP)?;
+        Ok(())
+    }
+
+    fn data_tip(&self) -> Vec<u8> {
+        self.db.get(DATA_TIP).ok().flatten().map(|v| v.to_vec()).unwrap_or_default()
+    }
+
+    fn data_location(&self, hash: &[u8]) -> Result<Option<DataLocation>, anyhow::Error> {
+        match self.data.get(hash)? {
+            Some(data) => Ok(Some(rmp_serde::from_slice(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn author_data(&self, author: &[u8]) -> Result<Vec<Vec<u8>>, anyhow::Error> {
+        let mut hashes = Vec::new();
+        for entry in self.authors.scan_prefix(author) {
+            let (key, hash) = entry?;
+            // a key which is the prefix of another one would also match
+            if key.len() == author.len() + 16 {
+                hashes.push(hash.to_vec());
+            }
+        }
+
+        Ok(hashes)
+    }
+}
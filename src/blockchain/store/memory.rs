@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use crate::blockchain::{
+    block::{Block, BlockHeader},
+    index::{DataIndex, DataLocation},
+};
+
+use super::BlockStore;
+
+
+///
+/// A [BlockStore] which keeps everything in memory, mostly useful for tests.
+///
+pub struct MemoryStore {
+    blocks: HashMap<Vec<u8>, Block>,
+    headers: HashMap<Vec<u8>, BlockHeader>,
+    main: Vec<Vec<u8>>,
+    lowest: usize,
+    data: DataIndex,
+    data_tip: Vec<u8>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self {
+            blocks: HashMap::new(),
+            headers: HashMap::new(),
+            main: Vec::new(),
+            lowest: 1,
+            data: DataIndex::new(),
+            data_tip: Vec::new(),
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockStore for MemoryStore {
+    fn put(&mut self, block: &Block) -> Result<(), anyhow::Error> {
+        self.blocks.insert(block.header.hash.clone(), block.clone());
+        self.headers.insert(block.header.hash.clone(), block.header.clone());
+        Ok(())
+    }
+
+    fn put_header(&mut self, header: &BlockHeader) -> Result<(), anyhow::Error> {
+        self.headers.insert(header.hash.clone(), header.clone());
+        self.lowest = self.lowest.max(header.height + 1);
+        Ok(())
+    }
+
+    fn get(&self, hash: &[u8]) -> Result<Option<Block>, anyhow::Error> {
+        Ok(self.blocks.get(hash).cloned())
+    }
+
+    fn header(&self, hash: &[u8]) -> Result<Option<BlockHeader>, anyhow::Error> {
+        Ok(self.headers.get(hash).cloned())
+    }
+
+    fn hash_at(&self, height: usize) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        Ok(height.checked_sub(1).and_then(|i| self.main.get(i)).cloned())
+    }
+
+    fn tip(&self) -> Vec<u8> {
+        self.main.last().cloned().unwrap_or_default()
+    }
+
+    fn set_tip(&mut self, hash: &[u8]) -> Result<(), anyhow::Error> {
+        let mut branch = Vec::new();
+        let mut current = hash.to_vec();
+        let mut height = 0;
+
+        while !current.is_empty() {
+            let header = self.headers.get(&current)
+                .ok_or_else(|| anyhow::anyhow!("unknown block {}", hex::encode(&current)))?;
+
+            if self.main.get(header.height - 1) == Some(&current) {
+                height = header.height;
+                break
+            }
+
+            let parent = header.parent.clone();
+            branch.push(current);
+            current = parent;
+        }
+
+        self.main.truncate(height);
+        self.main.extend(branch.into_iter().rev());
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    fn prune(&mut self, height: usize) -> Result<(), anyhow::Error> {
+        self.blocks.retain(|_, b| b.header.height >= height);
+        self.lowest = self.lowest.max(height);
+        Ok(())
+    }
+
+    fn lowest(&self) -> usize {
+        self.lowest
+    }
+
+    fn index_data(&mut self, block: &Block) -> Result<(), anyhow::Error> {
+        if self.data_tip == block.header.hash {
+            return Ok(())
+        }
+
+        self.data.add_block(block);
+        self.data_tip = block.header.hash.clone();
+        Ok(())
+    }
+
+    fn unindex_data(&mut self, block: &Block) -> Result<(), anyhow::Error> {
+        self.data.remove_block(block);
+        self.data_tip = block.header.parent.clone();
+        Ok(())
+    }
+
+    fn clear_data(&mut self) -> Result<(), anyhow::Error> {
+        self.data = DataIndex::new();
+        self.data_tip.clear();
+        Ok(())
+    }
+
+    fn data_tip(&self) -> Vec<u8> {
+        self.data_tip.clone()
+    }
+
+    fn data_location(&self, hash: &[u8]) -> Result<Option<DataLocation>, anyhow::Error> {
+        Ok(self.data.get(hash).cloned())
+    }
+
+    fn author_data(&self, author: &[u8]) -> Result<Vec<Vec<u8>>, anyhow::Error> {
+        Ok(self.data.by_author(author).to_vec())
+    }
+}
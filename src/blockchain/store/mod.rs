@@ -0,0 +1,133 @@
+pub mod file;
+pub mod kv;
+pub mod memory;
+pub mod table;
+
+use std::path::Path;
+
+use crate::config::StoreKind;
+
+use super::{block::{Block, BlockHeader}, index::DataLocation};
+
+pub use self::{
+    file::FileStore,
+    kv::SledStore,
+    memory::MemoryStore,
+};
+
+///
+/// The storage backend of a [Blockchain](super::Blockchain).
+///
+/// A store keeps every block it is given, on the main chain or on a side branch.
+/// The main chain is the branch ending in the tip set by [BlockStore::set_tip].
+/// Once pruned with [BlockStore::prune], only the headers of the old blocks are left.
+///
+pub trait BlockStore: Send {
+    /// Stores `block` without changing the main chain.
+    fn put(&mut self, block: &Block) -> Result<(), anyhow::Error>;
+
+    /// Stores a header without its body, like the header of a pruned block.
+    fn put_header(&mut self, header: &BlockHeader) -> Result<(), anyhow::Error>;
+
+    /// Loads the block with `hash`.
+    fn get(&self, hash: &[u8]) -> Result<Option<Block>, anyhow::Error>;
+
+    /// Loads only the header of the block with `hash`.
+    fn header(&self, hash: &[u8]) -> Result<Option<BlockHeader>, anyhow::Error>;
+
+    /// The hash of the main chain block at `height`.
+    fn hash_at(&self, height: usize) -> Result<Option<Vec<u8>>, anyhow::Error>;
+
+    /// The hash of the main chain tip, empty if there are no blocks.
+    fn tip(&self) -> Vec<u8>;
+
+    /// Makes the branch ending in `hash` the main chain.
+    fn set_tip(&mut self, hash: &[u8]) -> Result<(), anyhow::Error>;
+
+    /// Writes all pending changes to the storage medium.
+    fn flush(&mut self) -> Result<(), anyhow::Error>;
+
+    /// Drops the bodies of the main chain blocks below `height` and reclaims their space, the headers are kept.
+    /// Blocks of side branches may be kept.
+    fn prune(&mut self, height: usize) -> Result<(), anyhow::Error>;
+
+    /// The lowest main chain height with a complete block, `1` if nothing was pruned.
+    fn lowest(&self) -> usize;
+
+    /// Adds the data items of `block` to the data index, the block follows the [data tip](BlockStore::data_tip).
+    fn index_data(&mut self, block: &Block) -> Result<(), anyhow::Error>;
+
+    /// Removes the data items of `block` from the data index, the block is the [data tip](BlockStore::data_tip).
+    fn unindex_data(&mut self, block: &Block) -> Result<(), anyhow::Error>;
+
+    /// Drops the whole data index.
+    fn clear_data(&mut self) -> Result<(), anyhow::Error>;
+
+    /// The last block whose data is indexed, empty if there is none.
+    /// It lags behind the tip, if the store stopped before the data index was updated.
+    fn data_tip(&self) -> Vec<u8>;
+
+    /// Where the data item with `hash` is stored, as far as the data index knows.
+    fn data_location(&self, hash: &[u8]) -> Result<Option<DataLocation>, anyhow::Error>;
+
+    /// The hashes of the data items of `author` in the data index, in chain order.
+    fn author_data(&self, author: &[u8]) -> Result<Vec<Vec<u8>>, anyhow::Error>;
+
+    /// Loads the block of the main chain at `height`.
+    fn get_by_height(&self, height: usize) -> Result<Option<Block>, anyhow::Error> {
+        match self.hash_at(height)? {
+            Some(hash) => self.get(&hash),
+            None => Ok(None),
+        }
+    }
+
+    /// Iterates over the complete blocks of the main chain, from the [lowest](BlockStore::lowest) to the tip.
+    fn iter(&self) -> Box<dyn Iterator<Item=Result<Block, anyhow::Error>> + '_> {
+        let height = match self.header(&self.tip()) {
+            Ok(header) => header.map(|h| h.height).unwrap_or(0),
+            Err(e) => return Box::new(std::iter::once(Err(e))),
+        };
+
+        Box::new((self.lowest()..=height).map(move |h| {
+            self.get_by_height(h)?.ok_or_else(|| anyhow::anyhow!("missing block at height {}", h))
+        }))
+    }
+}
+
+///
+/// Opens the store of the given kind in `folder`.
+///
+pub fn open(kind: &StoreKind, folder: &str) -> Result<Box<dyn BlockStore>, anyhow::Error> {
+    let folder = Path::new(folder);
+
+    if !folder.exists() {
+        std::fs::create_dir_all(folder)?;
+    }
+
+    Ok(match kind {
+        StoreKind::File => Box::new(FileStore::open(folder)?),
+        StoreKind::Sled => Box::new(SledStore::open(folder)?),
+        StoreKind::Memory => Box::new(MemoryStore::new()),
+    })
+}
+
+///
+/// Opens the existing store of the given kind in `folder` to inspect it.
+///
+/// The file store is opened with [FileStore::open_read_only]. Sled has no read-only mode and
+/// locks its database, so a sled store can only be opened while the node is not running.
+///
+pub fn open_read_only(kind: &StoreKind, folder: &str) -> Result<Box<dyn BlockStore>, anyhow::Error> {
+    let folder = Path::new(folder);
+
+    if !folder.is_dir() {
+        anyhow::bail!("no node folder {}", folder.display());
+    }
+
+    Ok(match kind {
+        StoreKind::File => Box::new(FileStore::open_read_only(folder)?),
+        StoreKind::Sled if folder.join("bc.sled").exists() => Box::new(SledStore::open(folder)?),
+        StoreKind::Sled => anyhow::bail!("no sled database in {}", folder.display()),
+        StoreKind::Memory => anyhow::bail!("a memory store cannot be opened from a folder"),
+    })
+}
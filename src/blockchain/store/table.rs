@@ -0,0 +1,247 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+
+/// The magic and the version, followed by `count` and the [Marks], each as big endian `u64`.
+const HEADER: u64 = 48;
+/// Marks the file as a hash table of the index.
+const MAGIC: &[u8; 4] = b"mcix";
+///
+/// The version of the index files, the table, the index journal and the height records.
+///
+/// Increased whenever one of them changes, an index of another version is rebuilt from the data file.
+///
+pub const VERSION: u32 = 2;
+/// The 32 byte hash followed by position and length of the index record.
+const SLOT: u64 = 48;
+const MIN_CAPACITY: u64 = 1024;
+
+///
+/// How far the files of a [FileStore](super::FileStore) are indexed.
+///
+/// Positions in `bc.db` count from the start of the file before pruning cut off its front.
+///
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Marks {
+    /// The end of the data file `bc.db` up to which all blocks are indexed.
+    pub db_end: u64,
+    /// The end of the index journal.
+    pub idx_end: u64,
+    /// The end of the header journal `bc.hdr` up to which all headers are indexed.
+    pub hdr_end: u64,
+    /// The number of bytes cut off the front of `bc.db`.
+    pub db_base: u64,
+}
+
+///
+/// An on-disk hash table from block hash to the position of its record in the index journal.
+///
+/// The table uses open addressing with linear probing and doubles its capacity once it is half full.
+/// The header also holds the [Marks] up to which the files of the store are indexed.
+///
+pub struct HashTable {
+    path: PathBuf,
+    capacity: u64,
+    count: u64,
+    marks: Marks,
+}
+
+fn slot_of(hash: &[u8], capacity: u64) -> u64 {
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&hash[..8]);
+    u64::from_be_bytes(prefix) & (capacity - 1)
+}
+
+fn check_hash(hash: &[u8]) -> io::Result<()> {
+    if hash.len() == 32 {
+        Ok(())
+    }
+    else {
+        Err(io::Error::new(ErrorKind::InvalidInput, "block hashes have 32 bytes"))
+    }
+}
+
+impl HashTable {
+    ///
+    /// Opens the table at `path`, an empty one is created if there is none.
+    ///
+    /// Fails with [ErrorKind::InvalidData] if the file is no table of this [VERSION].
+    ///
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if !path.exists() {
+            Self::create(path, MIN_CAPACITY)?;
+        }
+
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut header = [0u8; HEADER as usize];
+        if len < HEADER || file.read_exact(&mut header).is_err() || &header[..4] != MAGIC
+            || header[4..8] != VERSION.to_be_bytes() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "index of another version"))
+        }
+
+        let field = |i: usize| {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(&header[8 + i * 8..16 + i * 8]);
+            u64::from_be_bytes(buf)
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            capacity: (len - HEADER) / SLOT,
+            count: field(0),
+            marks: Marks {
+                db_end: field(1),
+                idx_end: field(2),
+                hdr_end: field(3),
+                db_base: field(4),
+            },
+        })
+    }
+
+    fn create(path: &Path, capacity: u64) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(MAGIC)?;
+        file.write_all(&VERSION.to_be_bytes())?;
+        file.set_len(HEADER + capacity * SLOT)?;
+        file.sync_all()
+    }
+
+    pub fn marks(&self) -> Marks {
+        self.marks
+    }
+
+    pub fn set_marks(&mut self, marks: Marks) -> io::Result<()> {
+        self.marks = marks;
+        self.write_header()
+    }
+
+    fn write_header(&self) -> io::Result<()> {
+        let mut header = Vec::with_capacity(HEADER as usize);
+        header.extend(MAGIC);
+        header.extend(VERSION.to_be_bytes());
+        header.extend(self.count.to_be_bytes());
+        header.extend(self.marks.db_end.to_be_bytes());
+        header.extend(self.marks.idx_end.to_be_bytes());
+        header.extend(self.marks.hdr_end.to_be_bytes());
+        header.extend(self.marks.db_base.to_be_bytes());
+
+        let mut file = OpenOptions::new().write(true).open(&self.path)?;
+        file.write_all(&header)?;
+        file.sync_data()
+    }
+
+    fn read_slot(file: &mut File, slot: u64) -> io::Result<([u8; SLOT as usize], u64, u64)> {
+        let mut buf = [0u8; SLOT as usize];
+        file.seek(SeekFrom::Start(HEADER + slot * SLOT))?;
+        file.read_exact(&mut buf)?;
+
+        let mut pos = [0u8; 8];
+        let mut len = [0u8; 8];
+        pos.copy_from_slice(&buf[32..40]);
+        len.copy_from_slice(&buf[40..48]);
+
+        Ok((buf, u64::from_be_bytes(pos), u64::from_be_bytes(len)))
+    }
+
+    ///
+    /// Looks up the position and length of the index record of `hash`.
+    ///
+    pub fn get(&self, hash: &[u8]) -> io::Result<Option<(u64, u64)>> {
+        if hash.len() != 32 {
+            return Ok(None)
+        }
+
+        let mut file = File::open(&self.path)?;
+        let mut slot = slot_of(hash, self.capacity);
+
+        loop {
+            let (buf, pos, len) = Self::read_slot(&mut file, slot)?;
+
+            if len == 0 {
+                return Ok(None)
+            }
+            if &buf[..32] == hash {
+                return Ok(Some((pos, len)))
+            }
+
+            slot = (slot + 1) % self.capacity;
+        }
+    }
+
+    pub fn insert(&mut self, hash: &[u8], pos: u64, len: u64) -> io::Result<()> {
+        check_hash(hash)?;
+
+        if (self.count + 1) * 2 > self.capacity {
+            self.grow()?;
+        }
+
+        let mut file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        let mut slot = slot_of(hash, self.capacity);
+
+        loop {
+            let (buf, _, slot_len) = Self::read_slot(&mut file, slot)?;
+
+            if slot_len == 0 || &buf[..32] == hash {
+                if slot_len == 0 {
+                    self.count += 1;
+                }
+                break
+            }
+
+            slot = (slot + 1) % self.capacity;
+        }
+
+        let mut record = Vec::with_capacity(SLOT as usize);
+        record.extend(hash);
+        record.extend(pos.to_be_bytes());
+        record.extend(len.to_be_bytes());
+
+        file.seek(SeekFrom::Start(HEADER + slot * SLOT))?;
+        file.write_all(&record)?;
+        file.sync_data()?;
+
+        self.write_header()
+    }
+
+    ///
+    /// Rehashes all entries into a table of double the capacity, which then replaces this one.
+    ///
+    fn grow(&mut self) -> io::Result<()> {
+        log::debug!("grow hash table to {} slots", self.capacity * 2);
+
+        let old = std::fs::read(&self.path)?;
+        let capacity = self.capacity * 2;
+        let mut new = vec![0u8; (HEADER + capacity * SLOT) as usize];
+        new[..HEADER as usize].copy_from_slice(&old[..HEADER as usize]);
+
+        for entry in old[HEADER as usize..].chunks(SLOT as usize) {
+            if entry[40..48] == [0u8; 8] {
+                continue
+            }
+
+            let mut slot = slot_of(&entry[..32], capacity);
+            loop {
+                let start = (HEADER + slot * SLOT) as usize;
+                if new[start + 40..start + 48] == [0u8; 8] {
+                    new[start..start + SLOT as usize].copy_from_slice(entry);
+                    break
+                }
+                slot = (slot + 1) % capacity;
+            }
+        }
+
+        let tmp = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&new)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+
+        self.capacity = capacity;
+
+        Ok(())
+    }
+}
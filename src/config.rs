@@ -3,6 +3,58 @@ use std::path::Path;
 use serde::{Serialize, Deserialize};
 
 
+///
+/// The storage backend of the blockchain.
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    /// The `bc.db` data file with the `bc.idx` index journal.
+    #[default]
+    File,
+    /// An embedded sled database in `bc.sled`.
+    Sled,
+    /// Keeps everything in memory, nothing is persisted.
+    Memory,
+}
+
+///
+/// Which block bodies a pruning node keeps, the headers of all blocks are always kept.
+///
+/// **TOML Example:**
+/// ```toml
+/// prune = { blocks = 1000 }
+/// ```
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Retention {
+    /// Keeps the last `n` blocks of the main chain.
+    Blocks(usize),
+    /// Keeps the blocks younger than the given number of seconds.
+    Age(u64),
+}
+
+///
+/// The leader election which decides the producer of each block.
+///
+/// **TOML Example:**
+/// ```toml
+/// consensus = { authority = "02c6...e5" }
+/// ```
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsensusKind {
+    /// All known nodes play a tournament, the winner produces the block.
+    #[default]
+    Highlander,
+    /// The known nodes produce the blocks in turn, in the order of their keys.
+    RoundRobin,
+    /// Only the node with the hex encoded public key produces blocks.
+    Authority(String),
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ClientConfig {
     pub host: String,
@@ -20,6 +72,7 @@ pub struct ClientConfig {
 /// port = 39093
 /// folder = "data/"
 /// thin = false
+/// store = "file"
 /// clients = []
 /// ```
 /// 
@@ -33,17 +86,67 @@ pub struct Config {
     pub thin: bool,
     /// Folder where to store data. Defaults to `data/`.
     pub folder: String,
+    /// The storage backend of the blockchain. Defaults to `file`.
+    #[serde(default)]
+    pub store: StoreKind,
+    /// Prunes old blocks if set, by default all blocks are kept.
+    #[serde(default)]
+    pub prune: Option<Retention>,
+    /// The hex encoded hash of a trusted [Snapshot](crate::blockchain::Snapshot).
+    /// A node with an empty chain starts from it instead of downloading all blocks.
+    #[serde(default)]
+    pub checkpoint: Option<String>,
+    /// The file of the genesis block, its hash identifies the network.
+    /// Peers on another network are refused.
+    #[serde(default)]
+    pub genesis: Option<String>,
+    /// The seconds each phase of a tournament waits for the other nodes, nodes which do not
+    /// respond in time are dropped from the tournament. Defaults to `10`.
+    #[serde(default = "default_round_timeout")]
+    pub round_timeout: u64,
+    /// The number of nodes drawn from the known nodes to play each tournament, the draw is seeded
+    /// by the previous block, at least one. All nodes play by default.
+    #[serde(default)]
+    pub committee: Option<usize>,
+    /// The leader election of the network. Defaults to `highlander`.
+    #[serde(default)]
+    pub consensus: ConsensusKind,
+    /// The file of the key of the node, which is created if it does not exist.
+    /// By default the node uses a new key on every start.
+    #[serde(default)]
+    pub key: Option<String>,
     /// The other nodes to connect to.
     pub clients: Vec<ClientConfig>
 }
 
+fn default_round_timeout() -> u64 {
+    10
+}
+
 impl Config {
     ///
     /// Load [Config] from filename.
     ///
     pub async fn load(filename: &str) -> Result<Config, anyhow::Error> {
         let data = tokio::fs::read(filename).await?;
-        Ok(toml::from_slice(&data)?)
+        let config: Config = toml::from_slice(&data)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    ///
+    /// Rejects settings which parse, but cannot work.
+    ///
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.prune == Some(Retention::Blocks(0)) {
+            anyhow::bail!("prune = {{ blocks = 0 }} would prune the tip, keep at least one block");
+        }
+        if self.committee == Some(0) {
+            anyhow::bail!("committee = 0 would draw no node to produce the blocks, draw at least one");
+        }
+
+        Ok(())
     }
 
     pub async fn save(&self, filename: &str) -> Result<(), anyhow::Error> {
@@ -66,6 +169,14 @@ impl Default for Config {
             port: 39093,
             thin: false,
             folder: "data/".to_owned(),
+            store: StoreKind::File,
+            prune: None,
+            checkpoint: None,
+            genesis: None,
+            round_timeout: default_round_timeout(),
+            committee: None,
+            consensus: ConsensusKind::Highlander,
+            key: None,
             clients: Vec::new(),
         }
     }
@@ -0,0 +1,67 @@
+use std::{collections::HashSet, time::Duration};
+
+use crate::{
+    blockchain::{Block, BlockHeader, block::Proof},
+    key::{Key, PubKey},
+    network::message::Message,
+};
+
+use super::{block_deadline, Consensus, Decision, Step};
+
+///
+/// A [Consensus] of a single authority, only the node with the configured key produces blocks.
+///
+/// The other nodes only relay and validate the blocks, the chain stops while the authority is offline.
+///
+pub struct Authority {
+    authority: PubKey,
+    rounds: u64,
+}
+
+impl Authority {
+    pub fn new(authority: PubKey) -> Self {
+        Self {
+            authority,
+            rounds: 0,
+        }
+    }
+}
+
+impl Consensus for Authority {
+    fn start_round(&mut self, _parent: &[u8], _epoch: usize, _nodes: &HashSet<PubKey>, key: &Key) -> Step {
+        self.rounds += 1;
+
+        if key.public_key == self.authority {
+            Step::decide(Decision::Produce {
+                proof: Proof::Elected,
+                delay: Duration::ZERO,
+            })
+        }
+        else {
+            Step::decide(Decision::Wait {
+                deadline: block_deadline(0),
+            })
+        }
+    }
+
+    fn handle(&mut self, _msg: &Message, _key: &Key) -> Step {
+        Step::default()
+    }
+
+    fn timeout(&mut self, _key: &Key) -> Step {
+        Step::default()
+    }
+
+    fn round(&self) -> u64 {
+        self.rounds
+    }
+
+    fn validate(&self, block: &Block, _parent: Option<&BlockHeader>) -> bool {
+        if block.header.author != self.authority || !matches!(block.proof, Proof::Elected) {
+            log::error!("block {} is not made by the authority", hex::encode(&block.header.hash));
+            return false
+        }
+
+        true
+    }
+}
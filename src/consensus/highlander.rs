@@ -0,0 +1,417 @@
+use std::{collections::{HashSet, VecDeque}, time::Duration};
+
+use crate::{
+    blockchain::{Block, BlockHeader, block::{FALLBACK_DELAY, Proof}},
+    highlander::{Game, GameError, GameResult, Highlander},
+    key::{Key, PubKey},
+    network::message::Message,
+};
+
+use super::{block_deadline, Consensus, Decision, Step, Views};
+
+/// The number of messages kept of the rounds this node did not start yet.
+const MAX_EARLY: usize = 256;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum Phase {
+    Idle,
+    Commit,
+    Reveal,
+}
+
+///
+/// What this node saw of a tournament it played.
+///
+struct View {
+    /// The hash of the roster this node started the tournament with.
+    roster_hash: Vec<u8>,
+    /// The nodes this node drew the committee from, empty if all nodes play.
+    population: Vec<PubKey>,
+}
+
+///
+/// The tournament a consensus message belongs to, by the parent and the height of its block, and its author.
+///
+fn origin(msg: &Message) -> Option<(&[u8], usize, &PubKey)> {
+    match msg {
+        Message::Commit { commitment } => Some((commitment.parent(), commitment.epoch(), commitment.author())),
+        Message::Play { game } => Some((game.parent(), game.epoch(), game.author())),
+        Message::Vote { vote } => Some((vote.parent(), vote.epoch(), vote.author())),
+        _ => None,
+    }
+}
+
+///
+/// The default [Consensus], all known nodes play a [Highlander] tournament for the block.
+///
+/// A round has two phases, first every node commits to its game and then reveals it. After the
+/// round timeout of a phase the players vote for the nodes they are missing, a node is dropped once
+/// a majority of the roster voted for it. Without a majority after another timeout the round is
+/// given up. The winner produces the block, the other ranked nodes follow as fallback producers.
+///
+/// With a committee size, only a [committee](crate::highlander::committee) of the known nodes
+/// drawn from the parent block plays. The other nodes follow the tournament without a game
+/// and relay the messages of the committee.
+///
+/// A block has to be decided by a tournament on the members its parent names, or the committee
+/// drawn from them. The votes which dropped a node are part of the result, so every node checks
+/// the dropped nodes the same way.
+///
+pub struct HighlanderConsensus {
+    highlander: Highlander,
+    phase: Phase,
+    /// The own game of the running tournament until it is revealed.
+    game: Option<Game>,
+    /// How long each phase of a tournament waits for the other nodes.
+    round_timeout: Duration,
+    /// The number of nodes drawn to play each tournament, all nodes play if not set.
+    committee: Option<usize>,
+    /// Counts the phases, a deadline of an earlier phase is dropped.
+    steps: u64,
+    /// If this node voted in the running phase already.
+    voted: bool,
+    /// The messages of the rounds this node did not start yet, the oldest are dropped.
+    early: VecDeque<Message>,
+    /// The tournaments this node played.
+    views: Views<View>,
+}
+
+impl HighlanderConsensus {
+    pub fn new(round_timeout: Duration, committee: Option<usize>) -> Self {
+        Self {
+            highlander: Highlander::new(),
+            phase: Phase::Idle,
+            game: None,
+            round_timeout,
+            committee,
+            steps: 0,
+            voted: false,
+            early: VecDeque::new(),
+            views: Views::new(),
+        }
+    }
+
+    ///
+    /// Keeps a message of a round which is not started yet, a later message of the same kind,
+    /// round and author replaces the commitment or the game kept before.
+    ///
+    fn keep(&mut self, msg: &Message) {
+        let early = match msg {
+            Message::Commit { commitment } => Message::Commit { commitment: commitment.clone() },
+            Message::Play { game } => Message::Play { game: game.clone() },
+            Message::Vote { vote } => Message::Vote { vote: vote.clone() },
+            _ => return,
+        };
+
+        if !matches!(early, Message::Vote { .. }) {
+            self.early.retain(|kept| {
+                std::mem::discriminant(kept) != std::mem::discriminant(&early) || origin(kept) != origin(&early)
+            });
+        }
+        if self.early.len() == MAX_EARLY {
+            self.early.pop_front();
+        }
+        self.early.push_back(early);
+    }
+
+    ///
+    /// Handles the kept messages of the round just started, the valid ones are passed on.
+    /// The messages of later rounds stay kept, the ones of earlier rounds are dropped.
+    ///
+    fn replay(&mut self, step: &mut Step, key: &Key) {
+        let parent = self.highlander.parent().to_vec();
+        let epoch = self.highlander.epoch();
+
+        for msg in std::mem::take(&mut self.early) {
+            let (current, later) = match origin(&msg) {
+                Some((p, e, _)) => (p == parent && e == epoch, e > epoch),
+                None => (false, false),
+            };
+            if !current {
+                if later {
+                    self.early.push_back(msg);
+                }
+                continue
+            }
+
+            let handled = self.handle(&msg, key);
+            step.broadcast.extend(handled.broadcast);
+            if handled.relay {
+                step.broadcast.push(msg);
+            }
+            step.deadline = handled.deadline.or(step.deadline);
+            step.decision = handled.decision.or(step.decision.take());
+        }
+    }
+
+    ///
+    /// Populates the roster of `hl` with `nodes`, or the committee drawn from them.
+    ///
+    fn populate<'a, T: Iterator<Item=&'a PubKey>>(hl: &mut Highlander, nodes: T, committee: Option<usize>) {
+        match committee {
+            Some(size) => hl.populate_committee(nodes, size),
+            None => hl.populate_roster(nodes),
+        }
+    }
+
+    ///
+    /// The roster hash and the population of the tournament for `block`, played by the members
+    /// `parent` names. Without the parent, as this node played it.
+    ///
+    /// The committee is drawn from the parent of the block, so it does not depend on the block.
+    ///
+    fn roster(&self, block: &Block, parent: Option<&BlockHeader>) -> Option<(Vec<u8>, Vec<PubKey>)> {
+        let parent = match parent {
+            Some(parent) => parent,
+            None => {
+                let view = self.views.get(&block.header.parent, block.header.height)?;
+                return Some((view.roster_hash.clone(), view.population.clone()))
+            }
+        };
+
+        let mut hl = Highlander::new();
+        hl.set_epoch(&block.header.parent, block.header.height);
+        Self::populate(&mut hl, parent.members.iter(), self.committee);
+        Some((hl.roster_hash().to_vec(), hl.population().to_vec()))
+    }
+
+    ///
+    /// Reveals the own game, once the commitments of all nodes are in.
+    ///
+    fn reveal(&mut self, step: &mut Step) {
+        self.phase = Phase::Reveal;
+        self.steps += 1;
+        self.voted = false;
+        step.deadline = Some(self.round_timeout);
+
+        if let Some(game) = self.game.take() {
+            if let Err(e) = self.highlander.add_game(game.clone()) {
+                log::error!("could not add own game: {}", e);
+            }
+            step.broadcast.push(Message::Play { game });
+        }
+    }
+
+    ///
+    /// Moves on to the next phase, once the nodes which were not dropped are in.
+    ///
+    fn advance(&mut self, step: &mut Step, key: &Key) {
+        if self.phase == Phase::Commit && self.highlander.is_committed() {
+            self.reveal(step);
+        }
+
+        self.evaluate(step, key);
+    }
+
+    ///
+    /// Evaluates the tournament once all games are revealed.
+    ///
+    /// The nodes ranked after the winner produce the block as a fallback, after waiting
+    /// [FALLBACK_DELAY] for each node ranked before them.
+    ///
+    fn evaluate(&mut self, step: &mut Step, key: &Key) {
+        if self.phase != Phase::Reveal || !self.highlander.is_filled() {
+            return
+        }
+
+        let result = self.highlander.evaluate(key);
+        self.phase = Phase::Idle;
+        self.steps += 1;
+
+        // nobody is left to produce the block if all nodes were dropped
+        step.decision = Some(match result.as_ref().and_then(|r| r.rank(&key.public_key)) {
+            Some(rank) => Decision::Produce {
+                proof: Proof::Game(result.unwrap()),
+                delay: Duration::from_millis(rank as u64 * FALLBACK_DELAY),
+            },
+            None => Decision::Wait {
+                deadline: block_deadline(result.map_or(0, |r| r.ranking().len().saturating_sub(1))),
+            },
+        });
+    }
+}
+
+impl Consensus for HighlanderConsensus {
+    fn start_round(&mut self, parent: &[u8], epoch: usize, nodes: &HashSet<PubKey>, key: &Key) -> Step {
+        let mut step = Step::default();
+
+        // the games commit to the hash of the roster, nodes which follow another parent
+        // do not play together
+        let hl = &mut self.highlander;
+        hl.set_epoch(parent, epoch);
+        Self::populate(hl, nodes.iter(), self.committee);
+        self.views.insert(parent, epoch, View {
+            roster_hash: hl.roster_hash().to_vec(),
+            population: hl.population().to_vec(),
+        });
+        let commitment = if hl.is_player(&key.public_key) {
+            let game = hl.create_game(key);
+            let commitment = game.commitment(key);
+            hl.add_commitment(commitment.clone());
+            self.game = Some(game);
+            Some(commitment)
+        }
+        else {
+            log::info!("not a member or not drawn for the committee");
+            None
+        };
+        self.phase = Phase::Commit;
+        self.steps += 1;
+        self.voted = false;
+
+        if self.highlander.is_committed() {
+            self.reveal(&mut step);
+        }
+        else {
+            step.broadcast.extend(commitment.map(|commitment| Message::Commit { commitment }));
+            step.deadline = Some(self.round_timeout);
+        }
+
+        // the messages of the nodes which started the round before this node
+        self.replay(&mut step, key);
+
+        self.evaluate(&mut step, key);
+        step
+    }
+
+    fn handle(&mut self, msg: &Message, key: &Key) -> Step {
+        let mut step = Step::default();
+
+        let running = match origin(msg) {
+            Some((parent, epoch, _)) => parent == self.highlander.parent() && epoch == self.highlander.epoch(),
+            None => return step,
+        };
+        if self.phase == Phase::Idle || !running {
+            self.keep(msg);
+            return step
+        }
+
+        match msg {
+            Message::Commit { commitment } => {
+                step.relay = self.highlander.add_commitment(commitment.clone());
+            }
+            Message::Play { game } => {
+                // only valid games are relayed
+                match self.highlander.add_game(game.clone()) {
+                    Ok(()) => step.relay = true,
+                    Err(GameError::Duplicate) => {}
+                    Err(e) => log::warn!("rejected game: {}\nauthor: {}", e, hex::encode(game.author())),
+                }
+            }
+            Message::Vote { vote } => {
+                step.relay = self.highlander.add_vote(vote.clone());
+            }
+            _ => {}
+        }
+
+        self.advance(&mut step, key);
+        step
+    }
+
+    fn timeout(&mut self, key: &Key) -> Step {
+        let mut step = Step::default();
+
+        if self.phase == Phase::Idle {
+            return step
+        }
+
+        // the nodes which are still missing start the round over with the others
+        if self.voted {
+            log::warn!("no majority voted for the missing nodes, giving up the round");
+            self.phase = Phase::Idle;
+            self.steps += 1;
+            step.decision = Some(Decision::Wait { deadline: block_deadline(0) });
+            return step
+        }
+
+        self.voted = true;
+        self.steps += 1;
+        step.deadline = Some(self.round_timeout);
+
+        if self.highlander.is_player(&key.public_key) {
+            let vote = self.highlander.vote(key);
+            match self.phase {
+                Phase::Commit => log::warn!("{} nodes did not commit to a game", vote.missing().len()),
+                _ => log::warn!("{} nodes did not reveal their game", vote.missing().len()),
+            }
+            step.broadcast.push(Message::Vote { vote });
+        }
+
+        self.advance(&mut step, key);
+        step
+    }
+
+    fn round(&self) -> u64 {
+        self.steps
+    }
+
+    ///
+    /// Checks that the block is made by a ranked player of its tournament on the expected roster,
+    /// and that a fallback producer waited for the players ranked before it.
+    ///
+    fn validate(&self, block: &Block, parent: Option<&BlockHeader>) -> bool {
+        let game = match &block.proof {
+            Proof::Game(game) => game,
+            Proof::Elected => {
+                log::error!("block {} is not decided by a tournament", hex::encode(&block.header.hash));
+                return false
+            }
+        };
+
+        if let Err(e) = game.verify(self.committee) {
+            log::error!("invalid game in block {}: {}", hex::encode(&block.header.hash), e);
+            return false
+        }
+
+        if game.parent != block.header.parent || game.epoch != block.header.height {
+            log::error!("game of block {} is played for another block", hex::encode(&block.header.hash));
+            return false
+        }
+
+        if block.header.author != game.author {
+            log::error!("block {} is not made by the author of its game result", hex::encode(&block.header.hash));
+            return false
+        }
+
+        if let Some((roster_hash, population)) = self.roster(block, parent) {
+            if game.roster_hash != roster_hash {
+                log::error!(
+                    "block {} is decided by another roster:\nroster:   {}\nexpected: {}",
+                    hex::encode(&block.header.hash),
+                    hex::encode(&game.roster_hash),
+                    hex::encode(&roster_hash),
+                );
+                return false
+            }
+
+            if game.population != population {
+                log::error!("block {} is decided by a committee of other nodes", hex::encode(&block.header.hash));
+                return false
+            }
+        }
+
+        validate_rank(block, game, parent.map(|p| p.timestamp).unwrap_or(0))
+    }
+}
+
+///
+/// Checks that a fallback producer waited for the producers ranked before it,
+/// a block of rank `n` is at least `n` times [FALLBACK_DELAY] younger than its parent.
+///
+pub fn validate_rank(block: &Block, game: &GameResult, parent_timestamp: u64) -> bool {
+    let rank = game.rank(&block.header.author).unwrap_or(usize::MAX) as u64;
+
+    if block.header.timestamp < parent_timestamp.saturating_add(rank.saturating_mul(FALLBACK_DELAY)) {
+        log::error!(
+            "fallback block before its deadline:\nnode:      {}\nrank:      {}\ntimestamp: {}\nparent:    {}",
+            hex::encode(&block.header.hash),
+            rank,
+            block.header.timestamp,
+            parent_timestamp,
+        );
+        false
+    }
+    else {
+        true
+    }
+}
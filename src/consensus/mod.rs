@@ -0,0 +1,153 @@
+use std::{collections::{HashSet, VecDeque}, time::Duration};
+
+use crate::{
+    blockchain::{Block, BlockHeader, block::{FALLBACK_DELAY, MAX_FUTURE_DRIFT, Proof}},
+    config::{Config, ConsensusKind},
+    key::{Key, PubKey},
+    network::message::Message,
+};
+
+pub mod authority;
+pub mod highlander;
+pub mod round_robin;
+
+pub use self::{
+    authority::Authority,
+    highlander::HighlanderConsensus,
+    round_robin::RoundRobin,
+};
+
+/// The number of rounds a [Consensus] remembers what it saw of, to validate their blocks.
+pub const MAX_VIEWS: usize = 16;
+
+///
+/// Who produces the block of a round, once a [Consensus] decided it.
+///
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum Decision {
+    /// This node produces the block with `proof`, after `delay` if no other block arrived until then.
+    Produce { proof: Proof, delay: Duration },
+    /// Another node produces the block, the round starts over if none arrived after `deadline`.
+    Wait { deadline: Duration },
+}
+
+///
+/// What the daemon does after the [Consensus] handled an event.
+///
+#[derive(Default, Debug)]
+pub struct Step {
+    /// The messages to send to all peers.
+    pub broadcast: Vec<Message>,
+    /// If the handled message is valid and passed on to the other peers.
+    pub relay: bool,
+    /// Calls [Consensus::timeout] after this time, unless the round moved on.
+    pub deadline: Option<Duration>,
+    /// The producer of the block, once it is decided.
+    pub decision: Option<Decision>,
+}
+
+///
+/// How long a node waits for the block once the producers of up to rank `rank_max` had their turn.
+///
+pub fn block_deadline(rank_max: usize) -> Duration {
+    Duration::from_millis((rank_max as u64).saturating_mul(FALLBACK_DELAY).saturating_add(MAX_FUTURE_DRIFT))
+}
+
+impl Step {
+    fn decide(decision: Decision) -> Self {
+        Self {
+            decision: Some(decision),
+            ..Default::default()
+        }
+    }
+}
+
+///
+/// The leader election, which decides the node producing the next block.
+///
+/// The daemon drives a round through the returned [Steps](Step), it sends the messages, starts the
+/// timers and produces the block if this node is decided.
+///
+pub trait Consensus: Send {
+    ///
+    /// Starts the round for the block at height `epoch` on `parent`, played by the `nodes` the parent
+    /// names as members. This node only plays if it is one of them.
+    ///
+    fn start_round(&mut self, parent: &[u8], epoch: usize, nodes: &HashSet<PubKey>, key: &Key) -> Step;
+
+    ///
+    /// Handles a consensus message of another node.
+    ///
+    /// A message of a round which is not started yet is kept until it is.
+    ///
+    fn handle(&mut self, msg: &Message, key: &Key) -> Step;
+
+    ///
+    /// Called once the deadline of a [Step] passed.
+    ///
+    fn timeout(&mut self, key: &Key) -> Step;
+
+    ///
+    /// Changes whenever the round moves on, a deadline of an earlier step is dropped then.
+    ///
+    fn round(&self) -> u64;
+
+    ///
+    /// Checks that the author of `block` was allowed to produce it on `parent`, besides [Block::validate].
+    ///
+    /// The round is checked against the members the parent names. Without the parent, a round
+    /// this node played is checked against what it saw of it, otherwise only what the block proves
+    /// on its own is checked.
+    ///
+    fn validate(&self, block: &Block, parent: Option<&BlockHeader>) -> bool;
+}
+
+///
+/// What this node saw of the last [MAX_VIEWS] rounds it played, by the parent and the height of their blocks.
+///
+pub struct Views<T> {
+    views: VecDeque<(Vec<u8>, usize, T)>,
+}
+
+impl<T> Default for Views<T> {
+    fn default() -> Self {
+        Self {
+            views: VecDeque::new(),
+        }
+    }
+}
+
+impl<T> Views<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Keeps `view` of the round for the block at height `epoch` on `parent`, the oldest view is dropped.
+    ///
+    pub fn insert(&mut self, parent: &[u8], epoch: usize, view: T) {
+        self.views.retain(|(p, e, _)| p != parent || *e != epoch);
+        if self.views.len() == MAX_VIEWS {
+            self.views.pop_front();
+        }
+        self.views.push_back((parent.to_vec(), epoch, view));
+    }
+
+    pub fn get(&self, parent: &[u8], epoch: usize) -> Option<&T> {
+        self.views.iter().find(|(p, e, _)| p == parent && *e == epoch).map(|(_, _, view)| view)
+    }
+}
+
+///
+/// The [Consensus] of `config`.
+///
+pub fn from_config(config: &Config) -> Result<Box<dyn Consensus>, anyhow::Error> {
+    let consensus: Box<dyn Consensus> = match &config.consensus {
+        ConsensusKind::Highlander => Box::new(HighlanderConsensus::new(Duration::from_secs(config.round_timeout), config.committee)),
+        ConsensusKind::RoundRobin => Box::new(RoundRobin::new()),
+        ConsensusKind::Authority(key) => Box::new(Authority::new(hex::decode(key)?)),
+    };
+
+    Ok(consensus)
+}
@@ -0,0 +1,104 @@
+use std::{collections::HashSet, time::Duration};
+
+use crate::{
+    blockchain::{Block, BlockHeader, block::Proof},
+    key::{Key, PubKey},
+    network::message::Message,
+};
+
+use super::{block_deadline, Consensus, Decision, Step, Views};
+
+///
+/// A deterministic [Consensus], the known nodes produce the blocks in turn.
+///
+/// The nodes are ordered by their keys, the block at height `epoch` is produced by the node
+/// at `epoch` modulo the number of nodes. Every node checks the turn against the members the
+/// parent block names. No messages are exchanged, which makes it useful for tests and private
+/// deployments with trusted nodes.
+///
+#[derive(Default)]
+pub struct RoundRobin {
+    rounds: u64,
+    /// The nodes of the rounds this node played.
+    views: Views<HashSet<PubKey>>,
+}
+
+impl RoundRobin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// The node of the turn at height `epoch`.
+    ///
+    pub fn producer(nodes: &HashSet<PubKey>, epoch: usize) -> Option<PubKey> {
+        let mut nodes: Vec<&PubKey> = nodes.iter().collect();
+        nodes.sort();
+
+        if nodes.is_empty() {
+            None
+        }
+        else {
+            Some(nodes[epoch % nodes.len()].clone())
+        }
+    }
+}
+
+impl Consensus for RoundRobin {
+    fn start_round(&mut self, parent: &[u8], epoch: usize, nodes: &HashSet<PubKey>, key: &Key) -> Step {
+        self.rounds += 1;
+
+        let producer = RoundRobin::producer(nodes, epoch);
+        self.views.insert(parent, epoch, nodes.clone());
+
+        if producer.as_ref() == Some(&key.public_key) {
+            Step::decide(Decision::Produce {
+                proof: Proof::Elected,
+                delay: Duration::ZERO,
+            })
+        }
+        else {
+            Step::decide(Decision::Wait {
+                deadline: block_deadline(0),
+            })
+        }
+    }
+
+    fn handle(&mut self, _msg: &Message, _key: &Key) -> Step {
+        Step::default()
+    }
+
+    fn timeout(&mut self, _key: &Key) -> Step {
+        Step::default()
+    }
+
+    fn round(&self) -> u64 {
+        self.rounds
+    }
+
+    fn validate(&self, block: &Block, parent: Option<&BlockHeader>) -> bool {
+        if !matches!(block.proof, Proof::Elected) {
+            log::error!("block {} is not made in turn", hex::encode(&block.header.hash));
+            return false
+        }
+
+        let members: HashSet<PubKey>;
+        let nodes = match parent {
+            Some(parent) => {
+                members = parent.members.iter().cloned().collect();
+                &members
+            }
+            None => match self.views.get(&block.header.parent, block.header.height) {
+                Some(nodes) => nodes,
+                None => return true,
+            },
+        };
+
+        if RoundRobin::producer(nodes, block.header.height).as_ref() != Some(&block.header.author) {
+            log::error!("block {} is not made in turn", hex::encode(&block.header.hash));
+            return false
+        }
+
+        true
+    }
+}
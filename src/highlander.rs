@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use rand::{rngs::OsRng, RngCore};
 use serde::{Serialize, Deserialize};
@@ -10,6 +10,9 @@ const ROCK: u8 = 0;
 // const PAPER: u8 = 1;
 const SCISSOR: u8 = 2;
 
+/// The length of the random salt, which hides the rounds of a game in its commitment.
+const SALT_SIZE: usize = 32;
+
 
 fn winner(p0: PubKey, v0: u8, p1: PubKey, v1: u8) -> PubKey {
     if v0 > v1 {
@@ -30,23 +33,36 @@ fn winner(p0: PubKey, v0: u8, p1: PubKey, v1: u8) -> PubKey {
     }
 }
 
-struct IntIter {
-    pos: usize,
-    step: usize,
-    end: usize,
+///
+/// A random stream derived from a seed, so every node places the players the same way.
+///
+struct SeedRng {
+    seed: Vec<u8>,
+    counter: u64,
 }
 
-impl Iterator for IntIter {
-    type Item = usize;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.pos < self.end {
-            let val = self.pos;
-            self.pos += self.step;
-            Some(val)
+impl SeedRng {
+    fn new(seed: &[u8]) -> Self {
+        Self {
+            seed: seed.to_vec(),
+            counter: 0,
         }
-        else {
-            None
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        let mut sha = Sha256::new();
+        sha.update(&self.seed);
+        sha.update(self.counter.to_be_bytes());
+        self.counter += 1;
+
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(&sha.finalize()[..8]);
+        (u64::from_be_bytes(buf) % n as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
         }
     }
 }
@@ -58,10 +74,353 @@ impl Iterator for IntIter {
 pub struct Game {
     /// The public key of the playing node.
     author: PubKey,
-    /// The signature over the player rounds.
+    /// The block the tournament builds on.
+    parent: Vec<u8>,
+    /// The height of the block the tournament decides on.
+    epoch: usize,
+    /// The hash of the roster the game is played against.
+    roster: Vec<u8>,
+    /// The signature over the parent, the epoch, the roster and the player rounds.
     sign: Vec<u8>,
     /// The choices of the node for the game rounds.
     rounds: Vec<u8>,
+    /// The random salt of the commitment to the rounds.
+    salt: Vec<u8>,
+}
+
+///
+/// The signed payload of games and commitments, which binds `data` to a single tournament
+/// on a single roster.
+///
+fn payload(parent: &[u8], epoch: usize, roster: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut sha = Sha256::new();
+
+    sha.update(parent);
+    sha.update((epoch as u64).to_be_bytes());
+    sha.update(roster);
+    sha.update(data);
+
+    sha.finalize().to_vec()
+}
+
+///
+/// The hash of a roster, the public keys of the nodes in the order of their keys.
+///
+fn roster_hash<'a, T: Iterator<Item=&'a PubKey>>(ids: T) -> Vec<u8> {
+    let mut ids: Vec<&PubKey> = ids.collect();
+    ids.sort();
+
+    let mut sha = Sha256::new();
+    for id in ids {
+        sha.update(id);
+    }
+
+    sha.finalize().to_vec()
+}
+
+fn commitment_hash(author: &[u8], salt: &[u8], rounds: &[u8]) -> Vec<u8> {
+    let mut sha = Sha256::new();
+
+    sha.update(author);
+    sha.update(salt);
+    sha.update(rounds);
+
+    sha.finalize().to_vec()
+}
+
+///
+/// The reasons a [Game] is rejected.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameError {
+    /// The game belongs to a tournament on another block.
+    WrongEpoch,
+    /// The game is played against another roster.
+    WrongRoster,
+    /// The author is not part of the roster.
+    UnknownAuthor,
+    /// The author was excluded from the tournament.
+    Excluded,
+    /// The game of the author is already in.
+    Duplicate,
+    /// The signature does not match the rounds and the author.
+    InvalidSignature,
+    /// The number of rounds does not match the size of the roster.
+    RoundCount { expected: usize, actual: usize },
+    /// A round is not rock, paper or scissor.
+    InvalidRound { round: usize, value: u8 },
+    /// The author did not commit to a game yet.
+    NoCommitment,
+    /// The game is not the one the author committed to.
+    CommitmentMismatch,
+}
+
+impl std::fmt::Display for GameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WrongEpoch => write!(f, "game of another tournament"),
+            Self::WrongRoster => write!(f, "game of another roster"),
+            Self::UnknownAuthor => write!(f, "game author is not part of the game"),
+            Self::Excluded => write!(f, "game author is excluded from the game"),
+            Self::Duplicate => write!(f, "game is already known"),
+            Self::InvalidSignature => write!(f, "invalid game signature"),
+            Self::RoundCount { expected, actual } => write!(f, "round length do not match, expected {} got {}", expected, actual),
+            Self::InvalidRound { round, value } => write!(f, "invalid choice {} in round {}", value, round),
+            Self::NoCommitment => write!(f, "game without commitment"),
+            Self::CommitmentMismatch => write!(f, "game does not match the commitment"),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+fn validate_rounds(author: &[u8], signed: &[u8], rounds: &[u8], sign: &[u8]) -> Result<(), GameError> {
+    if Key::validate(signed, author, sign).is_err() {
+        return Err(GameError::InvalidSignature)
+    }
+    if let Some((round, value)) = rounds.iter().enumerate().find(|(_, v)| **v > SCISSOR) {
+        return Err(GameError::InvalidRound { round, value: *value })
+    }
+
+    Ok(())
+}
+
+///
+/// The number of rounds a node plays in a tournament of `count` nodes.
+///
+fn rounds_for(count: usize) -> usize {
+    count.next_power_of_two().trailing_zeros() as usize
+}
+
+///
+/// The seed of the bracket of a tournament, known to all nodes before the games are played.
+///
+fn bracket_seed(parent: &[u8], epoch: usize) -> Vec<u8> {
+    payload(parent, epoch, &[], b"bracket")
+}
+
+///
+/// Draws a committee of `size` nodes for the tournament of the block at height `epoch` on top of
+/// `parent`, sorted by key. All nodes are drawn, if there are not more than `size`.
+///
+/// The committee is seeded by the parent block, so every node which knows the same nodes draws
+/// the same committee.
+///
+pub fn committee<'a, T: Iterator<Item=&'a PubKey>>(nodes: T, parent: &[u8], epoch: usize, size: usize) -> Vec<PubKey> {
+    let mut rng = SeedRng::new(&payload(parent, epoch, &[], b"committee"));
+
+    let mut ids: Vec<PubKey> = nodes.cloned().collect();
+    ids.sort();
+    ids.dedup();
+    rng.shuffle(&mut ids);
+    ids.truncate(size);
+    ids.sort();
+
+    ids
+}
+
+///
+/// Plays the tournament of the `roster` and returns the game tree, the players followed by the
+/// winners of each round.
+///
+/// The players are placed in a bracket of the next power of two, the free places are byes.
+/// The order of the players and the matches with a bye are drawn from the `seed`, every bye
+/// is matched with a player, so the player moves on to the next round without a match.
+///
+fn bracket(roster: &HashMap<PubKey, Vec<u8>>, seed: &[u8]) -> Vec<Option<PubKey>> {
+    let mut rng = SeedRng::new(seed);
+
+    let mut ids: Vec<PubKey> = roster.keys().cloned().collect();
+    ids.sort();
+    rng.shuffle(&mut ids);
+
+    let size = ids.len().next_power_of_two();
+    let mut matches: Vec<usize> = (0..size / 2).collect();
+    rng.shuffle(&mut matches);
+    let byes: HashSet<usize> = matches.into_iter().take(size - ids.len()).collect();
+
+    let mut ids = ids.into_iter();
+    let mut tree: Vec<Option<PubKey>> = (0..size)
+        .map(|i| if i % 2 == 1 && byes.contains(&(i / 2)) { None } else { ids.next() })
+        .collect();
+
+    let mut lvl = 0;
+    let mut offset = 0;
+    let mut count = size;
+
+    while count > 1 {
+        for i in (offset..offset + count).step_by(2) {
+            let w = match (&tree[i], &tree[i + 1]) {
+                (Some(p0), Some(p1)) => Some(winner(p0.clone(), roster[p0][lvl], p1.clone(), roster[p1][lvl])),
+                (Some(p), None) | (None, Some(p)) => Some(p.clone()),
+                (None, None) => None,
+            };
+            tree.push(w);
+        }
+
+        offset += count;
+        count /= 2;
+        lvl += 1;
+    }
+
+    tree
+}
+
+impl Game {
+    pub fn author(&self) -> &PubKey {
+        &self.author
+    }
+
+    pub fn parent(&self) -> &[u8] {
+        &self.parent
+    }
+
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    ///
+    /// The signed payload of the game.
+    ///
+    pub fn payload(&self) -> Vec<u8> {
+        payload(&self.parent, self.epoch, &self.roster, &self.rounds)
+    }
+
+    ///
+    /// Checks the signature and that the game has `rounds` valid choices.
+    ///
+    pub fn validate(&self, rounds: usize) -> Result<(), GameError> {
+        validate_rounds(&self.author, &self.payload(), &self.rounds, &self.sign)?;
+        if self.rounds.len() != rounds {
+            return Err(GameError::RoundCount { expected: rounds, actual: self.rounds.len() })
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Commits to the rounds of the game without telling them.
+    ///
+    /// The commitment is shared first, the game is revealed after the commitments of all nodes are in,
+    /// so no node can choose its rounds knowing the rounds of the others.
+    ///
+    pub fn commitment(&self, key: &Key) -> Commitment {
+        let hash = commitment_hash(&self.author, &self.salt, &self.rounds);
+        let sign = key.sign(&payload(&self.parent, self.epoch, &self.roster, &hash)).unwrap();
+
+        Commitment {
+            author: self.author.clone(),
+            parent: self.parent.clone(),
+            epoch: self.epoch,
+            roster: self.roster.clone(),
+            hash,
+            sign,
+        }
+    }
+}
+
+///
+/// The commitment of a node to its game, a salted hash over its rounds.
+///
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Commitment {
+    /// The public key of the playing node.
+    author: PubKey,
+    /// The block the tournament builds on.
+    parent: Vec<u8>,
+    /// The height of the block the tournament decides on.
+    epoch: usize,
+    /// The hash of the roster the game is played against.
+    roster: Vec<u8>,
+    /// The hash over the author, the salt and the rounds.
+    hash: Vec<u8>,
+    /// The signature over the parent, the epoch, the roster and the hash.
+    sign: Vec<u8>,
+}
+
+impl Commitment {
+    pub fn author(&self) -> &PubKey {
+        &self.author
+    }
+
+    pub fn parent(&self) -> &[u8] {
+        &self.parent
+    }
+
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+}
+
+///
+/// The signed data of a [Vote], it cannot be taken for the rounds or the commitment of a game.
+///
+fn vote_data(reveal: bool, missing: &[PubKey]) -> Vec<u8> {
+    let mut data = b"vote".to_vec();
+
+    data.push(reveal as u8);
+    for id in missing {
+        data.extend_from_slice(id);
+    }
+
+    data
+}
+
+///
+/// The vote of a node of the roster to drop the nodes it did not get the commitment or the game of in time.
+///
+/// A node is only dropped once a majority of the roster voted so, the votes are part of the [GameResult].
+///
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Vote {
+    /// The public key of the voting node.
+    author: PubKey,
+    /// The block the tournament builds on.
+    parent: Vec<u8>,
+    /// The height of the block the tournament decides on.
+    epoch: usize,
+    /// The hash of the roster the tournament is played with.
+    roster: Vec<u8>,
+    /// If the vote is about the revealed games, otherwise about the commitments.
+    reveal: bool,
+    /// The nodes the author is missing, sorted by key.
+    missing: Vec<PubKey>,
+    /// The signature over the parent, the epoch, the roster and the missing nodes.
+    sign: Vec<u8>,
+}
+
+impl Vote {
+    pub fn author(&self) -> &PubKey {
+        &self.author
+    }
+
+    pub fn parent(&self) -> &[u8] {
+        &self.parent
+    }
+
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    pub fn missing(&self) -> &[PubKey] {
+        &self.missing
+    }
+
+    ///
+    /// Checks the signature of the author.
+    ///
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let signed = payload(&self.parent, self.epoch, &self.roster, &vote_data(self.reveal, &self.missing));
+        Key::validate(&signed, &self.author, &self.sign)
+            .map_err(|e| anyhow::anyhow!("invalid vote signature: {}", e))
+    }
+}
+
+///
+/// The number of votes which drop a node from a roster of `size` nodes.
+///
+fn quorum(size: usize) -> usize {
+    size / 2 + 1
 }
 
 ///
@@ -69,130 +428,590 @@ pub struct Game {
 /// 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct GameResult {
+    /// The block the tournament builds on.
+    pub parent: Vec<u8>,
+    /// The height of the block the tournament decides on.
+    pub epoch: usize,
+    /// The hash of the roster the tournament started with.
+    pub roster_hash: Vec<u8>,
+    /// The nodes of the roster dropped from the tournament, sorted by key.
+    pub excluded: Vec<PubKey>,
+    /// The votes which dropped the excluded nodes, by author.
+    pub votes: Vec<Vote>,
+    /// The nodes the roster is drawn from as a [committee], sorted by key.
+    /// Empty if all nodes play.
+    pub population: Vec<PubKey>,
     /// The game tree of the matches.
     pub tree: Vec<Option<PubKey>>,
     /// The single nodes and their choices.
     pub roster: HashMap<PubKey, Vec<u8>>,
-    /// The winner and author of this game result.
+    /// The signatures of the nodes over their choices.
+    pub signs: HashMap<PubKey, Vec<u8>>,
+    /// The winner of the tournament.
     pub winner: PubKey,
-    /// The signature by the winner.
+    /// The node producing the block with this game result, the winner or a fallback producer.
+    pub author: PubKey,
+    /// The signature by the author.
     pub sign: Vec<u8>,
 }
 
-fn game_result_hash(tree: &Vec<Option<PubKey>>, roster: &HashMap<PubKey, Vec<u8>>, winner: &PubKey) -> Vec<u8> {
-    let mut sha = Sha256::new();
+impl GameResult {
+    ///
+    /// The hash over the tournament, the tree, the roster, the winner and the author, which is signed by the author.
+    ///
+    pub fn hash(&self) -> Vec<u8> {
+        let mut sha = Sha256::new();
 
-    for id in tree {
-        if let Some(ref id) = id {
+        sha.update(&self.parent);
+        sha.update((self.epoch as u64).to_be_bytes());
+        sha.update(&self.roster_hash);
+        for id in &self.excluded {
             sha.update(id);
         }
+        for vote in &self.votes {
+            sha.update(&vote.author);
+            sha.update([vote.reveal as u8]);
+            for id in &vote.missing {
+                sha.update(id);
+            }
+            sha.update(&vote.sign);
+        }
+        for id in &self.population {
+            sha.update(id);
+        }
+
+        for id in self.tree.iter().flatten() {
+            sha.update(id);
+        }
+
+        // if we do not sort the keys, the hashing will be diffirent on another machine
+        let mut roster_keys: Vec<&PubKey> = self.roster.keys().collect();
+        roster_keys.sort();
+        for id in roster_keys {
+            sha.update(id);
+            sha.update(&self.roster[id]);
+            if let Some(sign) = self.signs.get(id) {
+                sha.update(sign);
+            }
+        }
+
+        sha.update(&self.winner);
+        sha.update(&self.author);
+
+        sha.finalize().to_vec()
     }
 
-    for (id ,v) in roster {
-        sha.update(id);
-        sha.update(v);
+    ///
+    /// Checks the game of every node, plays the tournament of the roster again and checks
+    /// that it leads to the same tree and winner, and that a ranked node signed the result.
+    /// Each excluded node has to be voted missing by a majority of the roster.
+    ///
+    /// With a `committee_size`, the roster has to be a committee of that size, or all nodes
+    /// if there are not more. Without it, all nodes play.
+    ///
+    pub fn verify(&self, committee_size: Option<usize>) -> Result<(), anyhow::Error> {
+        if self.roster.is_empty() {
+            anyhow::bail!("empty roster")
+        }
+
+        let size = self.roster.len() + self.excluded.len();
+        match committee_size {
+            Some(expected) if !self.population.is_empty() && size != expected => {
+                anyhow::bail!("committee of {} nodes, expected {}", size, expected)
+            }
+            Some(expected) if size > expected => {
+                anyhow::bail!("roster of {} nodes is larger than the committee of {}", size, expected)
+            }
+            None if !self.population.is_empty() => {
+                anyhow::bail!("roster is drawn as a committee, but all nodes play")
+            }
+            _ => {}
+        }
+
+        if roster_hash(self.roster.keys().chain(self.excluded.iter())) != self.roster_hash {
+            anyhow::bail!("roster does not match the roster hash")
+        }
+
+        // whether all nodes play is up to the consensus, which knows the nodes itself
+        if !self.population.is_empty() {
+            let mut ids: Vec<PubKey> = self.roster.keys().chain(self.excluded.iter()).cloned().collect();
+            ids.sort();
+            if committee(self.population.iter(), &self.parent, self.epoch, ids.len()) != ids {
+                anyhow::bail!("roster is not the committee of the population")
+            }
+        }
+
+        self.verify_votes()?;
+
+        // the rounds are played as if the excluded nodes were still there
+        let rounds = rounds_for(self.roster.len() + self.excluded.len());
+        for (id, choices) in &self.roster {
+            let sign = self.signs.get(id)
+                .ok_or_else(|| anyhow::anyhow!("no signature of {}", hex::encode(id)))?;
+            let signed = payload(&self.parent, self.epoch, &self.roster_hash, choices);
+            validate_rounds(id, &signed, choices, sign)
+                .map_err(|e| anyhow::anyhow!("{} of {}", e, hex::encode(id)))?;
+            if choices.len() != rounds {
+                anyhow::bail!("{} of {}", GameError::RoundCount { expected: rounds, actual: choices.len() }, hex::encode(id))
+            }
+        }
+
+        if bracket(&self.roster, &bracket_seed(&self.parent, self.epoch)) != self.tree {
+            anyhow::bail!("game tree does not match the roster")
+        }
+        if self.tree.last() != Some(&Some(self.winner.clone())) {
+            anyhow::bail!("winner does not match the game tree")
+        }
+        if self.rank(&self.author).is_none() {
+            anyhow::bail!("author is not part of the tournament")
+        }
+        Key::validate(&self.hash(), &self.author, &self.sign)
+            .map_err(|e| anyhow::anyhow!("invalid signature of the author: {}", e))?;
+
+        Ok(())
     }
 
-    sha.update(winner);
+    ///
+    /// Checks that the votes are signed by distinct nodes of the roster for this tournament,
+    /// and that a majority of them voted for each excluded node.
+    ///
+    fn verify_votes(&self) -> Result<(), anyhow::Error> {
+        let mut voted: HashSet<(&PubKey, bool)> = HashSet::new();
 
-    sha.finalize().to_vec()
-}
+        for vote in &self.votes {
+            if vote.parent != self.parent || vote.epoch != self.epoch || vote.roster != self.roster_hash {
+                anyhow::bail!("vote of another tournament by {}", hex::encode(&vote.author))
+            }
+            if !self.roster.contains_key(&vote.author) && !self.excluded.contains(&vote.author) {
+                anyhow::bail!("vote of {} which is not part of the roster", hex::encode(&vote.author))
+            }
+            if !voted.insert((&vote.author, vote.reveal)) {
+                anyhow::bail!("second vote of {}", hex::encode(&vote.author))
+            }
+            vote.validate().map_err(|e| anyhow::anyhow!("{} of {}", e, hex::encode(&vote.author)))?;
+        }
 
-impl GameResult {
-    fn build(tree: Vec<Option<PubKey>>, roster: &HashMap<PubKey, Option<Vec<u8>>>, key: &Key) -> Self {
-        let roster: HashMap<PubKey, Vec<u8>> = roster
-            .iter()
-            .map(|k| (k.0.clone(), k.1.as_ref().unwrap().clone()))
-            .collect();
-        let winner = tree.last().unwrap().clone().unwrap();
+        let quorum = quorum(self.roster.len() + self.excluded.len());
+        for id in &self.excluded {
+            let voters: HashSet<&PubKey> = self.votes.iter()
+                .filter(|vote| vote.missing.contains(id))
+                .map(|vote| &vote.author)
+                .collect();
+            if voters.len() < quorum {
+                anyhow::bail!("{} is excluded by {} votes, a majority is {}", hex::encode(id), voters.len(), quorum)
+            }
+        }
+
+        Ok(())
+    }
 
-        let sign = if key.public_key == winner {
-            let hash = game_result_hash(&tree, &roster, &winner);
-            key.sign(&hash).unwrap()
+    ///
+    /// The players from the winner down, the players which lost in a later round are ranked higher.
+    ///
+    /// Players which lost in the same round are ranked by their place in the bracket.
+    /// The nodes ranked after the winner produce the block in order, if the ones before do not.
+    ///
+    pub fn ranking(&self) -> Vec<PubKey> {
+        let levels = self.levels();
+        let mut ranking: Vec<PubKey> = Vec::new();
+
+        for level in levels.iter().rev() {
+            for id in level.iter().flatten() {
+                if !ranking.contains(id) {
+                    ranking.push(id.clone());
+                }
+            }
         }
-        else {
-            Vec::new()
-        };
 
-        Self {
+        ranking
+    }
+
+    ///
+    /// The rank of `id` in the [ranking](GameResult::ranking), the winner has rank 0.
+    ///
+    pub fn rank(&self, id: &[u8]) -> Option<usize> {
+        self.ranking().iter().position(|r| r == id)
+    }
+
+    ///
+    /// Splits the tree into the rounds of the tournament, from the players to the winner.
+    ///
+    /// Each entry of a round is the winner of two entries of the round before.
+    ///
+    pub fn levels(&self) -> Vec<&[Option<PubKey>]> {
+        let mut levels = Vec::new();
+        let mut rest = self.tree.as_slice();
+        let mut count = rest.len().div_ceil(2);
+
+        while !rest.is_empty() {
+            let (level, tail) = rest.split_at(count.min(rest.len()));
+            levels.push(level);
+            rest = tail;
+            count = count.div_ceil(2);
+        }
+
+        levels
+    }
+
+    fn build(
+        tournament: &Highlander,
+        tree: Vec<Option<PubKey>>,
+        roster: HashMap<PubKey, Vec<u8>>,
+        signs: HashMap<PubKey, Vec<u8>>,
+        key: &Key
+    ) -> Self {
+        let mut excluded: Vec<PubKey> = tournament.excluded.iter().cloned().collect();
+        excluded.sort();
+        let mut votes = tournament.votes.clone();
+        votes.sort_by(|a, b| (&a.author, a.reveal).cmp(&(&b.author, b.reveal)));
+
+        let mut result = Self {
+            parent: tournament.parent.clone(),
+            epoch: tournament.epoch,
+            roster_hash: tournament.roster_hash.clone(),
+            excluded,
+            votes,
+            population: tournament.population.clone(),
+            winner: tree.last().unwrap().clone().unwrap(),
+            author: key.public_key.clone(),
             tree,
             roster,
-            winner,
-            sign,
-        }
+            signs,
+            sign: Vec::new(),
+        };
+        result.sign = key.sign(&result.hash()).unwrap();
+
+        result
     }
 }
 
 ///
 /// An abstraction of the Highlander algorithm.
+///
+/// A tournament has two phases, first every node of the roster commits to its game,
+/// then the games are revealed and checked against the commitments.
 /// 
 pub struct Highlander {
+    /// The block the tournament builds on.
+    parent: Vec<u8>,
+    /// The height of the block the tournament decides on.
+    epoch: usize,
+    /// The hash of the roster the tournament started with, games of other rosters are rejected.
+    roster_hash: Vec<u8>,
     roster: HashMap<PubKey, Option<Vec<u8>>>,
+    commitments: HashMap<PubKey, Vec<u8>>,
+    /// The signatures of the revealed games.
+    signs: HashMap<PubKey, Vec<u8>>,
+    /// The nodes dropped from the tournament, as a majority of the roster voted them missing.
+    excluded: HashSet<PubKey>,
+    /// The votes of the nodes of the roster, at most one per phase and node.
+    votes: Vec<Vote>,
+    /// The nodes which committed twice or revealed another game than they committed to,
+    /// their games are not taken.
+    rejected: HashSet<PubKey>,
+    /// The nodes the roster is drawn from, if only a committee plays.
+    population: Vec<PubKey>,
+    /// The number of started tournaments.
+    tournament: u64,
+}
+
+impl Default for Highlander {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Highlander {
     pub fn new() -> Self {
         Self {
+            parent: Vec::new(),
+            epoch: 0,
+            roster_hash: Vec::new(),
             roster: HashMap::new(),
+            commitments: HashMap::new(),
+            signs: HashMap::new(),
+            excluded: HashSet::new(),
+            votes: Vec::new(),
+            rejected: HashSet::new(),
+            population: Vec::new(),
+            tournament: 0,
         }
     }
 
     pub fn clear(&mut self) {
         self.roster.clear();
+        self.roster_hash.clear();
+        self.commitments.clear();
+        self.signs.clear();
+        self.excluded.clear();
+        self.votes.clear();
+        self.rejected.clear();
+        self.population.clear();
+    }
+
+    ///
+    /// Starts the tournament for the block at height `epoch` on top of `parent`,
+    /// games and commitments of other tournaments are rejected.
+    ///
+    pub fn set_epoch(&mut self, parent: &[u8], epoch: usize) {
+        self.clear();
+        self.parent = parent.to_vec();
+        self.epoch = epoch;
+        self.tournament += 1;
+    }
+
+    pub fn tournament(&self) -> u64 {
+        self.tournament
+    }
+
+    pub fn parent(&self) -> &[u8] {
+        &self.parent
+    }
+
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    pub fn roster_hash(&self) -> &[u8] {
+        &self.roster_hash
+    }
+
+    pub fn excluded(&self) -> &HashSet<PubKey> {
+        &self.excluded
+    }
+
+    ///
+    /// The nodes the roster is drawn from, sorted by key, empty if all nodes play.
+    ///
+    pub fn population(&self) -> &[PubKey] {
+        &self.population
+    }
+
+    /// The number of rounds of a game, which stays the same when nodes are excluded.
+    fn rounds(&self) -> usize {
+        rounds_for(self.roster.len() + self.excluded.len())
+    }
+
+    fn exclude(&mut self, id: &PubKey) {
+        if self.roster.remove(id).is_some() {
+            log::warn!("exclude from the game {}", hex::encode(id));
+            self.signs.remove(id);
+            self.excluded.insert(id.clone());
+        }
     }
 
     pub fn populate_roster<'a, T: Iterator<Item=&'a PubKey>>(&mut self, iter: T) {
         for id in iter {
             self.roster.insert(id.clone(), None);
         }
+        self.roster_hash = roster_hash(self.roster.keys().chain(self.excluded.iter()));
     }
 
-    pub fn add_game(&mut self, game: Game) -> bool {
-        let count = self.roster.len();
-        let count = count + count % 2;
-        let count = (count as f64).log2() as usize;
+    ///
+    /// Populates the roster with a [committee] of `size` of the `nodes`, the other nodes do not play.
+    ///
+    pub fn populate_committee<'a, T: Iterator<Item=&'a PubKey>>(&mut self, nodes: T, size: usize) {
+        let mut population: Vec<PubKey> = nodes.cloned().collect();
+        population.sort();
+        population.dedup();
 
-        if game.rounds.len() == count {
-            if self.roster.contains_key(&game.author) {
-                self.roster.insert(game.author, Some(game.rounds));
-                true
-            }
-            else {
-                log::error!("game author is not part of the game\nauthor: {}", hex::encode(game.author));
+        let drawn = committee(population.iter(), &self.parent, self.epoch, size);
+        if drawn.len() < population.len() {
+            self.population = population;
+        }
+        self.populate_roster(drawn.iter());
+    }
+
+    ///
+    /// If `id` plays in the tournament.
+    ///
+    pub fn is_player(&self, id: &[u8]) -> bool {
+        self.roster.contains_key(id) || self.excluded.contains(id)
+    }
+
+    ///
+    /// Adds the commitment of a node of the roster, a node cannot change its commitment.
+    ///
+    pub fn add_commitment(&mut self, commitment: Commitment) -> bool {
+        if commitment.parent != self.parent || commitment.epoch != self.epoch {
+            log::warn!("commitment of another tournament\nauthor: {}", hex::encode(commitment.author));
+            return false
+        }
+        if commitment.roster != self.roster_hash {
+            log::warn!(
+                "commitment of another roster\nauthor: {}\nroster: {}",
+                hex::encode(commitment.author),
+                hex::encode(commitment.roster),
+            );
+            return false
+        }
+        if !self.roster.contains_key(&commitment.author) {
+            log::error!("commitment author is not part of the game\nauthor: {}", hex::encode(commitment.author));
+            return false
+        }
+        let signed = payload(&commitment.parent, commitment.epoch, &commitment.roster, &commitment.hash);
+        if let Err(e) = Key::validate(&signed, &commitment.author, &commitment.sign) {
+            log::error!("invalid commitment signature: {}\nauthor: {}", e, hex::encode(commitment.author));
+            return false
+        }
+
+        match self.commitments.get(&commitment.author) {
+            Some(hash) if *hash != commitment.hash => {
+                log::error!("second commitment\nauthor: {}", hex::encode(&commitment.author));
+                self.rejected.insert(commitment.author);
                 false
             }
+            Some(_) => false,
+            None => {
+                self.commitments.insert(commitment.author, commitment.hash);
+                true
+            }
         }
-        else {
-            log::error!(
-                "round length do not match:\nexpected: {}\ngame: {}\nauthor: {}",
-                count, game.rounds.len(),
-                hex::encode(game.author)
-            );
-            false
+    }
+
+    ///
+    /// Whether all nodes of the roster committed to their game, only then the games are revealed.
+    ///
+    pub fn is_committed(&self) -> bool {
+        self.roster.keys().all(|id| self.commitments.contains_key(id))
+    }
+
+    ///
+    /// Adds a revealed game, after a game which does not match the commitment of its author
+    /// no game of the author is taken.
+    ///
+    pub fn add_game(&mut self, game: Game) -> Result<(), GameError> {
+        if game.parent != self.parent || game.epoch != self.epoch {
+            return Err(GameError::WrongEpoch)
+        }
+        if game.roster != self.roster_hash {
+            return Err(GameError::WrongRoster)
+        }
+        if self.excluded.contains(&game.author) || self.rejected.contains(&game.author) {
+            return Err(GameError::Excluded)
+        }
+        let entry = self.roster.get(&game.author).ok_or(GameError::UnknownAuthor)?;
+        if entry.is_some() {
+            return Err(GameError::Duplicate)
+        }
+
+        // only a game signed by its author can get the author excluded
+        game.validate(self.rounds())?;
+
+        let committed = self.commitments.get(&game.author).ok_or(GameError::NoCommitment)?;
+        if *committed != commitment_hash(&game.author, &game.salt, &game.rounds) {
+            self.rejected.insert(game.author);
+            return Err(GameError::CommitmentMismatch)
         }
+
+        self.signs.insert(game.author.clone(), game.sign);
+        self.roster.insert(game.author, Some(game.rounds));
+        Ok(())
     }
 
     pub fn create_game(&self, key: &Key) -> Game {
-        let count = self.roster.len();
-        let count = count + count % 2;
-        let count = (count as f64).log2() as usize;
+        let count = self.rounds();
         let mut buf = vec![0u8; count];
 
         OsRng.fill_bytes(&mut buf);
 
-        for i in 0..count {
-            buf[i] %= 3;
+        for v in buf.iter_mut() {
+            *v %= 3;
         }
 
-        let sign = key.sign(&buf).unwrap();
+        let sign = key.sign(&payload(&self.parent, self.epoch, &self.roster_hash, &buf)).unwrap();
+
+        let mut salt = vec![0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
 
         Game { 
             author: key.public_key.clone(), 
+            parent: self.parent.clone(),
+            epoch: self.epoch,
+            roster: self.roster_hash.clone(),
             sign,
-            rounds: buf
+            rounds: buf,
+            salt,
         }
     }
 
+    ///
+    /// The nodes this node is missing, the ones without a commitment until all committed,
+    /// then the ones without a game.
+    ///
+    fn missing(&self) -> Vec<PubKey> {
+        let committed = self.is_committed();
+        let mut missing: Vec<PubKey> = self.roster
+            .iter()
+            .filter(|(id, rounds)| if committed { rounds.is_none() } else { !self.commitments.contains_key(*id) })
+            .map(|(id, _)| id.clone())
+            .collect();
+        missing.sort();
+
+        missing
+    }
+
+    ///
+    /// Votes to drop the nodes this node is missing, after waiting for them in time.
+    ///
+    pub fn vote(&mut self, key: &Key) -> Vote {
+        let reveal = self.is_committed();
+        let missing = self.missing();
+        let sign = key.sign(&payload(&self.parent, self.epoch, &self.roster_hash, &vote_data(reveal, &missing))).unwrap();
+
+        let vote = Vote {
+            author: key.public_key.clone(),
+            parent: self.parent.clone(),
+            epoch: self.epoch,
+            roster: self.roster_hash.clone(),
+            reveal,
+            missing,
+            sign,
+        };
+        self.add_vote(vote.clone());
+
+        vote
+    }
+
+    ///
+    /// Adds the vote of a node of the roster, the nodes a majority voted for are dropped.
+    ///
+    /// Returns if the vote is valid and new.
+    ///
+    pub fn add_vote(&mut self, vote: Vote) -> bool {
+        if vote.parent != self.parent || vote.epoch != self.epoch || vote.roster != self.roster_hash {
+            log::warn!("vote of another tournament\nauthor: {}", hex::encode(&vote.author));
+            return false
+        }
+        if !self.is_player(&vote.author) {
+            log::error!("vote author is not part of the game\nauthor: {}", hex::encode(&vote.author));
+            return false
+        }
+        if self.votes.iter().any(|v| v.author == vote.author && v.reveal == vote.reveal) {
+            return false
+        }
+        if let Err(e) = vote.validate() {
+            log::error!("{}\nauthor: {}", e, hex::encode(&vote.author));
+            return false
+        }
+
+        let quorum = quorum(self.roster.len() + self.excluded.len());
+        let missing = vote.missing.clone();
+        self.votes.push(vote);
+
+        for id in missing {
+            let voters: HashSet<&PubKey> = self.votes.iter()
+                .filter(|vote| vote.missing.contains(&id))
+                .map(|vote| &vote.author)
+                .collect();
+            if voters.len() >= quorum {
+                self.exclude(&id);
+            }
+        }
+
+        true
+    }
+
     pub fn is_filled(&self) -> bool {
         for val in self.roster.values() {
             if val.is_none() {
@@ -203,53 +1022,30 @@ impl Highlander {
         true
     }
 
-    pub fn evaluate(&mut self, key: &Key) -> GameResult {
-        let count = self.roster.len();
-        let count = (count + count % 2) * 2 - 1;
-        let mut tree: Vec<Option<PubKey>> = vec![None; count];
-
-        let mut ids: Vec<PubKey> = self.roster.keys().cloned().collect();
-        ids.sort();
-
-        for (i, id) in ids.into_iter().enumerate() {
-            tree[i] = Some(id);
+    ///
+    /// Plays the tournament of the revealed games, there is no winner if all nodes were excluded.
+    ///
+    pub fn evaluate(&mut self, key: &Key) -> Option<GameResult> {
+        if self.roster.is_empty() {
+            log::warn!("all nodes are excluded from the game");
+            self.clear();
+            return None
         }
-        
-        let mut lvl = 0;
-        let mut offset = 0;
-        let count = self.roster.len();
-        let mut count = count + count % 2;
-
-        while count > 1 {
-            let rng = IntIter{ pos: 0, end: count, step: 2 };
-            for i in rng {
-                let p0 = tree[offset + i].clone().unwrap();
-                let v0 = self.roster[&p0].as_ref().unwrap()[lvl];
-
-                let w = if let Some(ref p1) = tree[offset + i + 1] {
-                    let v1 = self.roster[p1].as_ref().unwrap()[lvl];
 
-                    winner(p0, v0, p1.clone(), v1)
-                }
-                else {
-                    p0
-                };
-                    
-                tree[i / 2 + count + offset] = Some(w);
-            }
-            
-            offset += count;
-            count = count / 2;
-            lvl += 1;
-        }
+        let roster: HashMap<PubKey, Vec<u8>> = self.roster
+            .drain()
+            .map(|(id, rounds)| (id, rounds.unwrap()))
+            .collect();
+        let tree = bracket(&roster, &bracket_seed(&self.parent, self.epoch));
 
         let winner = tree.last().unwrap().clone().unwrap();
         log::info!("winner {}", hex::encode(&winner));
 
-        let result = GameResult::build(tree, &self.roster, key);
+        let signs = std::mem::take(&mut self.signs);
+        let result = GameResult::build(self, tree, roster, signs, key);
 
-        self.roster.clear();
+        self.clear();
 
-        result
+        Some(result)
     }
 }
\ No newline at end of file
@@ -67,4 +67,10 @@ impl Key {
         let sign = k256::ecdsa::Signature::from_bytes(sign)?;
         Ok(verifer.verify(data, &sign)?)
     }
+}
+
+impl Default for Key {
+    fn default() -> Self {
+        Self::new()
+    }
 }
\ No newline at end of file
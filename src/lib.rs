@@ -2,6 +2,7 @@
 
 pub mod blockchain;
 pub mod config;
+pub mod consensus;
 pub mod network;
 pub mod key;
 pub mod highlander;
\ No newline at end of file
@@ -41,28 +41,28 @@ impl Client {
         })
     }
 
-    pub async fn write_aes(&self, data: &Vec<u8>) -> Result<(), anyhow::Error> {
+    pub async fn write_aes(&self, data: &[u8]) -> Result<(), anyhow::Error> {
         let mut iv = [0u8; 16];
         OsRng.fill_bytes(&mut iv);
         let enc = AesCbcEnc::new_from_slices(&self.shared, &iv).unwrap();
-        let encrypted = enc.encrypt_padded_vec_mut::<Pkcs7>(&data);
+        let encrypted = enc.encrypt_padded_vec_mut::<Pkcs7>(data);
 
         let size = (encrypted.len() as u32).to_be_bytes();
 
         let mut writer = self.writer.lock().await;
-        writer.write(&size).await?;
-        writer.write(&iv).await?;
+        writer.write_all(&size).await?;
+        writer.write_all(&iv).await?;
         writer.write_all(&encrypted).await?;
 
         Ok(())
     }
 
-    pub async fn write(&self, data: &Vec<u8>) -> Result<(), anyhow::Error> {
+    pub async fn write(&self, data: &[u8]) -> Result<(), anyhow::Error> {
         let size = (data.len() as u32).to_be_bytes();
 
         let mut writer = self.writer.lock().await;
-        writer.write(&size).await?;
-        writer.write_all(&data).await?;
+        writer.write_all(&size).await?;
+        writer.write_all(data).await?;
 
         Ok(())
     }
@@ -1,23 +1,29 @@
-use std::{sync::Arc, pin::Pin, future::Future};
+use std::{sync::Arc, pin::Pin, future::Future, path::PathBuf, time::Duration};
 
 use tokio::sync::Mutex;
 
 use crate::{
-    highlander::{Highlander, Game, GameResult},
-    blockchain::{Blockchain, Data, Block},
+    consensus::{Consensus, Decision, Step},
+    blockchain::{
+        snapshot::{CHECKPOINT_INTERVAL, MAX_HEADERS},
+        Blockchain, SharedBlockchain, Data, Block, BlockHeader, HeaderSync, Join, Proof, Snapshot,
+    },
     network::{
         client::ClientPtr,
         peer::Peer,
         handler::Handler,
         message::Message,
     },
-    config::Config
+    config::{Config, Retention}
 };
 
+/// A pruning node prunes whenever its height reaches a multiple of this.
+const PRUNE_INTERVAL: usize = 100;
+
 #[derive(PartialEq, Clone, Copy)]
 enum State {
     Idle,
-    Play,
+    Playing,
     ExpectBlock,
 }
 
@@ -36,13 +42,27 @@ macro_rules! check {
 #[derive(Clone)]
 pub struct DaemonHandler {
     state: Arc<Mutex<State>>,
-    highlander: Arc<Mutex<Highlander>>,
-    blockchain: Arc<Mutex<Blockchain>>,
+    /// Also holds the [Consensus], which checks the blocks as they connect.
+    blockchain: SharedBlockchain,
+    retention: Option<Retention>,
+    folder: PathBuf,
+    /// The hash of the trusted snapshot an empty chain starts from.
+    checkpoint: Option<Vec<u8>>,
+    /// The headers of the trusted snapshot while they are fetched.
+    sync: Arc<Mutex<Option<HeaderSync>>>,
 }
 
 impl DaemonHandler {
+    ///
+    /// Replaces the leader election of the node, the one of the config is used by default.
+    ///
+    pub async fn set_consensus(&self, consensus: Box<dyn Consensus>) {
+        self.blockchain.run(move |bc| bc.set_consensus(consensus)).await;
+    }
+
     async fn on_share(&self, peer: Peer<Self>, client: ClientPtr, data: Data) {
-        self.blockchain.lock().await.add_to_cache(data.clone());
+        let cached = data.clone();
+        self.blockchain.run(move |bc| bc.add_to_cache(cached)).await;
 
         let msg = Message::Share { data };
         check!(peer.broadcast(msg, Some(&client), None).await);
@@ -50,110 +70,488 @@ impl DaemonHandler {
         let mut state = self.state.lock().await;
 
         if *state == State::Idle {
-            *state = State::Play;
-
-            let mut all_known = peer.all_known.lock().await.clone();
-            all_known.insert(peer.key.public_key.clone());
-            let mut hl = self.highlander.lock().await;
-            hl.populate_roster(all_known.iter());
-            let game = hl.create_game(&peer.key);
-            hl.add_game(game.clone());
+            let proof = self.start_round(&peer, &mut state).await;
+            drop(state);
+            if let Some(proof) = proof {
+                self.generate_new_block(&peer, proof).await;
+            }
+        }
+    }
 
-            if hl.is_filled() {
-                let result = hl.evaluate(&peer.key);
-                if result.winner == peer.key.public_key {
-                    self.generate_new_block(&peer, result).await;
+    ///
+    /// Starts the round for the block on the tip, played by the members the tip names.
+    ///
+    /// The first block of a chain is played by the nodes this node knows. A node which is not
+    /// a member asks to join, so a later block takes it in.
+    ///
+    async fn start_round(&self, peer: &Peer<Self>, state: &mut State) -> Option<Proof> {
+        *state = State::Playing;
+
+        let known = peer.all_known.lock().await.clone();
+        let key = peer.key.clone();
+        let (round, step, join) = self.blockchain.run(move |bc| {
+            let (tip, height) = bc.highest_block();
+            let (nodes, join) = match bc.members() {
+                Some(members) => {
+                    let join = (!members.contains(&key.public_key)).then(|| Join::build(&key, height + 1));
+                    (members.into_iter().collect(), join)
                 }
-                else {
-                    log::error!("something really bad happend here");
+                None => (known, None),
+            };
+            if let Some(join) = &join {
+                bc.add_join(join.clone());
+            }
+
+            let step = bc.consensus_mut().start_round(&tip, height + 1, &nodes, &key);
+            (bc.consensus().round(), step, join)
+        }).await;
+
+        if let Some(join) = join {
+            log::info!("not a member yet, asking to join");
+            check!(peer.broadcast(Message::Join { join }, None, None).await);
+        }
+        self.apply(peer, state, round, step).await
+    }
+
+    ///
+    /// Sends the messages of a [Step] and starts its deadline.
+    ///
+    /// The proof is returned if this node produces the block right away, a fallback producer
+    /// produces it after its delay, if no other block arrived until then.
+    ///
+    async fn apply(&self, peer: &Peer<Self>, state: &mut State, round: u64, step: Step) -> Option<Proof> {
+        for msg in step.broadcast {
+            check!(peer.broadcast(msg, None, None).await);
+        }
+
+        if let Some(after) = step.deadline {
+            self.deadline(peer.clone(), round, after);
+        }
+
+        match step.decision? {
+            Decision::Produce { proof, delay } => {
+                *state = State::ExpectBlock;
+                if delay.is_zero() {
+                    return Some(proof)
                 }
+
+                log::info!("waiting for new block, fallback producer after {:?}", delay);
+                self.fallback(peer.clone(), round, delay, proof);
             }
-            else {
-                let msg = Message::Play { game };
-                check!(peer.broadcast(msg, None, None).await);
+            Decision::Wait { deadline } => {
+                *state = State::ExpectBlock;
+                log::info!("waiting for new block, starting over after {:?}", deadline);
+                self.expect_block(peer.clone(), round, deadline);
             }
         }
+
+        None
     }
 
-    async fn generate_new_block(&self, peer: &Peer<Self>, result: GameResult) {
-        log::info!("create new block");
+    ///
+    /// Tells the consensus that the deadline of `round` passed, if it is still running.
+    ///
+    fn deadline(&self, peer: Peer<Self>, round: u64, after: Duration) {
+        let handler = self.clone();
 
-        let block = self.blockchain.lock().await.generate_new_block(result, &peer.key);
-        block.validate();
-        let msg = Message::AddBlock { block };
-        check!(peer.broadcast(msg, None, None).await);
-        *self.state.lock().await = State::Idle;
+        tokio::spawn(async move {
+            tokio::time::sleep(after).await;
+
+            let mut state = handler.state.lock().await;
+            if *state != State::Playing {
+                return
+            }
+
+            let key = peer.key.clone();
+            let step = handler.blockchain.run(move |bc| {
+                if bc.consensus().round() != round {
+                    return None
+                }
+                let step = bc.consensus_mut().timeout(&key);
+                Some((bc.consensus().round(), step))
+            }).await;
+            let (round, step) = match step {
+                Some(step) => step,
+                None => return,
+            };
+            let proof = handler.apply(&peer, &mut state, round, step).await;
+            drop(state);
+            if let Some(proof) = proof {
+                handler.generate_new_block(&peer, proof).await;
+            }
+        });
     }
 
-    async fn on_game(&self, peer: Peer<Self>, client: ClientPtr, game: Game) {
-        let state = self.state.lock().await;
-        
-        if *state == State::Play { 
-            let mut hl = self.highlander.lock().await;
-            hl.add_game(game.clone());
+    ///
+    /// Starts the round over after `after`, if no block arrived until then and no other round was started.
+    ///
+    fn expect_block(&self, peer: Peer<Self>, round: u64, after: Duration) {
+        let handler = self.clone();
 
-            let msg = Message::Play { game };
-            check!(peer.broadcast(msg, Some(&client), None).await);
+        tokio::spawn(async move {
+            tokio::time::sleep(after).await;
 
-            if hl.is_filled() {
-                let result = hl.evaluate(&peer.key);
+            let mut state = handler.state.lock().await;
+            if *state != State::ExpectBlock {
+                return
+            }
 
-                if result.winner == peer.key.public_key {
-                    self.generate_new_block(&peer, result).await;
-                }
-                else {
-                    *self.state.lock().await = State::ExpectBlock;
-                    log::info!("waiting for new block");
+            let current = handler.blockchain.run(|bc| bc.consensus().round()).await;
+            if current != round {
+                return
+            }
+
+            log::warn!("no block from the nodes decided before, starting the round over");
+            let proof = handler.start_round(&peer, &mut state).await;
+            drop(state);
+            if let Some(proof) = proof {
+                handler.generate_new_block(&peer, proof).await;
+            }
+        });
+    }
+
+    ///
+    /// Produces the block after `delay`, if the chain did not move on and no other round was started.
+    ///
+    fn fallback(&self, peer: Peer<Self>, round: u64, delay: Duration, proof: Proof) {
+        let handler = self.clone();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+
+            let (current, (tip, _)) = handler.blockchain.run(|bc| (bc.consensus().round(), bc.highest_block())).await;
+            if current != round || proof.game().is_some_and(|game| game.parent != tip) {
+                return
+            }
+
+            log::warn!("no block from the nodes decided before, producing it as fallback");
+            handler.generate_new_block(&peer, proof).await;
+        });
+    }
+
+    async fn generate_new_block(&self, peer: &Peer<Self>, proof: Proof) {
+        log::info!("create new block");
+
+        let key = peer.key.clone();
+        match self.blockchain.run(move |bc| bc.generate_new_block(proof, &key)).await {
+            Ok(block) => {
+                let height = block.header.height;
+                let msg = Message::AddBlock { block };
+                check!(peer.broadcast(msg, None, None).await);
+
+                if height.is_multiple_of(CHECKPOINT_INTERVAL) {
+                    self.create_snapshot(peer).await;
                 }
             }
+            Err(e) => log::error!("could not create block: {}", e),
+        }
+        *self.state.lock().await = State::Idle;
+        self.prune().await;
+    }
+
+    async fn create_snapshot(&self, peer: &Peer<Self>) {
+        let key = peer.key.clone();
+        let folder = self.folder.clone();
+        let snapshot = self.blockchain.run(move |bc| {
+            let snapshot = bc.snapshot(&key)?;
+            snapshot.save(&folder)?;
+            Ok::<_, anyhow::Error>(snapshot)
+        }).await;
+
+        match snapshot {
+            Ok(snapshot) => {
+                log::info!("created snapshot {}", hex::encode(&snapshot.hash));
+                let msg = Message::Snapshot { snapshot: Some(snapshot) };
+                check!(peer.broadcast(msg, None, None).await);
+            }
+            Err(e) => log::error!("could not create snapshot: {}", e),
         }
-        else {
-            log::error!("got game while not playing");
+    }
+
+    async fn prune(&self) {
+        if let Some(retention) = self.retention.clone() {
+            self.blockchain.run(move |bc| {
+                if bc.highest_block().1.is_multiple_of(PRUNE_INTERVAL) {
+                    check!(bc.prune(&retention));
+                }
+            }).await;
+        }
+    }
+
+    ///
+    /// Passes a consensus message on to the [Consensus], which keeps the messages of a round
+    /// this node did not start yet until it does.
+    ///
+    async fn on_consensus(&self, peer: Peer<Self>, client: ClientPtr, msg: Message) {
+        let mut state = self.state.lock().await;
+
+        let key = peer.key.clone();
+        let (round, step, msg) = self.blockchain.run(move |bc| {
+            let step = bc.consensus_mut().handle(&msg, &key);
+            (bc.consensus().round(), step, msg)
+        }).await;
+
+        // only valid messages are relayed
+        if step.relay {
+            check!(peer.broadcast(msg, Some(&client), None).await);
+        }
+
+        let proof = self.apply(&peer, &mut state, round, step).await;
+        drop(state);
+        if let Some(proof) = proof {
+            self.generate_new_block(&peer, proof).await;
         }
     }
 
     async fn on_new_block(&self, peer: Peer<Self>, client: ClientPtr, block: Block) {
         log::info!("got new block");
-        self.blockchain.lock().await.add_new_block(block.clone());
+
+        // only valid blocks are relayed
+        let added = block.clone();
+        let valid = self.blockchain.run(move |bc| bc.add_new_block(added)).await;
+        if !valid {
+            return
+        }
 
         let msg = Message::AddBlock { block };
         check!(peer.broadcast(msg, Some(&client), None).await);
         *self.state.lock().await = State::Idle;
+        self.prune().await;
     }
 
-    async fn on_highest_hash(&self, _peer: Peer<Self>, client: ClientPtr, hash: Vec<u8>, count: usize) {
-        let (myhash, mycount) = self.blockchain.lock().await.highest_block();
+    async fn on_join(&self, peer: Peer<Self>, client: ClientPtr, join: Join) {
+        // only valid joins are relayed
+        let added = join.clone();
+        if self.blockchain.run(move |bc| bc.add_join(added)).await {
+            let msg = Message::Join { join };
+            check!(peer.broadcast(msg, Some(&client), None).await);
+        }
+    }
+
+    async fn on_highest_hash(&self, _peer: Peer<Self>, client: ClientPtr, hash: Vec<u8>, count: usize, lowest: usize) {
+        let (myhash, mycount) = self.blockchain.run(|bc| bc.highest_block()).await;
         
-        if myhash != hash && mycount < count {
+        if self.checkpoint.is_some() && mycount <= 1 {
+            log::debug!("waiting for the checkpoint snapshot");
+            return
+        }
+
+        // the rank of the tip is only known from its header, on equal height the chain decides once the blocks are in
+        if myhash != hash && count >= mycount {
+            if lowest > mycount + 1 {
+                log::warn!("peer pruned the missing blocks:\nheight: {}\nlowest: {}", mycount, lowest);
+                return
+            }
+
             let msg = Message::RequestBlocks { from: myhash, to: hash };
-            client.write_aes(&msg.to_bytes().unwrap()).await.unwrap();
+            check!(client.write_aes(&msg.to_bytes().unwrap()).await);
         }
     }
 
     async fn on_request_blocks(&self, _peer: Peer<Self>, client: ClientPtr, from: Vec<u8>, to: Vec<u8>) {
         log::debug!("request blocks:\nfrom: {}\nto:   {}", hex::encode(&from), hex::encode(&to));
 
-        let blocks = self.blockchain.lock().await.get_blocks(from, to).await.unwrap();
-        let msg = Message::Blocks { blocks };
-        client.write_aes(&msg.to_bytes().unwrap()).await.unwrap();
+        match self.blockchain.run(move |bc| bc.get_blocks(from, to)).await {
+            Ok(blocks) => {
+                let msg = Message::Blocks { blocks };
+                check!(client.write_aes(&msg.to_bytes().unwrap()).await);
+            }
+            Err(e) => log::error!("could not read blocks: {}", e),
+        }
     }
 
-    async fn on_blocks(&self, _peer: Peer<Self>, _client: ClientPtr, blocks: Vec<Block>) {
-        let mut bc = self.blockchain.lock().await;
+    async fn on_proof_request(&self, _peer: Peer<Self>, client: ClientPtr, data_hash: Vec<u8>) {
+        log::debug!("proof request {}", hex::encode(&data_hash));
+
+        let hash = data_hash.clone();
+        let proof = self.blockchain.run(move |bc| bc.prove(&hash)).await;
+        let msg = Message::Proof { data_hash, proof };
+        check!(client.write_aes(&msg.to_bytes().unwrap()).await);
+    }
+
+    async fn on_snapshot_request(&self, _peer: Peer<Self>, client: ClientPtr, hash: Vec<u8>) {
+        log::debug!("snapshot request {}", hex::encode(&hash));
+
+        let folder = self.folder.clone();
+        let (snapshot, (tip, count), lowest) = self.blockchain.run(move |bc| {
+            (Snapshot::load(&folder, &hash), bc.highest_block(), bc.lowest())
+        }).await;
+
+        let snapshot = match snapshot {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                log::error!("could not load snapshot: {}", e);
+                None
+            }
+        };
 
-        for block in blocks {
-            bc.add_new_block(block);
+        // the highest block tells the requester where to sync to after the snapshot
+        let msg = Message::Snapshot { snapshot };
+        check!(client.write_aes(&msg.to_bytes().unwrap()).await);
+        let msg = Message::HighestBlock { hash: tip, count, lowest };
+        check!(client.write_aes(&msg.to_bytes().unwrap()).await);
+    }
+
+    async fn on_snapshot(&self, peer: Peer<Self>, client: ClientPtr, snapshot: Option<Snapshot>) {
+        let snapshot = match snapshot {
+            Some(snapshot) => snapshot,
+            None => {
+                log::warn!("peer has no snapshot");
+                return
+            }
+        };
+
+        let folder = self.folder.clone();
+        let hash = snapshot.hash.clone();
+        let (known, height) = self.blockchain.run(move |bc| {
+            (Snapshot::load(&folder, &hash).map(|s| s.is_some()), bc.highest_block().1)
+        }).await;
+        match known {
+            Ok(false) => {}
+            Ok(true) => return,
+            Err(e) => {
+                log::error!("could not load snapshot: {}", e);
+                return
+            }
+        }
+
+        // the headers of the trusted snapshot are fetched from the checkpoint down
+        if Some(&snapshot.hash) == self.checkpoint.as_ref() && height <= 1 {
+            if !snapshot.validate() {
+                log::error!("rejected snapshot {}", hex::encode(&snapshot.hash));
+                return
+            }
+
+            let mut sync = self.sync.lock().await;
+            if sync.is_none() {
+                let headers = HeaderSync::new(snapshot, MAX_HEADERS);
+                self.request_headers(&client, &headers).await;
+                *sync = Some(headers);
+            }
+            return
+        }
+
+        let folder = self.folder.clone();
+        let relay = snapshot.clone();
+        let result = self.blockchain.run(move |bc| {
+            let height = snapshot.checkpoint.height;
+            if !bc.on_main_chain(&snapshot.checkpoint.hash) || !snapshot.validate()
+                || !snapshot.verify_headers(&bc.headers(1, height)?) {
+                anyhow::bail!("snapshot {} does not match the chain", hex::encode(&snapshot.hash));
+            }
+
+            snapshot.save(&folder)
+        }).await;
+
+        match result {
+            Ok(()) => {
+                let msg = Message::Snapshot { snapshot: Some(relay) };
+                check!(peer.broadcast(msg, Some(&client), None).await);
+            }
+            Err(e) => log::error!("rejected snapshot: {}", e),
         }
     }
+
+    async fn request_headers(&self, client: &ClientPtr, sync: &HeaderSync) {
+        if let Some((from, to)) = sync.next_range() {
+            log::debug!("request headers {} to {}", from, to);
+            let msg = Message::HeadersRequest { from, to };
+            check!(client.write_aes(&msg.to_bytes().unwrap()).await);
+        }
+    }
+
+    async fn on_headers_request(&self, _peer: Peer<Self>, client: ClientPtr, from: usize, to: usize) {
+        let to = to.min(from.saturating_add(MAX_HEADERS - 1));
+        let (headers, (tip, count), lowest) = self.blockchain.run(move |bc| {
+            (bc.headers(from, to), bc.highest_block(), bc.lowest())
+        }).await;
+
+        match headers {
+            Ok(headers) => {
+                let msg = Message::Headers { headers };
+                check!(client.write_aes(&msg.to_bytes().unwrap()).await);
+            }
+            Err(e) => {
+                log::error!("could not read headers: {}", e);
+                return
+            }
+        }
+
+        // the last range completes the snapshot, the highest block tells the requester where to sync to
+        if from <= 1 {
+            let msg = Message::HighestBlock { hash: tip, count, lowest };
+            check!(client.write_aes(&msg.to_bytes().unwrap()).await);
+        }
+    }
+
+    async fn on_headers(&self, peer: Peer<Self>, client: ClientPtr, headers: Vec<BlockHeader>) {
+        let mut sync = self.sync.lock().await;
+        let mut headers_sync = match sync.take() {
+            Some(headers_sync) => headers_sync,
+            None => {
+                log::warn!("got headers without a snapshot");
+                return
+            }
+        };
+
+        if let Err(e) = headers_sync.add(headers) {
+            log::error!("rejected headers: {}", e);
+            return
+        }
+        if headers_sync.next_range().is_some() {
+            self.request_headers(&client, &headers_sync).await;
+            *sync = Some(headers_sync);
+            return
+        }
+
+        let (snapshot, headers) = match headers_sync.finish() {
+            Some(finished) => finished,
+            None => return,
+        };
+        let folder = self.folder.clone();
+        let relay = snapshot.clone();
+        let result = self.blockchain.run(move |bc| {
+            bc.bootstrap(&snapshot, &headers)?;
+            snapshot.save(&folder)
+        }).await;
+
+        match result {
+            Ok(()) => {
+                let msg = Message::Snapshot { snapshot: Some(relay) };
+                check!(peer.broadcast(msg, Some(&client), None).await);
+            }
+            Err(e) => log::error!("rejected snapshot: {}", e),
+        }
+    }
+
+    async fn on_blocks(&self, _peer: Peer<Self>, _client: ClientPtr, blocks: Vec<Block>) {
+        self.blockchain.run(move |bc| {
+            for block in blocks {
+                bc.add_new_block(block);
+            }
+        }).await;
+        self.prune().await;
+    }
 }
 
 impl Handler for DaemonHandler {
 
     fn new(config: &Config) -> Self {
+        let blockchain = Blockchain::open(config).unwrap();
+
         Self {
             state: Arc::new(Mutex::new(State::Idle)),
-            highlander: Arc::new(Mutex::new(Highlander::new())),
-            blockchain: Arc::new(Mutex::new(Blockchain::new(&config.folder))),
+            blockchain: SharedBlockchain::new(blockchain),
+            retention: config.prune.clone(),
+            folder: PathBuf::from(&config.folder),
+            checkpoint: config.checkpoint.as_ref().and_then(|c| match hex::decode(c) {
+                Ok(hash) => Some(hash),
+                Err(e) => {
+                    log::error!("invalid checkpoint {}: {}", c, e);
+                    None
+                }
+            }),
+            sync: Arc::new(Mutex::new(None)),
         }
     }
     
@@ -162,8 +560,14 @@ impl Handler for DaemonHandler {
         Self: Sync + 'a {
         async fn run(_self: &DaemonHandler, _peer: Peer<DaemonHandler>, _client: ClientPtr) where
         {
-            let (hash, count) = _self.blockchain.lock().await.highest_block();
-            let msg = Message::HighestBlock { hash, count };
+            let ((hash, count), lowest) = _self.blockchain.run(|bc| (bc.highest_block(), bc.lowest())).await;
+
+            if let (Some(checkpoint), 0..=1) = (&_self.checkpoint, count) {
+                let msg = Message::SnapshotRequest { hash: checkpoint.clone() };
+                check!(_client.write_aes(&msg.to_bytes().unwrap()).await);
+            }
+
+            let msg = Message::HighestBlock { hash, count, lowest };
             check!(_peer.broadcast(msg, None, None).await);
         }
 
@@ -174,7 +578,7 @@ impl Handler for DaemonHandler {
     where
         Self: Sync + 'a {
         async fn run(_self: &DaemonHandler, _peer: Peer<DaemonHandler>) {
-            _self.blockchain.lock().await.save_index();            
+            _self.blockchain.run(|bc| bc.flush()).await;
         }
 
         Box::pin(run(self, peer)) 
@@ -186,8 +590,8 @@ impl Handler for DaemonHandler {
         async fn run(_self: &DaemonHandler, peer: Peer<DaemonHandler>, client: ClientPtr, msg: Message) {
             
             match msg {
-                Message::Play { game } => {
-                    _self.on_game(peer, client, game).await;
+                Message::Commit { .. } | Message::Play { .. } | Message::Vote { .. } => {
+                    _self.on_consensus(peer, client, msg).await;
                 }
                 Message::Share { data } => {
                     _self.on_share(peer, client, data).await;
@@ -195,8 +599,11 @@ impl Handler for DaemonHandler {
                 Message::AddBlock { block } => {
                     _self.on_new_block(peer, client, block).await;
                 }
-                Message::HighestBlock { hash, count } => {
-                    _self.on_highest_hash(peer, client, hash, count).await;
+                Message::Join { join } => {
+                    _self.on_join(peer, client, join).await;
+                }
+                Message::HighestBlock { hash, count, lowest } => {
+                    _self.on_highest_hash(peer, client, hash, count, lowest).await;
                 }
                 Message::RequestBlocks { from, to } => {
                     _self.on_request_blocks(peer, client, from, to).await;
@@ -204,6 +611,21 @@ impl Handler for DaemonHandler {
                 Message::Blocks { blocks } => {
                     _self.on_blocks(peer, client, blocks).await;
                 }
+                Message::ProofRequest { data_hash } => {
+                    _self.on_proof_request(peer, client, data_hash).await;
+                }
+                Message::SnapshotRequest { hash } => {
+                    _self.on_snapshot_request(peer, client, hash).await;
+                }
+                Message::Snapshot { snapshot } => {
+                    _self.on_snapshot(peer, client, snapshot).await;
+                }
+                Message::HeadersRequest { from, to } => {
+                    _self.on_headers_request(peer, client, from, to).await;
+                }
+                Message::Headers { headers } => {
+                    _self.on_headers(peer, client, headers).await;
+                }
                 _ => {}
             }
         }
@@ -2,8 +2,8 @@ use serde::{Serialize, Deserialize};
 
 use crate::{
     key::PubKey,
-    blockchain::{Data, Block},
-    highlander::Game
+    blockchain::{Data, Block, BlockHeader, InclusionProof, Join, Snapshot},
+    highlander::{Game, Commitment, Vote}
 };
 
 ///
@@ -11,13 +11,17 @@ use crate::{
 /// 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag="kind")]
+#[allow(clippy::large_enum_variant)]
 pub enum Message {
     Greeting {
         #[serde(with="serde_bytes")]
         id: PubKey,
         #[serde(with="serde_bytes")]
         shared: PubKey,
-        thin: bool
+        thin: bool,
+        /// The hash of the genesis block of the sender.
+        #[serde(with="serde_bytes", default)]
+        network: Vec<u8>
     },
     AllKnown { 
         all_known: Vec<serde_bytes::ByteBuf>
@@ -33,7 +37,9 @@ pub enum Message {
     HighestBlock{
         #[serde(with="serde_bytes")]
         hash: Vec<u8>,
-        count: usize
+        count: usize,
+        /// The lowest height the sender can serve blocks from, it pruned the blocks below.
+        lowest: usize
     },
     RequestBlocks{
         #[serde(with="serde_bytes")]
@@ -43,8 +49,31 @@ pub enum Message {
     },
     Blocks {blocks: Vec<Block>},
     Share {data: Data},
+    Commit {commitment: Commitment},
     Play {game: Game},
+    Vote {vote: Vote},
     AddBlock { block: Block },
+    Join { join: Join },
+    ProofRequest {
+        #[serde(with="serde_bytes")]
+        data_hash: Vec<u8>
+    },
+    Proof {
+        #[serde(with="serde_bytes")]
+        data_hash: Vec<u8>,
+        proof: Option<InclusionProof>
+    },
+    SnapshotRequest {
+        #[serde(with="serde_bytes")]
+        hash: Vec<u8>
+    },
+    Snapshot { snapshot: Option<Snapshot> },
+    HeadersRequest {
+        /// The heights of the first and the last requested main chain header.
+        from: usize,
+        to: usize
+    },
+    Headers { headers: Vec<BlockHeader> },
 }
 
 impl Message {
@@ -52,7 +81,7 @@ impl Message {
         rmp_serde::to_vec_named(self)
     }
 
-    pub fn from_bytes(v: &Vec<u8>) -> Result<Self, rmp_serde::decode::Error> {
+    pub fn from_bytes(v: &[u8]) -> Result<Self, rmp_serde::decode::Error> {
         rmp_serde::from_slice(v)
     }
 }
\ No newline at end of file
@@ -15,6 +15,7 @@ use tokio::{
 };
 
 use crate::{
+    blockchain::genesis,
     config::Config,
     key::{Key, PubKey},
     network::{
@@ -35,13 +36,15 @@ macro_rules! check {
 }
 
 #[derive(Clone)]
-pub struct Peer<T> where T: Handler + ?Sized {
+pub struct Peer<T> where T: Handler {
     pub key: Arc<Key>,
     pub config: Config,
     close: Arc<Notify>,
     clients: Arc<Mutex<HashMap<SocketAddr, ClientPtr>>>,
     pub all_known: Arc<Mutex<HashSet<PubKey>>>,
     pub handler: Arc<T>,
+    /// The hash of the genesis block, only peers with the same one are accepted.
+    pub network: Vec<u8>,
 }
 
 impl<T> Peer<T> 
@@ -50,14 +53,20 @@ where
 {
     pub fn new(config: Config) -> Self {
         let handler = Arc::new(T::new(&config));
+        let network = genesis::network_id(&config).unwrap();
+        let key = match &config.key {
+            Some(filename) => Key::load(filename).unwrap(),
+            None => Key::new(),
+        };
 
         Self {
-            key: Arc::new(Key::new()),
-            config: config,
+            key: Arc::new(key),
+            config,
             close: Arc::new(Notify::new()),
             clients: Arc::new(Mutex::new(HashMap::new())),
             all_known: Arc::new(Mutex::new(HashSet::new())),
             handler,
+            network,
         }
     }
 
@@ -118,13 +127,23 @@ where
                 id: peer.key.public_key.clone(),
                 shared: shared.to_vec(),
                 thin: peer.config.thin,
+                network: peer.network.clone(),
             }.to_bytes().unwrap();
             client.write(&greet).await.unwrap();
 
             let res = client.read().await.unwrap();
-            if let Message::Greeting { id, thin, shared } = res {
+            if let Message::Greeting { id, thin, shared, network } = res {
                 log::info!("id {}", hex::encode(&id));
 
+                if network != peer.network {
+                    log::warn!(
+                        "refuse peer of another network:\nid:      {}\nnetwork: {}",
+                        hex::encode(&id),
+                        hex::encode(&network),
+                    );
+                    return
+                }
+
                 let shared = k256::PublicKey::from_sec1_bytes(&shared).unwrap();
                 let shared = client.ephemeral.diffie_hellman(&shared).raw_secret_bytes().to_vec();
                 if let Some(cl) = Arc::get_mut(&mut client) {
@@ -133,7 +152,7 @@ where
                     cl.thin = thin;
                 }
 
-                peer.clients.lock().await.insert(client.addr.clone(), client.clone());
+                peer.clients.lock().await.insert(client.addr, client.clone());
 
                 peer.handler.init(peer.clone(), client.clone()).await;
 
@@ -0,0 +1,44 @@
+//!
+//! Helpers shared by the integration tests, each test crate uses only some of them.
+//!
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use mccloud::{
+    blockchain::{Block, Blockchain, Proof},
+    highlander::Highlander,
+    key::Key,
+};
+
+///
+/// Removes the test folder `filename` of an earlier run.
+///
+pub fn save_remove(filename: &str) {
+    let path = Path::new(filename);
+
+    if path.exists() {
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
+
+///
+/// Plays the tournament for the block at height `epoch` on `parent`, with `key` as the only player.
+///
+pub fn play(key: &Key, parent: &[u8], epoch: usize) -> Proof {
+    let mut hl = Highlander::new();
+    hl.set_epoch(parent, epoch);
+    hl.populate_roster([key.public_key.clone()].iter());
+    let game = hl.create_game(key);
+    hl.add_commitment(game.commitment(key));
+    hl.add_game(game).unwrap();
+    Proof::Game(hl.evaluate(key).unwrap())
+}
+
+///
+/// Produces the next block of `bc` with `key`.
+///
+pub fn generate(bc: &mut Blockchain, key: &Key) -> Block {
+    let (tip, height) = bc.highest_block();
+    bc.generate_new_block(play(key, &tip, height + 1), key).unwrap()
+}
@@ -0,0 +1,362 @@
+use std::{collections::{HashSet, VecDeque}, time::Duration};
+
+use mccloud::{
+    blockchain::{Block, BlockHeader, Proof, block::now},
+    consensus::{block_deadline, Authority, Consensus, Decision, HighlanderConsensus, RoundRobin, Step},
+    key::{Key, PubKey},
+    network::message::Message,
+};
+
+fn nodes(keys: &[Key]) -> HashSet<PubKey> {
+    keys.iter().map(|k| k.public_key.clone()).collect()
+}
+
+///
+/// A parent block header which names the keys as its members.
+///
+fn parent(keys: &[Key]) -> BlockHeader {
+    let mut header = Block::build(&[0u8; 32], 0, 0, Proof::Elected, &keys[0], Vec::new()).header;
+    header.members = keys.iter().map(|k| k.public_key.clone()).collect();
+    header.members.sort();
+    header
+}
+
+///
+/// Delivers the messages of the steps to every other node in order, until no node sends anything.
+///
+fn exchange(nodes: &mut [Box<dyn Consensus>], keys: &[Key], steps: Vec<Step>) -> Vec<Option<Decision>> {
+    deliver(nodes, keys, steps, |_, _| true)
+}
+
+///
+/// Like [exchange], but only the messages from a node to another one for which `reaches` is true arrive.
+///
+fn deliver(
+    nodes: &mut [Box<dyn Consensus>],
+    keys: &[Key],
+    steps: Vec<Step>,
+    reaches: impl Fn(usize, usize) -> bool,
+) -> Vec<Option<Decision>> {
+    let mut decisions: Vec<Option<Decision>> = (0..nodes.len()).map(|_| None).collect();
+    let mut queue: VecDeque<(usize, Message)> = VecDeque::new();
+
+    for (i, step) in steps.into_iter().enumerate() {
+        queue.extend(step.broadcast.into_iter().map(|m| (i, m)));
+        decisions[i] = decisions[i].take().or(step.decision);
+    }
+
+    while let Some((from, msg)) = queue.pop_front() {
+        for (i, node) in nodes.iter_mut().enumerate() {
+            if i != from && reaches(from, i) {
+                let step = node.handle(&msg, &keys[i]);
+                queue.extend(step.broadcast.into_iter().map(|m| (i, m)));
+                decisions[i] = decisions[i].take().or(step.decision);
+            }
+        }
+    }
+
+    decisions
+}
+
+#[test]
+fn highlander_decides_ranked_producers() {
+    let keys: Vec<Key> = (0..3).map(|_| Key::new()).collect();
+    let all = nodes(&keys);
+    let mut hls: Vec<Box<dyn Consensus>> = keys.iter()
+        .map(|_| Box::new(HighlanderConsensus::new(Duration::from_secs(10), None)) as Box<dyn Consensus>)
+        .collect();
+
+    let steps: Vec<Step> = hls.iter_mut().zip(&keys)
+        .map(|(hl, key)| hl.start_round(&[4u8; 32], 2, &all, key))
+        .collect();
+    assert!(steps.iter().all(|s| s.deadline.is_some() && s.decision.is_none()));
+
+    let decisions = exchange(&mut hls, &keys, steps);
+    let mut delays: Vec<Duration> = decisions.into_iter().map(|d| match d {
+        Some(Decision::Produce { proof, delay }) => {
+            assert!(proof.game().unwrap().verify(None).is_ok());
+            delay
+        }
+        _ => panic!("every player is ranked"),
+    }).collect();
+    delays.sort();
+    assert_eq!(delays[0], Duration::ZERO);
+    assert!(delays[1] > Duration::ZERO && delays[2] > delays[1]);
+}
+
+#[test]
+fn highlander_checks_the_roster() {
+    let keys: Vec<Key> = (0..3).map(|_| Key::new()).collect();
+    let all = nodes(&keys);
+    let mut hls: Vec<Box<dyn Consensus>> = keys.iter()
+        .map(|_| Box::new(HighlanderConsensus::new(Duration::from_secs(10), None)) as Box<dyn Consensus>)
+        .collect();
+
+    let steps: Vec<Step> = hls.iter_mut().zip(&keys)
+        .map(|(hl, key)| hl.start_round(&[10u8; 32], 5, &all, key))
+        .collect();
+    let decisions = exchange(&mut hls, &keys, steps);
+    let (winner, proof) = decisions.into_iter().zip(&keys).find_map(|(d, key)| match d {
+        Some(Decision::Produce { proof, delay }) if delay.is_zero() => Some((key, proof)),
+        _ => None,
+    }).unwrap();
+    let block = Block::build(&[10u8; 32], 5, now(), proof, winner, Vec::new());
+
+    // the players check the roster they played with, another node the members of the parent
+    let observer = HighlanderConsensus::new(Duration::from_secs(10), None);
+    assert!(hls.iter().all(|hl| hl.validate(&block, None)));
+    assert!(observer.validate(&block, Some(&parent(&keys))));
+    assert!(!observer.validate(&block, Some(&parent(&keys[..2]))));
+
+    // a player which plays a tournament of its own
+    let mut alone = HighlanderConsensus::new(Duration::from_secs(10), None);
+    let proof = match alone.start_round(&[10u8; 32], 5, &nodes(&keys[..1]), &keys[0]).decision {
+        Some(Decision::Produce { proof, .. }) => proof,
+        _ => panic!("a single node wins its tournament"),
+    };
+    let block = Block::build(&[10u8; 32], 5, now(), proof, &keys[0], Vec::new());
+    assert!(alone.validate(&block, None));
+    assert!(hls.iter().all(|hl| !hl.validate(&block, None)));
+    assert!(!observer.validate(&block, Some(&parent(&keys))));
+}
+
+#[test]
+fn highlander_drops_nodes_by_majority() {
+    let keys: Vec<Key> = (0..3).map(|_| Key::new()).collect();
+    let all = nodes(&keys);
+    let mut hls: Vec<Box<dyn Consensus>> = keys.iter()
+        .map(|_| Box::new(HighlanderConsensus::new(Duration::from_secs(10), None)) as Box<dyn Consensus>)
+        .collect();
+
+    // the last node crashed, the others vote for it after the deadline
+    let steps: Vec<Step> = hls.iter_mut().zip(&keys).take(2)
+        .map(|(hl, key)| hl.start_round(&[11u8; 32], 6, &all, key))
+        .collect();
+    let alive = |from: usize, to: usize| from != 2 && to != 2;
+    let decisions = deliver(&mut hls, &keys, steps, alive);
+    assert!(decisions.iter().all(|d| d.is_none()));
+
+    let steps: Vec<Step> = hls.iter_mut().zip(&keys).take(2)
+        .map(|(hl, key)| hl.timeout(key))
+        .collect();
+    assert!(steps.iter().all(|s| matches!(s.broadcast.as_slice(), [Message::Vote { .. }])));
+    let decisions = deliver(&mut hls, &keys, steps, alive);
+    let (winner, proof) = decisions.into_iter().zip(&keys).find_map(|(d, key)| match d {
+        Some(Decision::Produce { proof, delay }) if delay.is_zero() => Some((key, proof)),
+        _ => None,
+    }).unwrap();
+    assert_eq!(proof.game().unwrap().excluded, vec![keys[2].public_key.clone()]);
+    assert_eq!(proof.game().unwrap().votes.len(), 2);
+
+    // every node checks the votes the same way, also the dropped one
+    let block = Block::build(&[11u8; 32], 6, now(), proof.clone(), winner, Vec::new());
+    assert!(hls.iter().all(|hl| hl.validate(&block, Some(&parent(&keys)))));
+
+    // the dropped node is no longer a member after the block
+    let next = Block::build_on(&parent(&keys), now(), proof.clone(), winner, Vec::new(), Vec::new());
+    assert_eq!(next.header.members, parent(&keys[..2]).members);
+
+    // a single node cannot drop another one
+    let mut game = proof.game().unwrap().clone();
+    game.votes.retain(|vote| vote.author() == &winner.public_key);
+    game.sign = winner.sign(&game.hash()).unwrap();
+    let block = Block::build(&[11u8; 32], 6, now(), Proof::Game(game), winner, Vec::new());
+    assert!(hls.iter().all(|hl| !hl.validate(&block, Some(&parent(&keys)))));
+}
+
+#[test]
+fn highlander_gives_up_without_a_majority() {
+    let keys: Vec<Key> = (0..2).map(|_| Key::new()).collect();
+    let mut hl = HighlanderConsensus::new(Duration::from_secs(10), None);
+
+    let step = hl.start_round(&[5u8; 32], 3, &nodes(&keys), &keys[0]);
+    let round = hl.round();
+    assert!(matches!(step.broadcast.as_slice(), [Message::Commit { .. }]));
+
+    // a single vote of two nodes does not drop the other one
+    let step = hl.timeout(&keys[0]);
+    assert!(hl.round() > round);
+    assert!(matches!(step.broadcast.as_slice(), [Message::Vote { .. }]));
+    assert!(step.deadline.is_some() && step.decision.is_none());
+
+    let step = hl.timeout(&keys[0]);
+    assert!(matches!(step.decision, Some(Decision::Wait { deadline }) if deadline == block_deadline(0)));
+}
+
+#[test]
+fn highlander_keeps_messages_of_rounds_not_started() {
+    let keys: Vec<Key> = (0..2).map(|_| Key::new()).collect();
+    let all = nodes(&keys);
+    let mut hls: Vec<Box<dyn Consensus>> = keys.iter()
+        .map(|_| Box::new(HighlanderConsensus::new(Duration::from_secs(10), None)) as Box<dyn Consensus>)
+        .collect();
+
+    // the commitment of the first node arrives before the second node started the round
+    let first = hls[0].start_round(&[12u8; 32], 7, &all, &keys[0]);
+    for msg in &first.broadcast {
+        let step = hls[1].handle(msg, &keys[1]);
+        assert!(!step.relay && step.broadcast.is_empty());
+    }
+
+    // the kept commitment is taken and passed on as the round starts
+    let second = hls[1].start_round(&[12u8; 32], 7, &all, &keys[1]);
+    assert_eq!(second.broadcast.iter().filter(|m| matches!(m, Message::Commit { .. })).count(), 2);
+    assert!(second.broadcast.iter().any(|m| matches!(m, Message::Play { .. })));
+
+    let decisions = exchange(&mut hls, &keys, vec![Step::default(), second]);
+    assert!(decisions.iter().all(|d| matches!(d, Some(Decision::Produce { .. }))));
+
+    // the messages of a later round wait for it, the ones of an earlier round are dropped
+    let mut late = HighlanderConsensus::new(Duration::from_secs(10), None);
+    let early = HighlanderConsensus::new(Duration::from_secs(10), None).start_round(&[12u8; 32], 9, &all, &keys[0]);
+    let old = HighlanderConsensus::new(Duration::from_secs(10), None).start_round(&[12u8; 32], 6, &all, &keys[0]);
+    for msg in early.broadcast.iter().chain(&old.broadcast) {
+        late.handle(msg, &keys[1]);
+    }
+    let step = late.start_round(&[12u8; 32], 8, &all, &keys[1]);
+    assert_eq!(step.broadcast.len(), 1);
+    let step = late.start_round(&[12u8; 32], 9, &all, &keys[1]);
+    assert!(step.broadcast.iter().any(|m| matches!(m, Message::Play { .. })));
+}
+
+#[test]
+fn round_robin_takes_turns() {
+    let keys: Vec<Key> = (0..3).map(|_| Key::new()).collect();
+    let all = nodes(&keys);
+
+    for epoch in 1..=6 {
+        let producer = RoundRobin::producer(&all, epoch).unwrap();
+        let mut rrs: Vec<RoundRobin> = keys.iter().map(|_| RoundRobin::new()).collect();
+        let mut blocks = Vec::new();
+
+        for (rr, key) in rrs.iter_mut().zip(&keys) {
+            match rr.start_round(&[6u8; 32], epoch, &all, key).decision {
+                Some(Decision::Produce { proof, delay }) => {
+                    assert_eq!(delay, Duration::ZERO);
+                    blocks.push(Block::build(&[6u8; 32], epoch, now(), proof, key, Vec::new()));
+                }
+                Some(Decision::Wait { .. }) => {}
+                None => panic!("the turn is decided right away"),
+            }
+        }
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].header.author, producer);
+        assert!(blocks[0].validate());
+
+        // the nodes check the turn against the nodes they played the round with
+        assert!(rrs.iter().all(|rr| rr.validate(&blocks[0], None)));
+    }
+
+    // a node out of turn, checked by a node which played the round and by one which did not
+    let other = keys.iter().find(|k| Some(&k.public_key) != RoundRobin::producer(&all, 1).as_ref()).unwrap();
+    let block = Block::build(&[6u8; 32], 1, now(), Proof::Elected, other, Vec::new());
+    assert!(block.validate());
+
+    let mut played = RoundRobin::new();
+    played.start_round(&[6u8; 32], 1, &all, &keys[0]);
+    assert!(!played.validate(&block, None));
+    assert!(!RoundRobin::new().validate(&block, Some(&parent(&keys))));
+
+    // a block of another consensus
+    let producer = keys.iter().find(|k| Some(&k.public_key) == RoundRobin::producer(&all, 1).as_ref()).unwrap();
+    let proof = match HighlanderConsensus::new(Duration::from_secs(10), None).start_round(&[6u8; 32], 1, &nodes(std::slice::from_ref(producer)), producer).decision {
+        Some(Decision::Produce { proof, .. }) => proof,
+        _ => panic!("a single node wins its tournament"),
+    };
+    let block = Block::build(&[6u8; 32], 1, now(), proof, producer, Vec::new());
+    assert!(!RoundRobin::new().validate(&block, Some(&parent(&keys))));
+}
+
+#[test]
+fn only_the_authority_produces() {
+    let authority = Key::new();
+    let other = Key::new();
+    let all = nodes(&[Key::new()]);
+
+    let mut consensus = Authority::new(authority.public_key.clone());
+    let decision = consensus.start_round(&[7u8; 32], 1, &all, &other).decision;
+    assert!(matches!(decision, Some(Decision::Wait { deadline }) if deadline == block_deadline(0)));
+
+    let proof = match consensus.start_round(&[7u8; 32], 1, &all, &authority).decision {
+        Some(Decision::Produce { proof, .. }) => proof,
+        _ => panic!("the authority produces every block"),
+    };
+    let block = Block::build(&[7u8; 32], 1, now(), proof, &authority, Vec::new());
+    assert!(block.validate());
+    assert!(consensus.validate(&block, None));
+
+    let block = Block::build(&[7u8; 32], 1, now(), Proof::Elected, &other, Vec::new());
+    assert!(block.validate());
+    assert!(!consensus.validate(&block, None));
+}
+
+#[test]
+fn only_the_committee_plays() {
+    let keys: Vec<Key> = (0..6).map(|_| Key::new()).collect();
+    let all = nodes(&keys);
+    let mut hls: Vec<Box<dyn Consensus>> = keys.iter()
+        .map(|_| Box::new(HighlanderConsensus::new(Duration::from_secs(10), Some(3))) as Box<dyn Consensus>)
+        .collect();
+
+    let steps: Vec<Step> = hls.iter_mut().zip(&keys)
+        .map(|(hl, key)| hl.start_round(&[9u8; 32], 4, &all, key))
+        .collect();
+    assert_eq!(steps.iter().filter(|s| !s.broadcast.is_empty()).count(), 3);
+
+    // the other nodes follow the tournament, but do not produce
+    let decisions = exchange(&mut hls, &keys, steps);
+    let mut delays: Vec<Duration> = decisions.iter().filter_map(|d| match d {
+        Some(Decision::Produce { proof, delay }) => {
+            assert_eq!(proof.game().unwrap().population.len(), keys.len());
+            assert!(proof.game().unwrap().verify(Some(3)).is_ok());
+            Some(*delay)
+        }
+        Some(Decision::Wait { deadline }) => {
+            // the waiting nodes give every producer its turn
+            assert_eq!(*deadline, block_deadline(2));
+            None
+        }
+        None => panic!("every node follows the tournament"),
+    }).collect();
+    delays.sort();
+    assert_eq!(delays.len(), 3);
+    assert_eq!(delays[0], Duration::ZERO);
+
+    let (winner, proof) = decisions.into_iter().zip(&keys).find_map(|(d, key)| match d {
+        Some(Decision::Produce { proof, delay }) if delay.is_zero() => Some((key, proof)),
+        _ => None,
+    }).unwrap();
+    let block = Block::build(&[9u8; 32], 4, now(), proof.clone(), winner, Vec::new());
+    let observer = HighlanderConsensus::new(Duration::from_secs(10), Some(3));
+    assert!(hls.iter().all(|hl| hl.validate(&block, None)));
+    assert!(observer.validate(&block, Some(&parent(&keys))));
+
+    // a result which claims that all nodes played, so the committee is not checked by the result itself
+    let mut game = proof.game().unwrap().clone();
+    game.population.clear();
+    game.sign = winner.sign(&game.hash()).unwrap();
+    assert!(game.verify(None).is_ok());
+    assert!(game.verify(Some(3)).is_ok());
+    let block = Block::build(&[9u8; 32], 4, now(), Proof::Game(game), winner, Vec::new());
+    assert!(hls.iter().all(|hl| !hl.validate(&block, None)));
+    assert!(!observer.validate(&block, Some(&parent(&keys))));
+}
+
+#[test]
+fn highlander_waits_without_players() {
+    let keys: Vec<Key> = (0..4).map(|_| Key::new()).collect();
+    let all = nodes(&keys);
+
+    // a node outside the committee, whose committee stays silent
+    let (outsider, mut hl) = keys.iter().find_map(|key| {
+        let mut hl = HighlanderConsensus::new(Duration::from_secs(10), Some(1));
+        let step = hl.start_round(&[6u8; 32], 3, &all, key);
+        step.broadcast.is_empty().then_some((key, hl))
+    }).unwrap();
+
+    let mut decision = None;
+    for _ in 0..2 {
+        decision = decision.or(hl.timeout(outsider).decision);
+    }
+    assert!(matches!(decision, Some(Decision::Wait { deadline }) if deadline == block_deadline(0)));
+}
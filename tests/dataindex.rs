@@ -0,0 +1,122 @@
+use mccloud::{
+    blockchain::{Blockchain, Data},
+    config::Retention,
+    key::Key,
+};
+
+mod common;
+use common::{save_remove, generate};
+
+fn hashes(items: Vec<Data>) -> Vec<Vec<u8>> {
+    items.iter().map(Data::hash).collect()
+}
+
+#[test]
+fn find_by_hash_and_author() {
+    save_remove("data/dataindex00");
+
+    let ka = Key::new();
+    let kb = Key::new();
+    let mut bc = Blockchain::new("data/dataindex00");
+
+    let a1 = Data::build(&ka, b"a1".to_vec());
+    let b1 = Data::build(&kb, b"b1".to_vec());
+    bc.add_to_cache(a1.clone());
+    bc.add_to_cache(b1.clone());
+    let block1 = generate(&mut bc, &ka);
+
+    // kept up to date as blocks connect
+    let (location, data) = bc.find_data(&b1.hash()).unwrap().unwrap();
+    assert_eq!(data.hash(), b1.hash());
+    assert_eq!(location.block, block1.header.hash);
+    assert_eq!(location.height, 1);
+    assert_eq!(location.position, 1);
+
+    let a2 = Data::build(&ka, b"a2".to_vec());
+    bc.add_to_cache(a2.clone());
+    let block2 = generate(&mut bc, &ka);
+
+    let (location, _) = bc.find_data(&a2.hash()).unwrap().unwrap();
+    assert_eq!(location.block, block2.header.hash);
+    assert_eq!(hashes(bc.data_by_author(&ka.public_key).unwrap()), vec![a1.hash(), a2.hash()]);
+    assert_eq!(hashes(bc.data_by_author(&kb.public_key).unwrap()), vec![b1.hash()]);
+
+    let unknown = Data::build(&kb, b"unknown".to_vec());
+    assert!(bc.find_data(&unknown.hash()).unwrap().is_none());
+    assert!(bc.data_by_author(&Key::new().public_key).unwrap().is_empty());
+
+    // a reopened chain keeps its index
+    bc.flush();
+    drop(bc);
+    let bc = Blockchain::new("data/dataindex00");
+    assert_eq!(bc.data_by_author(&ka.public_key).unwrap().len(), 2);
+    assert!(bc.prove(&unknown.hash()).is_none());
+    assert!(bc.prove(&block2.data[0].hash()).is_some());
+    drop(bc);
+
+    // and builds it again, if it is lost
+    std::fs::remove_file("data/dataindex00/bc.dtp").unwrap();
+    let bc = Blockchain::new("data/dataindex00");
+    assert_eq!(hashes(bc.data_by_author(&ka.public_key).unwrap()), vec![a1.hash(), a2.hash()]);
+    assert_eq!(bc.find_data(&b1.hash()).unwrap().unwrap().0.block, block1.header.hash);
+}
+
+#[test]
+fn follow_reorganization() {
+    save_remove("data/dataindex01");
+    save_remove("data/dataindex02");
+
+    let ka = Key::new();
+    let kb = Key::new();
+
+    let mut bca = Blockchain::new("data/dataindex01");
+    let da = Data::build(&ka, b"from a".to_vec());
+    bca.add_to_cache(da.clone());
+    generate(&mut bca, &ka);
+    assert!(bca.find_data(&da.hash()).unwrap().is_some());
+
+    let mut bcb = Blockchain::new("data/dataindex02");
+    let db = Data::build(&kb, b"from b".to_vec());
+    bcb.add_to_cache(db.clone());
+    let b1 = generate(&mut bcb, &kb);
+    let b2 = generate(&mut bcb, &kb);
+
+    bca.add_new_block(b1);
+    bca.add_new_block(b2.clone());
+    assert_eq!(bca.highest_block(), (b2.header.hash, 2));
+
+    // the data of the rolled back block is no longer on the main chain
+    assert!(bca.find_data(&da.hash()).unwrap().is_none());
+    assert!(bca.data_by_author(&ka.public_key).unwrap().is_empty());
+    let (location, _) = bca.find_data(&db.hash()).unwrap().unwrap();
+    assert_eq!(location.height, 1);
+
+    // until a member of the chain includes it again
+    let a3 = generate(&mut bca, &kb);
+    let (location, _) = bca.find_data(&da.hash()).unwrap().unwrap();
+    assert_eq!(location.block, a3.header.hash);
+
+    bca.rebuild_data_index().unwrap();
+    assert_eq!(bca.find_data(&da.hash()).unwrap().unwrap().0, location);
+    assert_eq!(hashes(bca.data_by_author(&kb.public_key).unwrap()), vec![db.hash()]);
+}
+
+#[test]
+fn forget_pruned_data() {
+    save_remove("data/dataindex03");
+
+    let key = Key::new();
+    let mut bc = Blockchain::new("data/dataindex03");
+    let mut items = Vec::new();
+    for i in 0..4 {
+        let data = Data::build(&key, vec![i]);
+        bc.add_to_cache(data.clone());
+        generate(&mut bc, &key);
+        items.push(data);
+    }
+
+    bc.prune(&Retention::Blocks(2)).unwrap();
+    assert!(bc.find_data(&items[1].hash()).unwrap().is_none());
+    assert!(bc.find_data(&items[2].hash()).unwrap().is_some());
+    assert_eq!(hashes(bc.data_by_author(&key.public_key).unwrap()), vec![items[2].hash(), items[3].hash()]);
+}
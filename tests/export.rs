@@ -0,0 +1,103 @@
+use std::io::Cursor;
+
+use mccloud::{
+    blockchain::{Block, Blockchain, Proof, export, store},
+    config::{Config, ConsensusKind, StoreKind},
+    consensus::Authority,
+    key::Key,
+};
+
+mod common;
+use common::{save_remove, play, generate};
+
+fn memory() -> Blockchain {
+    Blockchain::with_store(store::open(&StoreKind::Memory, "data/export").unwrap()).unwrap()
+}
+
+#[test]
+fn export_and_import() {
+    save_remove("data/export00");
+
+    let key = Key::new();
+    let mut bca = Blockchain::new("data/export00");
+    for _ in 0..4 {
+        generate(&mut bca, &key);
+    }
+
+    let mut file = Vec::new();
+    assert_eq!(export::export(&bca, &mut file).unwrap(), 4);
+
+    let mut bcb = memory();
+    assert_eq!(export::import(&mut bcb, &mut Cursor::new(&file)).unwrap(), 4);
+    assert_eq!(bcb.highest_block(), bca.highest_block());
+
+    // a second import of the same blocks changes nothing
+    assert_eq!(export::import(&mut bcb, &mut Cursor::new(&file)).unwrap(), 4);
+    assert_eq!(bcb.highest_block(), bca.highest_block());
+
+    // a cut export is rejected
+    let mut bcc = memory();
+    assert!(export::import(&mut bcc, &mut Cursor::new(&file[..file.len() - 10])).is_err());
+
+    // so is a record longer than any block, before it is read
+    let mut oversized = file.clone();
+    let header = u32::from_be_bytes(oversized[..4].try_into().unwrap()) as usize;
+    oversized[4 + header..8 + header].copy_from_slice(&u32::MAX.to_be_bytes());
+    let err = export::import(&mut memory(), &mut Cursor::new(&oversized)).unwrap_err();
+    assert!(err.to_string().contains("larger than a block"));
+}
+
+#[test]
+fn reject_invalid_blocks() {
+    let key = Key::new();
+    let mut bc = memory();
+    let b1 = generate(&mut bc, &key);
+
+    let mut tampered = b1.clone();
+    tampered.header.timestamp += 1;
+    assert!(memory().import_block(tampered).is_err());
+
+    let orphan = Block::build(&[1u8; 32], 2, b1.header.timestamp, play(&key, &[1u8; 32], 2), &key, Vec::new());
+    assert!(memory().import_block(orphan).is_err());
+
+    let wrong_height = Block::build(&b1.header.hash, 3, b1.header.timestamp, play(&key, &b1.header.hash, 3), &key, Vec::new());
+    assert!(bc.import_block(wrong_height).is_err());
+
+    let mut foreign = Vec::new();
+    foreign.extend(4u32.to_be_bytes());
+    foreign.extend(b"junk");
+    assert!(export::import(&mut memory(), &mut Cursor::new(&foreign)).is_err());
+}
+
+#[test]
+fn import_checks_the_consensus() {
+    let authority = Key::new();
+    let mut bca = memory();
+    bca.set_consensus(Box::new(Authority::new(authority.public_key.clone())));
+    for _ in 0..3 {
+        bca.generate_new_block(Proof::Elected, &authority).unwrap();
+    }
+    assert!(bca.generate_new_block(Proof::Elected, &Key::new()).is_err());
+
+    let mut file = Vec::new();
+    export::export(&bca, &mut file).unwrap();
+
+    let mut bcb = memory();
+    bcb.set_consensus(Box::new(Authority::new(authority.public_key.clone())));
+    assert_eq!(export::import(&mut bcb, &mut Cursor::new(&file)).unwrap(), 3);
+    assert_eq!(bcb.highest_block(), bca.highest_block());
+
+    // the blocks of an authority are not decided by a tournament
+    let mut bcc = memory();
+    assert!(export::import(&mut bcc, &mut Cursor::new(&file)).is_err());
+    assert_eq!(bcc.highest_block().1, 0);
+
+    // a chain opened from a config checks the blocks with the consensus of the config
+    let config = Config {
+        store: StoreKind::Memory,
+        consensus: ConsensusKind::Authority(hex::encode(Key::new().public_key)),
+        ..Default::default()
+    };
+    let mut bcd = Blockchain::open(&config).unwrap();
+    assert!(export::import(&mut bcd, &mut Cursor::new(&file)).is_err());
+}
@@ -0,0 +1,228 @@
+use mccloud::{
+    blockchain::{Blockchain, Block, Data, Proof, block::{now, FALLBACK_DELAY, MAX_FUTURE_DRIFT}, orphans::OrphanPool},
+    highlander::Highlander,
+    key::Key,
+};
+
+mod common;
+use common::{save_remove, play, generate};
+
+#[test]
+fn reorganize_to_longer_branch() {
+    save_remove("data/fork00");
+    save_remove("data/fork01");
+
+    let ka = Key::new();
+    let kb = Key::new();
+
+    let mut bca = Blockchain::new("data/fork00");
+    let da = Data::build(&ka, b"from a".to_vec());
+    bca.add_to_cache(da.clone());
+    let a1 = generate(&mut bca, &ka);
+    assert_eq!(bca.highest_block(), (a1.header.hash.clone(), 1));
+
+    let mut bcb = Blockchain::new("data/fork01");
+    let db = Data::build(&kb, b"from b".to_vec());
+    bcb.add_to_cache(db.clone());
+    let b1 = generate(&mut bcb, &kb);
+    let b2 = generate(&mut bcb, &kb);
+
+    // the child arrives before its parent
+    bca.add_new_block(b2.clone());
+    assert_eq!(bca.highest_block(), (a1.header.hash.clone(), 1));
+
+    bca.add_new_block(b1);
+    assert_eq!(bca.highest_block(), (b2.header.hash.clone(), 2));
+
+    // the data of the rolled back block is pending again, the data of the new branch is not
+    let a3 = generate(&mut bca, &kb);
+    let hashes: Vec<Vec<u8>> = a3.data.iter().map(Data::hash).collect();
+    assert_eq!(hashes, vec![da.hash()]);
+    assert_eq!(a3.header.parent, b2.header.hash);
+
+    bca.flush();
+    let bca = Blockchain::new("data/fork00");
+    assert_eq!(bca.highest_block(), (a3.header.hash, 3));
+}
+
+#[test]
+fn orphan_pool_is_bounded() {
+    let key = Key::new();
+    let orphan = |parent: &[u8]| Block::build(parent, 2, now(), play(&key, parent, 2), &key, Vec::new());
+    let (a, b, c) = (orphan(&[1u8; 32]), orphan(&[2u8; 32]), orphan(&[2u8; 32]));
+
+    let mut pool = OrphanPool::new(2, 60_000);
+    assert!(pool.insert(a.clone()));
+    assert!(!pool.insert(a.clone()));
+    assert!(pool.insert(b.clone()));
+    assert_eq!(pool.len(), 2);
+
+    // the block received first makes room
+    assert!(pool.insert(c.clone()));
+    assert_eq!(pool.len(), 2);
+    assert!(!pool.contains(&a.header.hash));
+    assert!(pool.take_children(&[1u8; 32]).is_empty());
+
+    let children: Vec<Vec<u8>> = pool.take_children(&[2u8; 32]).into_iter().map(|b| b.header.hash).collect();
+    assert_eq!(children, vec![b.header.hash.clone(), c.header.hash]);
+    assert!(pool.is_empty());
+
+    // old blocks expire
+    let mut pool = OrphanPool::new(2, 1);
+    pool.insert(a);
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    pool.insert(b.clone());
+    assert_eq!(pool.len(), 1);
+    assert!(pool.contains(&b.header.hash));
+}
+
+#[test]
+fn equal_length_prefers_lower_hash() {
+    save_remove("data/fork02");
+    save_remove("data/fork03");
+
+    let ka = Key::new();
+    let kb = Key::new();
+
+    let mut bca = Blockchain::new("data/fork02");
+    let a1 = generate(&mut bca, &ka);
+
+    let mut bcb = Blockchain::new("data/fork03");
+    let b1 = generate(&mut bcb, &kb);
+
+    bca.add_new_block(b1.clone());
+    bcb.add_new_block(a1.clone());
+
+    let lowest = if a1.header.hash < b1.header.hash { a1.header.hash } else { b1.header.hash };
+    assert_eq!(bca.highest_block(), (lowest.clone(), 1));
+    assert_eq!(bcb.highest_block(), (lowest, 1));
+}
+
+#[test]
+fn reject_bad_height_and_timestamp() {
+    save_remove("data/fork04");
+
+    let key = Key::new();
+    let mut bc = Blockchain::new("data/fork04");
+    let b1 = generate(&mut bc, &key);
+
+    let skipped = Block::build(&b1.header.hash, 3, now(), play(&key, &b1.header.hash, 3), &key, Vec::new());
+    bc.add_new_block(skipped);
+    assert_eq!(bc.highest_block(), (b1.header.hash.clone(), 1));
+
+    let past = Block::build(&b1.header.hash, 2, b1.header.timestamp - 1, play(&key, &b1.header.hash, 2), &key, Vec::new());
+    bc.add_new_block(past);
+    assert_eq!(bc.highest_block(), (b1.header.hash.clone(), 1));
+
+    let future = Block::build(&b1.header.hash, 2, now() + 2 * MAX_FUTURE_DRIFT, play(&key, &b1.header.hash, 2), &key, Vec::new());
+    bc.add_new_block(future);
+    assert_eq!(bc.highest_block(), (b1.header.hash.clone(), 1));
+
+    let b2 = Block::build(&b1.header.hash, 2, now(), play(&key, &b1.header.hash, 2), &key, Vec::new());
+    bc.add_new_block(b2.clone());
+    assert_eq!(bc.highest_block(), (b2.header.hash, 2));
+}
+
+///
+/// Plays the tournament of `ka` and `kb` for the block at height `epoch` on `parent`, returns the
+/// winner and the fallback producer with their game results.
+///
+fn duel<'a>(ka: &'a Key, kb: &'a Key, parent: &[u8], epoch: usize) -> ((&'a Key, Proof), (&'a Key, Proof)) {
+    let mut hl = Highlander::new();
+    hl.set_epoch(parent, epoch);
+    hl.populate_roster([ka.public_key.clone(), kb.public_key.clone()].iter());
+    for key in [ka, kb] {
+        let game = hl.create_game(key);
+        hl.add_commitment(game.commitment(key));
+        hl.add_game(game).unwrap();
+    }
+    let result = hl.evaluate(ka).unwrap();
+    let (winner, fallback) = if result.winner == ka.public_key { (ka, kb) } else { (kb, ka) };
+
+    let mut second = result.clone();
+    second.author = fallback.public_key.clone();
+    second.sign = fallback.sign(&second.hash()).unwrap();
+    assert_eq!(second.rank(&fallback.public_key), Some(1));
+
+    let mut first = result;
+    first.author = winner.public_key.clone();
+    first.sign = winner.sign(&first.hash()).unwrap();
+
+    ((winner, Proof::Game(first)), (fallback, Proof::Game(second)))
+}
+
+///
+/// Adds the first block at `timestamp`, which names `ka` and `kb` as the members.
+///
+fn first_block(bc: &mut Blockchain, ka: &Key, kb: &Key, timestamp: u64) -> Block {
+    let ((winner, proof), _) = duel(ka, kb, &[], 1);
+    let b1 = Block::build(&[], 1, timestamp, proof, winner, Vec::new());
+    bc.add_new_block(b1.clone());
+    b1
+}
+
+#[test]
+fn fallback_producer_waits_for_its_deadline() {
+    save_remove("data/fork05");
+
+    let ka = Key::new();
+    let kb = Key::new();
+    let mut bc = Blockchain::new("data/fork05");
+    let b1 = first_block(&mut bc, &ka, &kb, now() - 2 * FALLBACK_DELAY);
+    let (_, (fallback, result)) = duel(&ka, &kb, &b1.header.hash, 2);
+
+    // the finalist may not produce the block before its deadline
+    let early = Block::build(&b1.header.hash, 2, b1.header.timestamp + FALLBACK_DELAY - 1, result.clone(), fallback, Vec::new());
+    bc.add_new_block(early);
+    assert_eq!(bc.highest_block(), (b1.header.hash.clone(), 1));
+
+    let late = Block::build(&b1.header.hash, 2, b1.header.timestamp + FALLBACK_DELAY, result, fallback, Vec::new());
+    bc.add_new_block(late.clone());
+    assert_eq!(bc.highest_block(), (late.header.hash, 2));
+}
+
+#[test]
+fn fallback_producer_cannot_skip_its_deadline() {
+    save_remove("data/fork06");
+
+    let ka = Key::new();
+    let kb = Key::new();
+    let mut bc = Blockchain::new("data/fork06");
+    let b1 = first_block(&mut bc, &ka, &kb, now());
+    let (_, (fallback, result)) = duel(&ka, &kb, &b1.header.hash, 2);
+
+    // the deadline lies beyond the drift of the clocks
+    const { assert!(MAX_FUTURE_DRIFT < FALLBACK_DELAY) };
+    assert!(bc.generate_new_block(result.clone(), fallback).is_err());
+    let ahead = Block::build(&b1.header.hash, 2, b1.header.timestamp + FALLBACK_DELAY, result, fallback, Vec::new());
+    bc.add_new_block(ahead);
+    assert_eq!(bc.highest_block(), (b1.header.hash, 1));
+}
+
+#[test]
+fn prefer_the_winner_at_equal_height() {
+    save_remove("data/fork07");
+    save_remove("data/fork08");
+
+    let ka = Key::new();
+    let kb = Key::new();
+    let mut bca = Blockchain::new("data/fork07");
+    let mut bcb = Blockchain::new("data/fork08");
+    let b1 = first_block(&mut bca, &ka, &kb, now() - 2 * FALLBACK_DELAY);
+    bcb.add_new_block(b1.clone());
+
+    let ((winner, first), (fallback, second)) = duel(&ka, &kb, &b1.header.hash, 2);
+    let won = Block::build(&b1.header.hash, 2, b1.header.timestamp + 1, first, winner, Vec::new());
+    let late = Block::build(&b1.header.hash, 2, b1.header.timestamp + FALLBACK_DELAY, second, fallback, Vec::new());
+    assert_eq!((won.header.rank, late.header.rank), (0, 1));
+
+    // the block of the winner is preferred in any order, whatever the hashes
+    bca.add_new_block(late.clone());
+    assert_eq!(bca.highest_block(), (late.header.hash.clone(), 2));
+    bca.add_new_block(won.clone());
+    assert_eq!(bca.highest_block(), (won.header.hash.clone(), 2));
+
+    bcb.add_new_block(won.clone());
+    bcb.add_new_block(late);
+    assert_eq!(bcb.highest_block(), (won.header.hash, 2));
+}
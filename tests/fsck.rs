@@ -0,0 +1,113 @@
+use std::{fs::OpenOptions, io::{Seek, SeekFrom, Write}, path::Path};
+
+use mccloud::{
+    blockchain::{Block, Blockchain, fsck, journal},
+    key::Key,
+};
+
+mod common;
+use common::{save_remove, generate};
+
+#[test]
+fn check_and_repair() {
+    save_remove("data/fsck00");
+
+    let key = Key::new();
+    let mut bc = Blockchain::new("data/fsck00");
+    for _ in 0..3 {
+        generate(&mut bc, &key);
+    }
+    let highest = bc.highest_block();
+    drop(bc);
+
+    let folder = Path::new("data/fsck00");
+    let report = fsck::check(folder).unwrap();
+    assert!(report.is_clean());
+    assert_eq!(report.blocks, 3);
+    assert_eq!((report.tip.clone(), report.height), highest);
+    assert!(report.stale.is_empty());
+
+    // a torn tail and an index which claims more than the data file holds
+    let db_len = std::fs::metadata("data/fsck00/bc.db").unwrap().len();
+    OpenOptions::new().write(true).open("data/fsck00/bc.db").unwrap().set_len(db_len - 5).unwrap();
+    OpenOptions::new().append(true).open("data/fsck00/bc.idx").unwrap().write_all(&[0, 0, 1]).unwrap();
+
+    let report = fsck::check(folder).unwrap();
+    assert!(!report.is_clean());
+    assert_eq!(report.blocks, 2);
+    assert!(report.torn.is_some());
+    assert!(report.torn_index.is_some());
+    assert_eq!(report.past_eof.len(), 1);
+
+    // the data file is left as it is, only the index is written again
+    assert!(fsck::rebuild_index(folder).unwrap().is_empty());
+    let report = fsck::check(folder).unwrap();
+    assert!(report.torn.is_some());
+    assert!(report.torn_index.is_none());
+    assert!(report.past_eof.is_empty());
+    assert_eq!(report.height, 2);
+    assert_eq!(std::fs::metadata("data/fsck00/bc.db").unwrap().len(), db_len - 5);
+}
+
+#[test]
+fn rebuild_skips_invalid_records() {
+    save_remove("data/fsck03");
+
+    let key = Key::new();
+    let mut bc = Blockchain::new("data/fsck03");
+    let blocks: Vec<Block> = (0..3).map(|_| generate(&mut bc, &key)).collect();
+    drop(bc);
+
+    // a broken record in the middle of the data file
+    let folder = Path::new("data/fsck03");
+    let records = journal::read_from(&folder.join("bc.db"), 0).unwrap().0;
+    let (pos, _) = records[1];
+    let mut file = OpenOptions::new().write(true).open(folder.join("bc.db")).unwrap();
+    file.seek(SeekFrom::Start(pos + 10)).unwrap();
+    file.write_all(b"broken").unwrap();
+    drop(file);
+    let db_len = std::fs::metadata(folder.join("bc.db")).unwrap().len();
+
+    assert_eq!(fsck::rebuild_index(folder).unwrap(), vec![pos]);
+    let report = fsck::check(folder).unwrap();
+    assert_eq!(report.invalid.len(), 1);
+    assert_eq!(report.blocks, 2);
+    assert_eq!(report.tip, blocks[0].header.hash);
+
+    // opening the store does not cut the data file at the broken record
+    let bc = Blockchain::new("data/fsck03");
+    assert_eq!(bc.highest_block(), (blocks[0].header.hash.clone(), 1));
+    drop(bc);
+    assert_eq!(std::fs::metadata(folder.join("bc.db")).unwrap().len(), db_len);
+}
+
+#[test]
+fn report_orphans() {
+    save_remove("data/fsck01");
+    save_remove("data/fsck02");
+
+    let key = Key::new();
+    let mut bca = Blockchain::new("data/fsck01");
+    let a1 = generate(&mut bca, &key);
+    generate(&mut bca, &key);
+    drop(bca);
+
+    // a block stored without its parent
+    let mut bcb = Blockchain::new("data/fsck02");
+    bcb.import_block(a1).unwrap();
+    drop(bcb);
+    let mut bcc = Blockchain::new("data/fsck01");
+    generate(&mut bcc, &key);
+    drop(bcc);
+
+    let records = journal::read_from(Path::new("data/fsck01/bc.db"), 0).unwrap().0;
+    let mut file = OpenOptions::new().append(true).open("data/fsck02/bc.db").unwrap();
+    let (_, last) = &records[2];
+    file.write_all(&(last.len() as u32).to_be_bytes()).unwrap();
+    file.write_all(last).unwrap();
+    drop(file);
+
+    let report = fsck::check(Path::new("data/fsck02")).unwrap();
+    assert_eq!(report.orphans.len(), 1);
+    assert!(!report.is_clean());
+}
@@ -0,0 +1,106 @@
+use std::{path::Path, time::Duration};
+
+use mccloud::{
+    blockchain::{Block, Blockchain, Data, block::now, genesis},
+    config::{Config, ClientConfig},
+    key::Key,
+    network::{peer::Peer, handler::daemon::DaemonHandler},
+};
+
+mod common;
+use common::{save_remove, play, generate};
+
+#[test]
+fn chain_starts_with_genesis() {
+    save_remove("data/genesis00");
+
+    let key = Key::new();
+    let block = genesis::create(&key, vec![Data::build(&key, b"hello".to_vec())]);
+    let path = Path::new("data/genesis00/genesis");
+    genesis::save(&block, path).unwrap();
+    assert!(genesis::save(&block, path).is_err());
+    assert_eq!(genesis::load(path).unwrap().header.hash, block.header.hash);
+
+    let mut bc = Blockchain::new("data/genesis00/chain");
+    bc.set_genesis(&block).unwrap();
+    assert_eq!(bc.highest_block(), (block.header.hash.clone(), 1));
+    assert_eq!(bc.network(), block.header.hash.as_slice());
+
+    let b2 = generate(&mut bc, &key);
+    assert_eq!(b2.header.parent, block.header.hash);
+    drop(bc);
+
+    // the same chain opens again with its genesis block, but not with another one
+    let mut bc = Blockchain::new("data/genesis00/chain");
+    bc.set_genesis(&block).unwrap();
+    assert_eq!(bc.highest_block(), (b2.header.hash.clone(), 2));
+
+    let other = genesis::create(&key, Vec::new());
+    let mut bc = Blockchain::new("data/genesis00/chain");
+    assert!(bc.set_genesis(&other).is_err());
+}
+
+#[test]
+fn reject_other_first_block() {
+    save_remove("data/genesis01");
+
+    let key = Key::new();
+    let mut bc = Blockchain::new("data/genesis01");
+    bc.set_genesis(&genesis::create(&key, Vec::new())).unwrap();
+    let highest = bc.highest_block();
+
+    let first = Block::build(&[], 1, now(), play(&key, &[], 1), &key, Vec::new());
+    let second = Block::build(&first.header.hash, 2, now(), play(&key, &first.header.hash, 2), &key, Vec::new());
+    assert!(bc.import_block(first.clone()).is_err());
+
+    bc.add_new_block(first);
+    bc.add_new_block(second);
+    assert_eq!(bc.highest_block(), highest);
+}
+
+#[tokio::test]
+async fn refuse_other_network() {
+    save_remove("data/genesis02");
+
+    let key = Key::new();
+    genesis::save(&genesis::create(&key, Vec::new()), Path::new("data/genesis02/a")).unwrap();
+    genesis::save(&genesis::create(&key, Vec::new()), Path::new("data/genesis02/b")).unwrap();
+
+    let config = Config {
+        port: 39193,
+        folder: "data/genesis02/peer00".into(),
+        genesis: Some("data/genesis02/a".into()),
+        ..Default::default()
+    };
+    let peer00 = Peer::<DaemonHandler>::new(config);
+    let p00 = peer00.clone();
+
+    let config = Config {
+        port: 39194,
+        folder: "data/genesis02/peer01".into(),
+        genesis: Some("data/genesis02/b".into()),
+        clients: vec![
+            ClientConfig {host: "127.0.0.1".into(), port: 39193, reconnect: false}
+        ],
+        ..Default::default()
+    };
+    let peer01 = Peer::<DaemonHandler>::new(config);
+    let p01 = peer01.clone();
+    assert_ne!(peer00.network, peer01.network);
+
+    tokio::spawn(async move {
+        p00.listen().await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    tokio::spawn(async move {
+        p01.listen().await.unwrap();
+    });
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    assert_eq!(peer00.all_known.lock().await.len(), 1);
+    assert_eq!(peer01.all_known.lock().await.len(), 1);
+
+    peer01.shutdown();
+    peer00.shutdown();
+}
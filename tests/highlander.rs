@@ -0,0 +1,474 @@
+use std::time::Duration;
+
+use mccloud::{
+    blockchain::{Block, Proof, block::{now, FALLBACK_DELAY}},
+    consensus::{Consensus, HighlanderConsensus, highlander::validate_rank},
+    highlander::{committee, Game, GameError, GameResult, Highlander},
+    key::Key,
+};
+
+///
+/// Changes a field of the game, the way a malicious node would.
+///
+fn tamper(game: &Game, field: &str, value: impl serde::Serialize) -> Game {
+    let mut json = serde_json::to_value(game).unwrap();
+    json[field] = serde_json::to_value(value).unwrap();
+    serde_json::from_value(json).unwrap()
+}
+
+fn tournament(keys: &[Key], author: &Key) -> GameResult {
+    let mut hl = Highlander::new();
+    hl.set_epoch(&[], 1);
+    hl.populate_roster(keys.iter().map(|k| &k.public_key));
+    let games: Vec<Game> = keys.iter().map(|k| hl.create_game(k)).collect();
+    for (game, key) in games.iter().zip(keys) {
+        assert!(hl.add_commitment(game.commitment(key)));
+    }
+    for game in games {
+        assert!(hl.add_game(game).is_ok());
+    }
+
+    hl.evaluate(author).unwrap()
+}
+
+#[test]
+fn levels_of_game_result() {
+    let keys: Vec<Key> = (0..4).map(|_| Key::new()).collect();
+    let mut hl = Highlander::new();
+    hl.populate_roster(keys.iter().map(|k| &k.public_key));
+    for key in &keys {
+        let game = hl.create_game(key);
+        assert!(hl.add_commitment(game.commitment(key)));
+        assert!(hl.add_game(game).is_ok());
+    }
+
+    let result = hl.evaluate(&keys[0]).unwrap();
+    let levels = result.levels();
+    assert_eq!(levels.iter().map(|l| l.len()).collect::<Vec<_>>(), vec![4, 2, 1]);
+    assert_eq!(levels[2][0].as_ref(), Some(&result.winner));
+
+    // every winner comes from one of the two entries below it
+    for l in 1..levels.len() {
+        for (i, w) in levels[l].iter().enumerate() {
+            assert!(w == &levels[l - 1][i * 2] || w == &levels[l - 1][i * 2 + 1]);
+        }
+    }
+}
+
+#[test]
+fn reveal_after_commitment() {
+    let keys: Vec<Key> = (0..4).map(|_| Key::new()).collect();
+    let mut hl = Highlander::new();
+    hl.set_epoch(&[], 1);
+    hl.populate_roster(keys.iter().map(|k| &k.public_key));
+
+    let games: Vec<_> = keys.iter().map(|k| hl.create_game(k)).collect();
+
+    // a game without commitment is not accepted
+    assert_eq!(hl.add_game(games[0].clone()), Err(GameError::NoCommitment));
+
+    for (game, key) in games.iter().zip(&keys) {
+        assert!(!hl.is_committed());
+        assert!(hl.add_commitment(game.commitment(key)));
+    }
+    assert!(hl.is_committed());
+
+    // a commitment signed by another node is not accepted
+    let other = Key::new();
+    assert!(!hl.add_commitment(games[0].commitment(&other)));
+
+    // after a different game than the committed one no game of its author is taken
+    let cheat = hl.create_game(&keys[1]);
+    assert_eq!(hl.add_game(cheat), Err(GameError::CommitmentMismatch));
+    assert!(hl.excluded().is_empty());
+
+    assert_eq!(hl.add_game(games[1].clone()), Err(GameError::Excluded));
+
+    assert!(hl.add_game(games[0].clone()).is_ok());
+    assert_eq!(hl.add_game(games[0].clone()), Err(GameError::Duplicate));
+    assert!(hl.add_game(games[2].clone()).is_ok());
+    assert!(!hl.is_filled());
+
+    // the nodes without a game are dropped once a majority voted for them
+    let mut missing = vec![keys[1].public_key.clone(), keys[3].public_key.clone()];
+    missing.sort();
+    assert_eq!(hl.vote(&keys[0]).missing(), missing.as_slice());
+    hl.vote(&keys[2]);
+    assert!(hl.excluded().is_empty());
+    hl.vote(&keys[3]);
+    assert_eq!(hl.excluded().len(), 2);
+    assert!(hl.is_filled());
+
+    let result = hl.evaluate(&keys[0]).unwrap();
+    assert_eq!(result.roster.len(), 2);
+    assert!(result.winner == keys[0].public_key || result.winner == keys[2].public_key);
+    assert_eq!(hl.tournament(), 1);
+    assert!(hl.excluded().is_empty());
+}
+
+#[test]
+fn reject_forged_games() {
+    let keys: Vec<Key> = (0..4).map(|_| Key::new()).collect();
+    let mut hl = Highlander::new();
+    hl.populate_roster(keys.iter().map(|k| &k.public_key));
+
+    for key in &keys {
+        let game = hl.create_game(key);
+        assert!(hl.add_commitment(game.commitment(key)));
+    }
+
+    // a game of another node does not get the node excluded
+    let forged = tamper(&hl.create_game(&Key::new()), "author", &keys[0].public_key);
+    assert_eq!(hl.add_game(forged), Err(GameError::InvalidSignature));
+    assert!(hl.excluded().is_empty());
+
+    let stranger = Key::new();
+    assert_eq!(hl.add_game(hl.create_game(&stranger)), Err(GameError::UnknownAuthor));
+
+    let game = hl.create_game(&keys[1]);
+    let changed = tamper(&game, "rounds", [1, 1, 1]);
+    assert_eq!(hl.add_game(changed), Err(GameError::InvalidSignature));
+
+    let short = tamper(&game, "rounds", [1]);
+    let short = tamper(&short, "sign", keys[1].sign(&short.payload()).unwrap());
+    assert_eq!(hl.add_game(short), Err(GameError::RoundCount { expected: 2, actual: 1 }));
+
+    let invalid = tamper(&game, "rounds", [1, 3]);
+    let invalid = tamper(&invalid, "sign", keys[1].sign(&invalid.payload()).unwrap());
+    assert_eq!(hl.add_game(invalid), Err(GameError::InvalidRound { round: 1, value: 3 }));
+    assert!(hl.excluded().is_empty());
+}
+
+#[test]
+fn verify_game_result() {
+    let keys: Vec<Key> = (0..4).map(|_| Key::new()).collect();
+    let mut result = tournament(&keys, &keys[0]);
+    // the result is signed by the node it was evaluated on
+    assert_eq!(result.author, keys[0].public_key);
+    assert!(result.verify(None).is_ok());
+
+    let winner = keys.iter().find(|k| k.public_key == result.winner).unwrap();
+    result.author = winner.public_key.clone();
+    result.sign = winner.sign(&result.hash()).unwrap();
+    assert!(result.verify(None).is_ok());
+
+    let loser = keys.iter().find(|k| k.public_key != result.winner).unwrap();
+    let mut unsigned = result.clone();
+    unsigned.sign = loser.sign(&unsigned.hash()).unwrap();
+    assert!(unsigned.verify(None).is_err());
+
+    let mut changed = result.clone();
+    let id = changed.roster.keys().next().unwrap().clone();
+    changed.roster.get_mut(&id).unwrap()[0] = (changed.roster[&id][0] + 1) % 3;
+    changed.sign = winner.sign(&changed.hash()).unwrap();
+    assert!(changed.verify(None).is_err());
+
+    let mut unknown = result.clone();
+    unknown.signs.remove(&id);
+    unknown.sign = winner.sign(&unknown.hash()).unwrap();
+    assert!(unknown.verify(None).is_err());
+
+    // a made up winner with a matching signature
+    let mut made_up = result.clone();
+    made_up.winner = loser.public_key.clone();
+    made_up.author = loser.public_key.clone();
+    *made_up.tree.last_mut().unwrap() = Some(loser.public_key.clone());
+    made_up.sign = loser.sign(&made_up.hash()).unwrap();
+    assert!(made_up.verify(None).is_err());
+
+    // an author which did not play
+    let outsider = Key::new();
+    let mut foreign = result.clone();
+    foreign.author = outsider.public_key.clone();
+    foreign.sign = outsider.sign(&foreign.hash()).unwrap();
+    assert!(foreign.verify(None).is_err());
+
+    let consensus = HighlanderConsensus::new(Duration::from_secs(10), None);
+    let block = Block::build(&[], 1, now(), Proof::Game(result.clone()), winner, Vec::new());
+    assert!(block.validate() && consensus.validate(&block, None));
+    let block = Block::build(&[], 1, now(), Proof::Game(result), loser, Vec::new());
+    assert!(block.validate() && !consensus.validate(&block, None));
+}
+
+#[test]
+fn reject_games_of_other_tournaments() {
+    let keys: Vec<Key> = (0..2).map(|_| Key::new()).collect();
+    let mut hl = Highlander::new();
+    hl.set_epoch(&[1u8; 32], 5);
+    hl.populate_roster(keys.iter().map(|k| &k.public_key));
+
+    let old: Vec<Game> = keys.iter().map(|k| hl.create_game(k)).collect();
+    for (game, key) in old.iter().zip(&keys) {
+        assert!(hl.add_commitment(game.commitment(key)));
+    }
+    assert!(hl.add_game(old[0].clone()).is_ok());
+    assert!(hl.add_game(old[1].clone()).is_ok());
+    let result = hl.evaluate(&keys[0]).unwrap();
+    assert_eq!((result.parent.as_slice(), result.epoch), (&[1u8; 32][..], 5));
+
+    // the games and commitments of the last tournament are replayed in the next one
+    hl.set_epoch(&[2u8; 32], 6);
+    hl.populate_roster(keys.iter().map(|k| &k.public_key));
+    assert!(!hl.add_commitment(old[1].commitment(&keys[1])));
+    let game = hl.create_game(&keys[1]);
+    assert!(hl.add_commitment(game.commitment(&keys[1])));
+    assert_eq!(hl.add_game(old[1].clone()), Err(GameError::WrongEpoch));
+    assert!(hl.excluded().is_empty());
+
+    // the tournament is part of the signed payload
+    let moved = tamper(&tamper(&old[0], "epoch", 6), "parent", [2u8; 32]);
+    assert_eq!(hl.add_game(moved), Err(GameError::InvalidSignature));
+}
+
+#[test]
+fn brackets_of_every_size() {
+    let keys: Vec<Key> = (0..64).map(|_| Key::new()).collect();
+
+    for count in 1..=keys.len() {
+        let players = &keys[..count];
+        let result = tournament(players, &players[0]);
+        assert!(result.verify(None).is_ok(), "size {}", count);
+
+        let levels = result.levels();
+        let size = count.next_power_of_two();
+        assert_eq!(levels[0].len(), size, "size {}", count);
+        assert_eq!(levels.len(), size.trailing_zeros() as usize + 1);
+        assert_eq!(levels.last().unwrap()[0].as_ref(), Some(&result.winner));
+
+        // every player is placed once and a bye never meets a bye
+        let mut placed: Vec<&Vec<u8>> = levels[0].iter().flatten().collect();
+        placed.sort();
+        placed.dedup();
+        assert_eq!(placed.len(), count);
+        for pair in levels[0].chunks(2) {
+            assert!(pair.iter().any(Option::is_some), "size {}", count);
+        }
+
+        for l in 1..levels.len() {
+            for (i, w) in levels[l].iter().enumerate() {
+                assert!(w.is_some());
+                assert!(w == &levels[l - 1][i * 2] || w == &levels[l - 1][i * 2 + 1]);
+            }
+        }
+    }
+}
+
+#[test]
+fn byes_depend_on_the_seed() {
+    let keys: Vec<Key> = (0..5).map(|_| Key::new()).collect();
+    let byes = |parent: &[u8]| {
+        let mut hl = Highlander::new();
+        hl.set_epoch(parent, 2);
+        hl.populate_roster(keys.iter().map(|k| &k.public_key));
+        let games: Vec<Game> = keys.iter().map(|k| hl.create_game(k)).collect();
+        for (game, key) in games.iter().zip(&keys) {
+            assert!(hl.add_commitment(game.commitment(key)));
+        }
+        for game in games {
+            assert!(hl.add_game(game).is_ok());
+        }
+        let result = hl.evaluate(&keys[0]).unwrap();
+        result.levels()[0].iter().map(Option::is_none).collect::<Vec<bool>>()
+    };
+
+    // 5 players need 3 rounds, 3 of them get a bye in the first round
+    let placements: Vec<Vec<bool>> = (0..16u8).map(|i| byes(&[i; 32])).collect();
+    assert!(placements.iter().all(|p| p.len() == 8 && p.iter().filter(|b| **b).count() == 3));
+    assert!(placements.iter().any(|p| p != &placements[0]));
+}
+
+#[test]
+fn drop_unresponsive_players() {
+    let keys: Vec<Key> = (0..5).map(|_| Key::new()).collect();
+    let mut hl = Highlander::new();
+    hl.set_epoch(&[7u8; 32], 3);
+    hl.populate_roster(keys.iter().map(|k| &k.public_key));
+
+    // the last node crashed before it committed, the one before after it committed
+    let games: Vec<Game> = keys[..4].iter().map(|k| hl.create_game(k)).collect();
+    for (game, key) in games.iter().zip(&keys) {
+        assert!(hl.add_commitment(game.commitment(key)));
+    }
+    assert!(!hl.is_committed());
+    assert_eq!(hl.vote(&keys[0]).missing(), [keys[4].public_key.clone()]);
+    hl.vote(&keys[1]);
+    assert!(!hl.is_committed());
+    hl.vote(&keys[2]);
+    assert!(hl.is_committed());
+
+    // a node votes once per phase
+    let mut other = Highlander::new();
+    other.set_epoch(&[7u8; 32], 3);
+    other.populate_roster(keys.iter().map(|k| &k.public_key));
+    assert!(!hl.add_vote(other.vote(&keys[1])));
+
+    for game in &games[..3] {
+        assert!(hl.add_game(game.clone()).is_ok());
+    }
+    for key in &keys[..3] {
+        assert_eq!(hl.vote(key).missing(), [keys[3].public_key.clone()]);
+    }
+    assert!(hl.is_filled());
+
+    // the remaining nodes play a bracket of three with the rounds of five
+    let result = hl.evaluate(&keys[0]).unwrap();
+    assert!(result.verify(None).is_ok());
+    assert_eq!(result.votes.len(), 6);
+    assert_eq!(result.roster.len(), 3);
+    assert!(result.roster.values().all(|r| r.len() == 3));
+    assert_eq!(result.levels()[0].len(), 4);
+
+    // a new tournament forgets the dropped nodes
+    hl.set_epoch(&result.hash(), 4);
+    assert_eq!(hl.tournament(), 2);
+    assert!(hl.excluded().is_empty());
+}
+
+#[test]
+fn reject_games_of_other_rosters() {
+    let keys: Vec<Key> = (0..3).map(|_| Key::new()).collect();
+
+    // the first node did not hear of the third node yet
+    let mut first = Highlander::new();
+    first.set_epoch(&[3u8; 32], 2);
+    first.populate_roster(keys[..2].iter().map(|k| &k.public_key));
+    let mut second = Highlander::new();
+    second.set_epoch(&[3u8; 32], 2);
+    second.populate_roster(keys.iter().map(|k| &k.public_key));
+    assert_ne!(first.roster_hash(), second.roster_hash());
+
+    let game = first.create_game(&keys[0]);
+    assert!(!second.add_commitment(game.commitment(&keys[0])));
+    assert_eq!(second.add_game(game.clone()), Err(GameError::WrongRoster));
+
+    // the roster hash is part of the signed payload
+    let moved = tamper(&game, "roster", second.roster_hash());
+    assert_eq!(second.add_game(moved), Err(GameError::InvalidSignature));
+
+    // the order the nodes are known in does not matter
+    let mut third = Highlander::new();
+    third.set_epoch(&[3u8; 32], 2);
+    third.populate_roster(keys.iter().rev().map(|k| &k.public_key));
+    assert_eq!(third.roster_hash(), second.roster_hash());
+
+    // the result names the whole roster, also the dropped nodes
+    let games: Vec<Game> = keys.iter().map(|k| third.create_game(k)).collect();
+    for (game, key) in games.iter().zip(&keys).take(2) {
+        assert!(third.add_commitment(game.commitment(key)));
+    }
+    // only the nodes of the roster vote
+    let mut stranger = Highlander::new();
+    stranger.set_epoch(&[3u8; 32], 2);
+    stranger.populate_roster(keys.iter().map(|k| &k.public_key));
+    assert!(!third.add_vote(stranger.vote(&Key::new())));
+    assert!(!third.add_vote(first.vote(&keys[0])));
+    third.vote(&keys[0]);
+    third.vote(&keys[1]);
+    assert!(third.is_committed());
+    assert!(third.add_game(games[0].clone()).is_ok());
+    assert!(third.add_game(games[1].clone()).is_ok());
+    let result = third.evaluate(&keys[0]).unwrap();
+    assert_eq!(result.excluded, vec![keys[2].public_key.clone()]);
+    assert!(result.verify(None).is_ok());
+
+    // a node is only dropped by a majority
+    let mut outvoted = result.clone();
+    outvoted.votes.retain(|vote| vote.author() == &keys[0].public_key);
+    outvoted.sign = keys[0].sign(&outvoted.hash()).unwrap();
+    assert!(outvoted.verify(None).is_err());
+
+    let mut twice = result.clone();
+    twice.votes.push(twice.votes[0].clone());
+    twice.sign = keys[0].sign(&twice.hash()).unwrap();
+    assert!(twice.verify(None).is_err());
+
+    // nobody wins a tournament without players
+    let mut empty = Highlander::new();
+    empty.set_epoch(&[3u8; 32], 2);
+    empty.populate_roster(keys.iter().map(|k| &k.public_key));
+    empty.vote(&keys[0]);
+    empty.vote(&keys[1]);
+    assert!(empty.is_filled());
+    assert!(empty.evaluate(&keys[0]).is_none());
+
+    let mut hidden = result.clone();
+    hidden.excluded.clear();
+    hidden.sign = keys[0].sign(&hidden.hash()).unwrap();
+    assert!(hidden.verify(None).is_err());
+}
+
+#[test]
+fn rank_fallback_producers() {
+    let keys: Vec<Key> = (0..5).map(|_| Key::new()).collect();
+    let mut result = tournament(&keys, &keys[0]);
+
+    // every player is ranked once, from the winner down to the first round
+    let ranking = result.ranking();
+    assert_eq!(ranking.len(), keys.len());
+    assert_eq!(ranking[0], result.winner);
+    let levels = result.levels();
+    let finalist = levels[levels.len() - 2].iter().flatten().find(|id| **id != result.winner).unwrap();
+    assert_eq!(result.rank(finalist), Some(1));
+    for key in &keys {
+        assert!(result.rank(&key.public_key).is_some());
+    }
+    assert_eq!(result.rank(&Key::new().public_key), None);
+
+    // a fallback producer waits for the nodes ranked before it
+    let rank = 2;
+    let fallback = keys.iter().find(|k| result.rank(&k.public_key) == Some(rank)).unwrap();
+    result.author = fallback.public_key.clone();
+    result.sign = fallback.sign(&result.hash()).unwrap();
+    let parent = now();
+    let early = Block::build(&[], 1, parent + FALLBACK_DELAY, Proof::Game(result.clone()), fallback, Vec::new());
+    assert!(early.validate());
+    assert!(!validate_rank(&early, &result, parent));
+    let late = Block::build(&[], 1, parent + 2 * FALLBACK_DELAY, Proof::Game(result.clone()), fallback, Vec::new());
+    assert!(validate_rank(&late, &result, parent));
+}
+
+#[test]
+fn draw_committee_from_the_parent() {
+    let keys: Vec<Key> = (0..20).map(|_| Key::new()).collect();
+    let ids = || keys.iter().map(|k| &k.public_key);
+
+    let drawn = committee(ids(), &[8u8; 32], 3, 5);
+    assert_eq!(drawn.len(), 5);
+    assert_eq!(committee(ids().rev(), &[8u8; 32], 3, 5), drawn);
+    assert_ne!(committee(ids(), &[9u8; 32], 3, 5), drawn);
+    assert_eq!(committee(ids(), &[8u8; 32], 3, 20).len(), 20);
+
+    let mut hl = Highlander::new();
+    hl.set_epoch(&[8u8; 32], 3);
+    hl.populate_committee(ids(), 5);
+    let mut players: Vec<&Key> = keys.iter().filter(|k| hl.is_player(&k.public_key)).collect();
+    players.sort_by_key(|k| &k.public_key);
+    assert_eq!(players.iter().map(|k| k.public_key.clone()).collect::<Vec<_>>(), drawn);
+
+    // nodes outside the committee cannot play
+    let outsider = keys.iter().find(|k| !hl.is_player(&k.public_key)).unwrap();
+    let game = hl.create_game(outsider);
+    assert!(!hl.add_commitment(game.commitment(outsider)));
+
+    let games: Vec<Game> = players.iter().map(|k| hl.create_game(k)).collect();
+    for (game, key) in games.iter().zip(&players) {
+        assert!(hl.add_commitment(game.commitment(key)));
+    }
+    for game in games {
+        assert!(hl.add_game(game).is_ok());
+    }
+    let result = hl.evaluate(players[0]).unwrap();
+    assert_eq!(result.population.len(), keys.len());
+    assert!(result.verify(Some(5)).is_ok());
+
+    // the size of the committee is part of the consensus
+    assert!(result.verify(Some(6)).is_err());
+    assert!(result.verify(None).is_err());
+
+    // the committee is checked against the population
+    let mut padded = result.clone();
+    padded.population.push(Key::new().public_key);
+    padded.population.sort();
+    padded.sign = players[0].sign(&padded.hash()).unwrap();
+    assert!(padded.verify(Some(5)).is_err());
+}
@@ -0,0 +1,71 @@
+use mccloud::{
+    blockchain::{Blockchain, Block, Join, block::now},
+    key::Key,
+};
+
+mod common;
+use common::{save_remove, play, generate};
+
+#[test]
+fn join_makes_a_member() {
+    save_remove("data/members00");
+    save_remove("data/members01");
+
+    let ka = Key::new();
+    let kb = Key::new();
+    let mut bca = Blockchain::new("data/members00");
+    let b1 = generate(&mut bca, &ka);
+    assert_eq!(bca.members(), Some(vec![ka.public_key.clone()]));
+
+    // a member, a join for a later block and a forged join are refused
+    assert!(!bca.add_join(Join::build(&ka, 2)));
+    assert!(!bca.add_join(Join::build(&kb, 100)));
+    let mut forged = Join::build(&kb, 2);
+    forged.height = 3;
+    assert!(!bca.add_join(forged));
+
+    assert!(bca.add_join(Join::build(&kb, 2)));
+    assert!(!bca.add_join(Join::build(&kb, 2)));
+    let b2 = generate(&mut bca, &ka);
+    assert_eq!(b2.joins.len(), 1);
+    let mut members = vec![ka.public_key.clone(), kb.public_key.clone()];
+    members.sort();
+    assert_eq!(b2.header.members, members);
+
+    // another node follows the members, the new member has to play the next round
+    let mut bcb = Blockchain::new("data/members01");
+    bcb.add_new_block(b1);
+    bcb.add_new_block(b2.clone());
+    assert_eq!(bcb.highest_block(), (b2.header.hash.clone(), 2));
+    assert_eq!(bcb.members(), Some(members));
+    assert!(bcb.generate_new_block(play(&ka, &b2.header.hash, 3), &ka).is_err());
+}
+
+#[test]
+fn reject_blocks_which_do_not_follow_the_members() {
+    save_remove("data/members02");
+
+    let ka = Key::new();
+    let kb = Key::new();
+    let mut bc = Blockchain::new("data/members02");
+    let b1 = generate(&mut bc, &ka);
+
+    // a block which takes in a node without its join
+    let mut claimed = b1.header.clone();
+    claimed.members.push(kb.public_key.clone());
+    claimed.members.sort();
+    let b2 = Block::build_on(&claimed, now(), play(&ka, &b1.header.hash, 2), &ka, Vec::new(), Vec::new());
+    assert!(b2.validate());
+    bc.add_new_block(b2);
+    assert_eq!(bc.highest_block(), (b1.header.hash.clone(), 1));
+
+    // a join which is not open for the block
+    let b2 = Block::build_on(&b1.header, now(), play(&ka, &b1.header.hash, 2), &ka, Vec::new(), vec![Join::build(&kb, 5)]);
+    assert!(b2.validate());
+    bc.add_new_block(b2);
+    assert_eq!(bc.highest_block(), (b1.header.hash.clone(), 1));
+
+    let b2 = Block::build_on(&b1.header, now(), play(&ka, &b1.header.hash, 2), &ka, Vec::new(), vec![Join::build(&kb, 2)]);
+    bc.add_new_block(b2.clone());
+    assert_eq!(bc.highest_block(), (b2.header.hash, 2));
+}
@@ -0,0 +1,78 @@
+use mccloud::{
+    blockchain::{Blockchain, Block, Data, Proof, merkle, block::block_hash},
+    key::Key,
+};
+
+mod common;
+use common::{save_remove, generate};
+
+#[test]
+fn proofs_for_every_leaf() {
+    for size in 1..20u8 {
+        let hashes: Vec<Vec<u8>> = (0..size).map(|i| vec![i; 32]).collect();
+        let root = merkle::root(&hashes);
+
+        for (i, h) in hashes.iter().enumerate() {
+            let path = merkle::proof(&hashes, i).unwrap();
+            assert!(merkle::verify(h, &path, &root), "size {} index {}", size, i);
+            assert!(!merkle::verify(&[0xff; 32], &path, &root));
+        }
+
+        assert!(merkle::proof(&hashes, size as usize).is_none());
+    }
+}
+
+#[test]
+fn inclusion_proof_from_chain() {
+    save_remove("data/merkle00");
+
+    let key = Key::new();
+    let mut bc = Blockchain::new("data/merkle00");
+    let data: Vec<Data> = (0..5u8).map(|i| Data::build(&key, vec![i])).collect();
+
+    for d in &data[..3] {
+        bc.add_to_cache(d.clone());
+    }
+    let b1 = generate(&mut bc, &key);
+
+    for d in &data[3..] {
+        bc.add_to_cache(d.clone());
+    }
+    let b2 = generate(&mut bc, &key);
+
+    let trusted = vec![b1.header.clone(), b2.header.clone()];
+
+    for d in &data {
+        let proof = bc.prove(&d.hash()).unwrap();
+        assert!(proof.verify(&d.hash(), &trusted));
+        assert!(!proof.verify(&d.hash(), &trusted[..0]));
+    }
+
+    let unknown = Data::build(&key, b"unknown".to_vec());
+    assert!(bc.prove(&unknown.hash()).is_none());
+
+    let proof = bc.prove(&data[0].hash()).unwrap();
+    assert!(!proof.verify(&data[1].hash(), &trusted));
+}
+
+#[test]
+fn header_fields_do_not_run_into_each_other() {
+    let key = Key::new();
+    let block = Block::build(&[], 1, 0, Proof::Elected, &key, Vec::new());
+
+    // the same bytes split differently between the proof and the author
+    let mut proof = block.header.clone();
+    proof.proof = vec![1u8];
+    let mut shifted = block.header.clone();
+    shifted.proof = Vec::new();
+    shifted.author = vec![1u8];
+    shifted.author.extend(&key.public_key);
+    assert_ne!(block_hash(&proof), block_hash(&shifted));
+
+    // the same keys split differently between the members
+    let mut joined = block.header.clone();
+    joined.members = vec![[key.public_key.clone(), vec![2u8]].concat()];
+    let mut split = block.header.clone();
+    split.members = vec![key.public_key.clone(), vec![2u8]];
+    assert_ne!(block_hash(&joined), block_hash(&split));
+}
@@ -0,0 +1,153 @@
+use mccloud::{
+    blockchain::{Block, Blockchain, block::now, fsck, store},
+    config::{Config, Retention, StoreKind},
+    key::Key,
+};
+
+mod common;
+use common::{save_remove, play, generate};
+
+fn prune_kind(kind: StoreKind, folder: &str) {
+    save_remove(folder);
+
+    let key = Key::new();
+    let mut st = store::open(&kind, folder).unwrap();
+    let mut parent: Option<Block> = None;
+    let mut blocks = Vec::new();
+
+    for height in 1..=6 {
+        let hash = parent.as_ref().map(|p| p.header.hash.clone()).unwrap_or_default();
+        let block = Block::build(&hash, height, now(), play(&key, &hash, height), &key, Vec::new());
+        st.put(&block).unwrap();
+        st.set_tip(&block.header.hash).unwrap();
+        blocks.push(block.clone());
+        parent = Some(block);
+    }
+
+    st.prune(4).unwrap();
+    assert_eq!(st.lowest(), 4);
+    assert!(st.get(&blocks[2].header.hash).unwrap().is_none());
+    assert_eq!(st.header(&blocks[2].header.hash).unwrap().unwrap().height, 3);
+    assert_eq!(st.hash_at(2).unwrap().unwrap(), blocks[1].header.hash);
+    assert_eq!(st.get_by_height(4).unwrap().unwrap().header.hash, blocks[3].header.hash);
+    assert_eq!(st.iter().count(), 3);
+    assert_eq!(st.tip(), blocks[5].header.hash);
+}
+
+#[test]
+fn prune_memory_store() {
+    prune_kind(StoreKind::Memory, "data/prune00");
+}
+
+#[test]
+fn prune_file_store() {
+    prune_kind(StoreKind::File, "data/prune01");
+}
+
+#[test]
+fn prune_sled_store() {
+    prune_kind(StoreKind::Sled, "data/prune02");
+}
+
+#[test]
+fn keep_last_blocks() {
+    save_remove("data/prune03");
+
+    let key = Key::new();
+    let mut bc = Blockchain::new("data/prune03");
+    for _ in 0..10 {
+        generate(&mut bc, &key);
+    }
+    let size = std::fs::metadata("data/prune03/bc.db").unwrap().len();
+
+    bc.prune(&Retention::Blocks(4)).unwrap();
+    assert_eq!(bc.lowest(), 7);
+    assert!(std::fs::metadata("data/prune03/bc.db").unwrap().len() < size);
+    assert!(bc.get_blocks(Vec::new(), bc.highest_block().0).is_err());
+    let highest = bc.highest_block();
+    drop(bc);
+
+    // the compacted file is opened again and still extends
+    let mut bc = Blockchain::new("data/prune03");
+    assert_eq!(bc.highest_block(), highest);
+    assert_eq!(bc.lowest(), 7);
+
+    let block = generate(&mut bc, &key);
+    assert_eq!(block.header.height, 11);
+}
+
+#[test]
+fn prune_only_the_front() {
+    save_remove("data/prune05");
+
+    let key = Key::new();
+    let mut bc = Blockchain::new("data/prune05");
+    for _ in 0..10 {
+        generate(&mut bc, &key);
+    }
+    bc.prune(&Retention::Blocks(4)).unwrap();
+    let headers = std::fs::read("data/prune05/bc.hdr").unwrap();
+
+    for _ in 0..3 {
+        generate(&mut bc, &key);
+    }
+    bc.prune(&Retention::Blocks(4)).unwrap();
+    assert_eq!(bc.lowest(), 10);
+    let highest = bc.highest_block();
+    drop(bc);
+
+    // the headers pruned before are left as they are, only the new ones are appended
+    let after = std::fs::read("data/prune05/bc.hdr").unwrap();
+    assert_eq!(&after[..headers.len()], headers.as_slice());
+
+    let report = fsck::check(std::path::Path::new("data/prune05")).unwrap();
+    assert!(report.is_clean());
+    assert_eq!((report.blocks, report.headers), (4, 9));
+
+    // the index of the cut data file is rebuilt
+    std::fs::remove_file("data/prune05/bc.hsh").unwrap();
+    let mut bc = Blockchain::new("data/prune05");
+    assert_eq!(bc.highest_block(), highest);
+    assert_eq!(bc.lowest(), 10);
+    assert_eq!(generate(&mut bc, &key).header.height, 14);
+}
+
+#[test]
+fn keep_young_blocks() {
+    save_remove("data/prune04");
+
+    let key = Key::new();
+    let mut bc = Blockchain::with_store(store::open(&StoreKind::Memory, "data/prune04").unwrap()).unwrap();
+    for _ in 0..3 {
+        generate(&mut bc, &key);
+    }
+
+    bc.prune(&Retention::Age(3600)).unwrap();
+    assert_eq!(bc.lowest(), 1);
+
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    bc.prune(&Retention::Age(0)).unwrap();
+    assert_eq!(bc.lowest(), 4);
+}
+
+#[test]
+fn retention_in_config() {
+    let config: Config = toml::from_str(
+        "host = \"127.0.0.1\"\nport = 39093\nthin = false\nfolder = \"data/\"\nclients = []\nprune = { blocks = 1000 }\n"
+    ).unwrap();
+    assert_eq!(config.prune, Some(Retention::Blocks(1000)));
+    assert_eq!(config.store, StoreKind::File);
+}
+
+#[test]
+fn reject_keeping_no_blocks() {
+    let config: Config = toml::from_str(
+        "host = \"127.0.0.1\"\nport = 39093\nthin = false\nfolder = \"data/\"\nclients = []\nprune = { blocks = 0 }\n"
+    ).unwrap();
+    assert!(config.validate().is_err());
+
+    let config: Config = toml::from_str(
+        "host = \"127.0.0.1\"\nport = 39093\nthin = false\nfolder = \"data/\"\nclients = []\nprune = { blocks = 1 }\n"
+    ).unwrap();
+    assert!(config.validate().is_ok());
+}
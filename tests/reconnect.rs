@@ -17,6 +17,7 @@ async fn two_peers() {
         thin: false,
         folder: "data/test00".into(),
         clients: Vec::new(),
+        ..Default::default()
     };
     let peer00 = Peer::<DaemonHandler>::new(config);
     let p00 = peer00.clone();
@@ -29,6 +30,7 @@ async fn two_peers() {
         clients: vec![
             ClientConfig {host: "127.0.0.1".into(), port: 39093, reconnect: true}
         ],
+        ..Default::default()
     };
     let peer01 = Peer::<DaemonHandler>::new(config);
     let p01 = peer01.clone();
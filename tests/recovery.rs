@@ -0,0 +1,115 @@
+use std::{fs::OpenOptions, io::Write, path::Path};
+
+use mccloud::{
+    blockchain::{Blockchain, journal, store},
+    config::StoreKind,
+    key::Key,
+};
+
+mod common;
+use common::{save_remove, generate};
+
+fn append(filename: &str, data: &[u8]) {
+    let mut file = OpenOptions::new().append(true).open(filename).unwrap();
+    file.write_all(data).unwrap();
+}
+
+#[test]
+fn rebuild_missing_index() {
+    save_remove("data/recovery00");
+
+    let key = Key::new();
+    let mut bc = Blockchain::new("data/recovery00");
+    for _ in 0..3 {
+        generate(&mut bc, &key);
+    }
+    let highest = bc.highest_block();
+    drop(bc);
+
+    std::fs::remove_file("data/recovery00/bc.idx").unwrap();
+
+    let bc = Blockchain::new("data/recovery00");
+    assert_eq!(bc.highest_block(), highest);
+
+    let (records, _) = journal::read_from(Path::new("data/recovery00/bc.idx"), 0).unwrap();
+    assert_eq!(records.len(), 3);
+}
+
+#[test]
+fn cut_torn_tails() {
+    save_remove("data/recovery01");
+
+    let key = Key::new();
+    let mut bc = Blockchain::new("data/recovery01");
+    for _ in 0..2 {
+        generate(&mut bc, &key);
+    }
+    let highest = bc.highest_block();
+    drop(bc);
+
+    let db_len = std::fs::metadata("data/recovery01/bc.db").unwrap().len();
+    append("data/recovery01/bc.db", &[0, 0, 4, 0, 1, 2, 3]);
+    append("data/recovery01/bc.idx", &[0, 0]);
+
+    let mut bc = Blockchain::new("data/recovery01");
+    assert_eq!(bc.highest_block(), highest);
+    assert_eq!(std::fs::metadata("data/recovery01/bc.db").unwrap().len(), db_len);
+
+    let b3 = generate(&mut bc, &key);
+    drop(bc);
+
+    let bc = Blockchain::new("data/recovery01");
+    assert_eq!(bc.highest_block(), (b3.header.hash, 3));
+}
+
+#[test]
+fn drop_entries_past_the_end() {
+    save_remove("data/recovery02");
+
+    let key = Key::new();
+    let mut bc = Blockchain::new("data/recovery02");
+    let b1 = generate(&mut bc, &key);
+    generate(&mut bc, &key);
+    drop(bc);
+
+    // the second block is only written half
+    let db_len = std::fs::metadata("data/recovery02/bc.db").unwrap().len();
+    journal::truncate(Path::new("data/recovery02/bc.db"), db_len - 10).unwrap();
+
+    let bc = Blockchain::new("data/recovery02");
+    assert_eq!(bc.highest_block(), (b1.header.hash, 1));
+}
+
+#[test]
+fn index_of_another_version() {
+    save_remove("data/recovery03");
+
+    let key = Key::new();
+    let mut bc = Blockchain::new("data/recovery03");
+    for _ in 0..3 {
+        generate(&mut bc, &key);
+    }
+    let highest = bc.highest_block();
+    drop(bc);
+
+    // an index without the version is rebuilt from the data file
+    let mut file = OpenOptions::new().write(true).open("data/recovery03/bc.hsh").unwrap();
+    file.write_all(&[0u8; 8]).unwrap();
+    drop(file);
+    let bc = Blockchain::new("data/recovery03");
+    assert_eq!(bc.highest_block(), highest);
+}
+
+#[test]
+fn refuse_data_of_another_version() {
+    save_remove("data/recovery04");
+    std::fs::create_dir_all("data/recovery04").unwrap();
+
+    // the first format wrote the blocks one after another without a length prefix
+    let data = b"\x85\xa6parent\x90\xa4hash";
+    std::fs::write("data/recovery04/bc.db", data).unwrap();
+    std::fs::write("data/recovery04/bc.idx", b"\x80").unwrap();
+
+    assert!(store::open(&StoreKind::File, "data/recovery04").is_err());
+    assert_eq!(std::fs::metadata("data/recovery04/bc.db").unwrap().len(), data.len() as u64);
+}
@@ -0,0 +1,97 @@
+use std::{collections::HashSet, sync::{Arc, atomic::{AtomicUsize, Ordering}}, time::Duration};
+
+use mccloud::{
+    blockchain::{Block, BlockHeader},
+    config::{Config, ClientConfig},
+    consensus::{Consensus, Decision, Step},
+    key::{Key, PubKey},
+    network::{peer::Peer, handler::daemon::DaemonHandler, message::Message},
+};
+
+mod common;
+use common::save_remove;
+
+mod testclient;
+use testclient::TestHandler;
+
+///
+/// A consensus whose producers never send their block.
+///
+struct Silent {
+    rounds: Arc<AtomicUsize>,
+}
+
+impl Consensus for Silent {
+    fn start_round(&mut self, _parent: &[u8], _epoch: usize, _nodes: &HashSet<PubKey>, _key: &Key) -> Step {
+        self.rounds.fetch_add(1, Ordering::SeqCst);
+
+        Step {
+            decision: Some(Decision::Wait { deadline: Duration::from_millis(200) }),
+            ..Default::default()
+        }
+    }
+
+    fn handle(&mut self, _msg: &Message, _key: &Key) -> Step {
+        Step::default()
+    }
+
+    fn timeout(&mut self, _key: &Key) -> Step {
+        Step::default()
+    }
+
+    fn round(&self) -> u64 {
+        self.rounds.load(Ordering::SeqCst) as u64
+    }
+
+    fn validate(&self, _block: &Block, _parent: Option<&BlockHeader>) -> bool {
+        false
+    }
+}
+
+#[tokio::test]
+async fn restart_round_without_block() {
+    save_remove("data/test30");
+    save_remove("data/client30");
+
+    let config = Config {
+        host: "127.0.0.1".into(),
+        port: 39293,
+        thin: false,
+        folder: "data/test30".into(),
+        clients: Vec::new(),
+        ..Default::default()
+    };
+    let peer30 = Peer::<DaemonHandler>::new(config);
+    let p30 = peer30.clone();
+
+    let rounds = Arc::new(AtomicUsize::new(0));
+    peer30.handler.set_consensus(Box::new(Silent { rounds: rounds.clone() })).await;
+
+    let config = Config {
+        host: "127.0.0.1".into(),
+        port: 39294,
+        thin: true,
+        folder: "data/client30".into(),
+        clients: vec![
+            ClientConfig {host: "127.0.0.1".into(), port: 39293, reconnect: false}
+        ],
+        ..Default::default()
+    };
+    let client = Peer::<TestHandler>::new(config);
+    let c30 = client.clone();
+
+    tokio::spawn(async move {
+        p30.listen().await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    tokio::spawn(async move {
+        c30.listen().await.unwrap();
+    });
+
+    // the share starts the first round, the node starts it over while no block arrives
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    assert!(rounds.load(Ordering::SeqCst) >= 3);
+
+    client.shutdown();
+    peer30.shutdown();
+}
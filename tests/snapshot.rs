@@ -0,0 +1,120 @@
+use std::path::Path;
+
+use mccloud::{
+    blockchain::{Blockchain, HeaderSync, Snapshot, store},
+    config::StoreKind,
+    key::Key,
+};
+
+mod common;
+use common::{save_remove, generate};
+
+#[test]
+fn bootstrap_from_snapshot() {
+    save_remove("data/snapshot00");
+    save_remove("data/snapshot01");
+
+    let key = Key::new();
+    let mut bca = Blockchain::new("data/snapshot00");
+    for _ in 0..5 {
+        generate(&mut bca, &key);
+    }
+
+    let snapshot = bca.snapshot(&key).unwrap();
+    assert!(snapshot.validate());
+    assert!(bca.snapshot(&Key::new()).is_err());
+
+    snapshot.save(Path::new("data/snapshot00")).unwrap();
+    let loaded = Snapshot::load(Path::new("data/snapshot00"), &snapshot.hash).unwrap().unwrap();
+    assert_eq!(loaded.hash, snapshot.hash);
+    assert!(Snapshot::load(Path::new("data/snapshot00"), &[0u8; 32]).unwrap().is_none());
+
+    let headers = bca.headers(1, 5).unwrap();
+    assert!(loaded.verify_headers(&headers));
+    let b6 = generate(&mut bca, &key);
+
+    let mut bcb = Blockchain::new("data/snapshot01");
+    bcb.bootstrap(&loaded, &headers).unwrap();
+    assert_eq!(bcb.highest_block().1, 5);
+    assert_eq!(bcb.lowest(), 6);
+    assert!(bcb.bootstrap(&loaded, &headers).is_err());
+
+    bcb.add_new_block(b6);
+    assert_eq!(bcb.highest_block(), bca.highest_block());
+    drop(bcb);
+
+    let bcb = Blockchain::new("data/snapshot01");
+    assert_eq!(bcb.highest_block(), bca.highest_block());
+    assert_eq!(bcb.lowest(), 6);
+}
+
+#[test]
+fn reject_tampered_snapshot() {
+    let key = Key::new();
+    let mut bc = Blockchain::with_store(store::open(&StoreKind::Memory, "data/snapshot02").unwrap()).unwrap();
+    for _ in 0..3 {
+        generate(&mut bc, &key);
+    }
+    let snapshot = bc.snapshot(&key).unwrap();
+    let headers = bc.headers(1, 3).unwrap();
+
+    let mut dropped = headers.clone();
+    dropped.remove(1);
+    assert!(!snapshot.verify_headers(&dropped));
+
+    let mut rooted = snapshot.clone();
+    rooted.root = vec![0u8; 32];
+    assert!(!rooted.validate());
+
+    let mut resigned = snapshot.clone();
+    resigned.sign = Key::new().sign(&snapshot.hash).unwrap();
+    assert!(!resigned.validate());
+
+    let mut empty = Blockchain::with_store(store::open(&StoreKind::Memory, "data/snapshot02").unwrap()).unwrap();
+    assert!(empty.bootstrap(&resigned, &headers).is_err());
+    assert_eq!(empty.highest_block().1, 0);
+    save_remove("data/snapshot02");
+}
+
+#[test]
+fn fetch_headers_in_ranges() {
+    let key = Key::new();
+    let mut bc = Blockchain::with_store(store::open(&StoreKind::Memory, "data/snapshot03").unwrap()).unwrap();
+    for _ in 0..5 {
+        generate(&mut bc, &key);
+    }
+    let snapshot = bc.snapshot(&key).unwrap();
+
+    // the snapshot stays the same size, however long the chain is
+    let size = rmp_serde::to_vec_named(&snapshot).unwrap().len();
+    for _ in 0..5 {
+        generate(&mut bc, &key);
+    }
+    // the hashes and signatures are encoded with one or two bytes per byte, so only their length varies
+    let grown = rmp_serde::to_vec_named(&bc.snapshot(&key).unwrap()).unwrap().len();
+    assert!(grown < 2 * size && size < 2 * grown);
+
+    let mut sync = HeaderSync::new(snapshot.clone(), 2);
+    assert_eq!(sync.next_range(), Some((3, 4)));
+    assert!(sync.add(bc.headers(2, 3).unwrap()).is_err());
+    sync.add(bc.headers(3, 4).unwrap()).unwrap();
+    assert_eq!(sync.next_range(), Some((1, 2)));
+    sync.add(bc.headers(1, 2).unwrap()).unwrap();
+    assert_eq!(sync.next_range(), None);
+
+    let (snapshot, headers) = sync.finish().unwrap();
+    let hashes = |headers: &[mccloud::blockchain::BlockHeader]| headers.iter().map(|h| h.hash.clone()).collect::<Vec<_>>();
+    assert_eq!(hashes(&headers), hashes(&bc.headers(1, 5).unwrap()));
+
+    let mut empty = Blockchain::with_store(store::open(&StoreKind::Memory, "data/snapshot03").unwrap()).unwrap();
+    empty.bootstrap(&snapshot, &headers).unwrap();
+    assert_eq!(empty.highest_block(), (snapshot.checkpoint.hash.clone(), 5));
+
+    // headers of another chain do not lead to the checkpoint
+    let mut other = Blockchain::with_store(store::open(&StoreKind::Memory, "data/snapshot03").unwrap()).unwrap();
+    for _ in 0..4 {
+        generate(&mut other, &key);
+    }
+    let mut sync = HeaderSync::new(snapshot, 2);
+    assert!(sync.add(other.headers(3, 4).unwrap()).is_err());
+}
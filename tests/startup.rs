@@ -14,6 +14,7 @@ async fn simple_start() {
         thin: false,
         folder: "data/test".into(),
         clients: Vec::new(),
+        ..Default::default()
     };
     let peer = Peer::<DaemonHandler>::new(config);
     let pc = peer.clone();
@@ -0,0 +1,223 @@
+use std::{fs::OpenOptions, io::Write};
+
+use mccloud::{
+    blockchain::{Block, BlockStore, Data, block::now, cache::BlockCache, store},
+    config::StoreKind,
+    key::Key,
+};
+
+mod common;
+use common::{save_remove, play};
+
+fn child(key: &Key, parent: Option<&Block>) -> Block {
+    let (hash, height) = parent
+        .map(|p| (p.header.hash.clone(), p.header.height))
+        .unwrap_or_default();
+    Block::build(&hash, height + 1, now(), play(key, &hash, height + 1), key, Vec::new())
+}
+
+fn main_chain(store: &dyn BlockStore) -> Vec<Vec<u8>> {
+    store.iter().map(|b| b.unwrap().header.hash).collect()
+}
+
+fn exercise(store: &mut dyn BlockStore) -> Vec<Block> {
+    let key = Key::new();
+    let a1 = child(&key, None);
+    let a2 = child(&key, Some(&a1));
+    let b2 = child(&key, Some(&a1));
+    let b3 = child(&key, Some(&b2));
+
+    for b in [&a1, &a2, &b2, &b3] {
+        store.put(b).unwrap();
+    }
+    assert!(store.tip().is_empty());
+    assert!(main_chain(store).is_empty());
+
+    store.set_tip(&a2.header.hash).unwrap();
+    assert_eq!(store.tip(), a2.header.hash);
+    assert_eq!(main_chain(store), vec![a1.header.hash.clone(), a2.header.hash.clone()]);
+
+    store.set_tip(&b3.header.hash).unwrap();
+    assert_eq!(main_chain(store), vec![a1.header.hash.clone(), b2.header.hash.clone(), b3.header.hash.clone()]);
+    assert_eq!(store.get_by_height(2).unwrap().unwrap().header.hash, b2.header.hash);
+
+    store.set_tip(&a2.header.hash).unwrap();
+    assert!(store.get_by_height(3).unwrap().is_none());
+    assert_eq!(store.get_by_height(2).unwrap().unwrap().header.hash, a2.header.hash);
+
+    // side branches stay available
+    assert_eq!(store.get(&b3.header.hash).unwrap().unwrap().header.hash, b3.header.hash);
+    assert_eq!(store.header(&b2.header.hash).unwrap().unwrap().height, 2);
+    assert!(store.get(&[1, 2, 3]).unwrap().is_none());
+
+    store.set_tip(&b3.header.hash).unwrap();
+    store.flush().unwrap();
+
+    vec![a1, b2, b3]
+}
+
+///
+/// Indexes the data of two blocks, removes the second one again and returns the first one.
+///
+fn exercise_data(store: &mut dyn BlockStore) -> Block {
+    let ka = Key::new();
+    let kb = Key::new();
+    let (a1, b1, a2) = (Data::build(&ka, b"a1".to_vec()), Data::build(&kb, b"b1".to_vec()), Data::build(&ka, b"a2".to_vec()));
+    let d1 = Block::build(&[], 1, now(), play(&ka, &[], 1), &ka, vec![a1.clone(), b1.clone()]);
+    let d2 = Block::build(&d1.header.hash, 2, now(), play(&ka, &d1.header.hash, 2), &ka, vec![a2.clone()]);
+
+    assert!(store.data_tip().is_empty());
+    store.index_data(&d1).unwrap();
+    store.index_data(&d2).unwrap();
+    assert_eq!(store.data_tip(), d2.header.hash);
+    assert_eq!(store.author_data(&ka.public_key).unwrap(), vec![a1.hash(), a2.hash()]);
+    assert_eq!(store.data_location(&b1.hash()).unwrap().unwrap().position, 1);
+
+    // a block indexed again after a crash adds nothing
+    store.index_data(&d2).unwrap();
+    assert_eq!(store.author_data(&ka.public_key).unwrap().len(), 2);
+
+    store.unindex_data(&d2).unwrap();
+    assert_eq!(store.data_tip(), d1.header.hash);
+    assert!(store.data_location(&a2.hash()).unwrap().is_none());
+    assert_eq!(store.author_data(&ka.public_key).unwrap(), vec![a1.hash()]);
+    assert!(store.author_data(&Key::new().public_key).unwrap().is_empty());
+
+    d1
+}
+
+///
+/// Checks that the data index of the first block of [exercise_data] is still there.
+///
+fn check_data(store: &dyn BlockStore, block: &Block) {
+    assert_eq!(store.data_tip(), block.header.hash);
+    for (i, item) in block.data.iter().enumerate() {
+        assert_eq!(store.data_location(&item.hash()).unwrap().unwrap().position, i);
+        assert_eq!(store.author_data(&item.author).unwrap(), vec![item.hash()]);
+    }
+}
+
+#[test]
+fn memory_store() {
+    let mut store = store::open(&StoreKind::Memory, "data/store00").unwrap();
+    exercise(store.as_mut());
+    let block = exercise_data(store.as_mut());
+    check_data(store.as_ref(), &block);
+}
+
+#[test]
+fn file_store() {
+    save_remove("data/store01");
+
+    let mut store = store::open(&StoreKind::File, "data/store01").unwrap();
+    let chain = exercise(store.as_mut());
+    let block = exercise_data(store.as_mut());
+    drop(store);
+
+    let store = store::open(&StoreKind::File, "data/store01").unwrap();
+    let hashes: Vec<Vec<u8>> = chain.into_iter().map(|b| b.header.hash).collect();
+    assert_eq!(main_chain(store.as_ref()), hashes);
+    check_data(store.as_ref(), &block);
+}
+
+#[test]
+fn file_store_read_only() {
+    save_remove("data/store04");
+    assert!(store::open_read_only(&StoreKind::File, "data/store04").is_err());
+    assert!(!std::path::Path::new("data/store04").exists());
+
+    let mut store = store::open(&StoreKind::File, "data/store04").unwrap();
+    let chain = exercise(store.as_mut());
+    drop(store);
+
+    // a torn tail is left as it is
+    let mut file = OpenOptions::new().append(true).open("data/store04/bc.db").unwrap();
+    file.write_all(&[0, 0, 0, 9, 1]).unwrap();
+    drop(file);
+    let db_len = std::fs::metadata("data/store04/bc.db").unwrap().len();
+
+    let mut store = store::open_read_only(&StoreKind::File, "data/store04").unwrap();
+    let hashes: Vec<Vec<u8>> = chain.iter().map(|b| b.header.hash.clone()).collect();
+    assert_eq!(main_chain(store.as_ref()), hashes);
+    assert!(store.set_tip(&chain[0].header.hash).is_err());
+    assert!(store.put(&child(&Key::new(), None)).is_err());
+    drop(store);
+
+    assert_eq!(std::fs::metadata("data/store04/bc.db").unwrap().len(), db_len);
+}
+
+#[test]
+fn sled_store() {
+    save_remove("data/store02");
+
+    let mut store = store::open(&StoreKind::Sled, "data/store02").unwrap();
+    let chain = exercise(store.as_mut());
+    let block = exercise_data(store.as_mut());
+    drop(store);
+
+    // sled may release its file lock a moment after the store is dropped
+    let mut retries = 20;
+    let store = loop {
+        match store::open(&StoreKind::Sled, "data/store02") {
+            Ok(store) => break store,
+            Err(e) if retries > 0 => {
+                log::debug!("reopen: {}", e);
+                retries -= 1;
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+            Err(e) => panic!("{}", e),
+        }
+    };
+    let hashes: Vec<Vec<u8>> = chain.into_iter().map(|b| b.header.hash).collect();
+    assert_eq!(main_chain(store.as_ref()), hashes);
+    check_data(store.as_ref(), &block);
+}
+
+#[test]
+fn file_store_grows_hash_table() {
+    save_remove("data/store03");
+
+    let key = Key::new();
+    let mut store = store::open(&StoreKind::File, "data/store03").unwrap();
+    let mut parent: Option<Block> = None;
+    let mut hashes = Vec::new();
+
+    for _ in 0..600 {
+        let block = child(&key, parent.as_ref());
+        store.put(&block).unwrap();
+        store.set_tip(&block.header.hash).unwrap();
+        hashes.push(block.header.hash.clone());
+        parent = Some(block);
+    }
+    drop(store);
+
+    let store = store::open(&StoreKind::File, "data/store03").unwrap();
+    assert_eq!(store.tip(), hashes[599]);
+
+    for (i, hash) in hashes.iter().enumerate() {
+        assert_eq!(store.header(hash).unwrap().unwrap().height, i + 1);
+        assert_eq!(store.hash_at(i + 1).unwrap().as_ref(), Some(hash));
+    }
+}
+
+#[test]
+fn block_cache_evicts_least_recently_used() {
+    let key = Key::new();
+    let b1 = child(&key, None);
+    let b2 = child(&key, Some(&b1));
+    let b3 = child(&key, Some(&b2));
+
+    let mut cache = BlockCache::new(2);
+    cache.insert(b1.clone(), true);
+    cache.insert(b2.clone(), true);
+    assert!(cache.get(&b1.header.hash).is_some());
+
+    cache.insert(b3.clone(), true);
+    assert!(cache.get(&b2.header.hash).is_none());
+    assert!(cache.get_by_height(2).is_none());
+    assert_eq!(cache.get_by_height(1).unwrap().header.hash, b1.header.hash);
+
+    cache.truncate(2);
+    assert!(cache.get_by_height(3).is_none());
+    assert!(cache.get(&b3.header.hash).is_some());
+}
@@ -61,6 +61,7 @@ impl Handler for TestHandler {
     fn handle<'a>(&'a self, peer: Peer<Self>, client: ClientPtr, msg: Message) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>>
     where
         Self: Sync + 'a {
+        #[allow(clippy::match_single_binding)]
         async fn run(_self: &TestHandler, _peer: Peer<TestHandler>, _client: ClientPtr, msg: Message) {
             match msg {
                 _ => {}
@@ -1,22 +1,17 @@
 
-use std::{time::Duration, path::Path};
+use std::time::Duration;
 
 use mccloud::{
     config::{Config, ClientConfig},
     network::{peer::Peer, handler::daemon::DaemonHandler},
 };
 
+mod common;
+use common::save_remove;
+
 mod testclient;
 use testclient::TestHandler;
 
-fn save_remove(filename: &str) {
-    let path = Path::new(filename);
-
-    if path.exists() {
-        std::fs::remove_dir_all(path).unwrap();
-    }
-}
-
 #[tokio::test]
 async fn two_peers() {
     let env = env_logger::Env::default().default_filter_or("debug");
@@ -31,6 +26,7 @@ async fn two_peers() {
         thin: false,
         folder: "data/test00".into(),
         clients: Vec::new(),
+        ..Default::default()
     };
     let peer00 = Peer::<DaemonHandler>::new(config);
     let p00 = peer00.clone();
@@ -43,6 +39,7 @@ async fn two_peers() {
         clients: vec![
             ClientConfig {host: "127.0.0.1".into(), port: 39093, reconnect: false}
         ],
+        ..Default::default()
     };
     let peer01 = Peer::<DaemonHandler>::new(config);
     let p01 = peer01.clone();
@@ -55,6 +52,7 @@ async fn two_peers() {
         clients: vec![
             ClientConfig {host: "127.0.0.1".into(), port: 39093, reconnect: false}
         ],
+        ..Default::default()
     };
     let client = Peer::<TestHandler>::new(config);
     let c00 = client.clone();